@@ -0,0 +1,481 @@
+use std::fs::File;
+use std::io::Read;
+use std::rc::Rc;
+use std::str::FromStr;
+use quaternion;
+use quaternion::Quaternion;
+use clock::lerp;
+
+/**
+ * How the value changes between two keyframes.
+ */
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Interpolation {
+    // Jumps to the next value when its keyframe is reached
+    Step,
+    Linear,
+    // Catmull-Rom spline through the keyframes
+    Cubic,
+}
+
+/**
+ * Remaps the time between two keyframes, applied before interpolating.
+ */
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Easing {
+    Linear,
+    EaseIn,
+    EaseOut,
+    EaseInOut,
+}
+
+impl Easing {
+    pub fn apply(&self, t: f32) -> f32 {
+        match *self {
+            Easing::Linear => t,
+            Easing::EaseIn => t * t,
+            Easing::EaseOut => t * (2.0 - t),
+            Easing::EaseInOut => t * t * (3.0 - 2.0 * t),
+        }
+    }
+
+    /**
+     * The easing with the given name as used in files, like ease_in.
+     */
+    pub fn parse(name: &str) -> Option<Easing> {
+        match name {
+            "linear" => Some(Easing::Linear),
+            "ease_in" => Some(Easing::EaseIn),
+            "ease_out" => Some(Easing::EaseOut),
+            "ease_in_out" => Some(Easing::EaseInOut),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Easing::Linear => "linear",
+            Easing::EaseIn => "ease_in",
+            Easing::EaseOut => "ease_out",
+            Easing::EaseInOut => "ease_in_out",
+        }
+    }
+}
+
+/**
+ * Which part of a node's transform a track animates.
+ */
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Channel {
+    Translation,
+    Rotation,
+    Scale,
+}
+
+/**
+ * A value at a point in time. Translations and scales use the first three
+ * components, rotations are quaternions stored as [w, x, y, z]. The easing
+ * applies to the way to the next keyframe.
+ */
+#[derive(Copy, Clone, Debug)]
+pub struct Keyframe {
+    pub time: f32,
+    pub value: [f32; 4],
+    pub easing: Easing,
+}
+
+#[derive(Clone, Debug)]
+pub struct Track {
+    pub node: String,
+    pub channel: Channel,
+    pub interpolation: Interpolation,
+    // Sorted by time
+    pub keyframes: Vec<Keyframe>,
+}
+
+impl Track {
+    pub fn sample(&self, time: f32) -> [f32; 4] {
+        let keys = &self.keyframes;
+        let next = match keys.iter().position(|k| k.time > time) {
+            Some(0) => return keys[0].value,
+            Some(next) => next,
+            None => return keys[keys.len() - 1].value,
+        };
+        let from = &keys[next - 1];
+        let to = &keys[next];
+        let t = from.easing.apply((time - from.time) / (to.time - from.time));
+
+        let mut value = match self.interpolation {
+            Interpolation::Step => from.value,
+            Interpolation::Linear => {
+                let mut target = to.value;
+                if self.channel == Channel::Rotation && dot4(from.value, target) < 0.0 {
+                    // Take the short way around
+                    target = scale4(target, -1.0);
+                }
+                mix4(from.value, target, t)
+            },
+            Interpolation::Cubic => {
+                let before = if next >= 2 { keys[next - 2].value } else { from.value };
+                let after = if next + 1 < keys.len() { keys[next + 1].value } else { to.value };
+                catmull_rom(before, from.value, to.value, after, t)
+            },
+        };
+        if self.channel == Channel::Rotation {
+            let length = dot4(value, value).sqrt();
+            value = scale4(value, 1.0 / length);
+        }
+        value
+    }
+}
+
+/**
+ * Translation, rotation and scale of a node relative to its parent.
+ */
+#[derive(Copy, Clone, Debug)]
+pub struct Transform {
+    pub translation: [f32; 3],
+    pub rotation: Quaternion<f32>,
+    pub scale: [f32; 3],
+}
+
+pub const IDENTITY: Transform = Transform {
+    translation: [0.0, 0.0, 0.0],
+    rotation: (1.0, [0.0, 0.0, 0.0]),
+    scale: [1.0, 1.0, 1.0],
+};
+
+impl Transform {
+    /**
+     * Mixes in weight (0.0 to 1.0) of the other transform.
+     */
+    pub fn blend(&self, other: &Transform, weight: f32) -> Transform {
+        let from = quat_to_array(self.rotation);
+        let mut to = quat_to_array(other.rotation);
+        if dot4(from, to) < 0.0 {
+            to = scale4(to, -1.0);
+        }
+        let rotation = mix4(from, to, weight);
+        let length = dot4(rotation, rotation).sqrt();
+        Transform {
+            translation: mix3(self.translation, other.translation, weight),
+            rotation: array_to_quat(scale4(rotation, 1.0 / length)),
+            scale: mix3(self.scale, other.scale, weight),
+        }
+    }
+}
+
+/**
+ * A named animation of any number of nodes.
+ */
+#[derive(Clone, Debug)]
+pub struct Clip {
+    pub name: String,
+    pub duration: f32,
+    pub tracks: Vec<Track>,
+}
+
+impl Clip {
+    /**
+     * The transform of the node at the given time, None if the clip doesn't
+     * animate it. Channels without a track keep their identity value.
+     */
+    pub fn sample(&self, node: &str, time: f32) -> Option<Transform> {
+        let mut transform = IDENTITY;
+        let mut animated = false;
+        for track in self.tracks.iter().filter(|track| track.node == node) {
+            animated = true;
+            let v = track.sample(time);
+            match track.channel {
+                Channel::Translation => transform.translation = [v[0], v[1], v[2]],
+                Channel::Rotation => transform.rotation = array_to_quat(v),
+                Channel::Scale => transform.scale = [v[0], v[1], v[2]],
+            }
+        }
+        if animated { Some(transform) } else { None }
+    }
+}
+
+struct Layer {
+    clip: Rc<Clip>,
+    time: f32,
+    speed: f32,
+    looping: bool,
+    weight: f32,
+    // Change of the weight per second, negative while fading out
+    fade: f32,
+}
+
+/**
+ * Plays clips and blends between them. Each clip played gets a layer on top
+ * of the previous ones; crossfading fades the new layer in while the older
+ * ones fade out and get removed.
+ */
+pub struct AnimationPlayer {
+    layers: Vec<Layer>,
+}
+
+impl AnimationPlayer {
+    pub fn new() -> AnimationPlayer {
+        AnimationPlayer { layers: Vec::new() }
+    }
+
+    /**
+     * Plays the clip from the start, replacing everything playing.
+     */
+    pub fn play(&mut self, clip: Rc<Clip>, speed: f32, looping: bool) {
+        self.layers.clear();
+        self.layers.push(Layer {
+            clip: clip, time: 0.0, speed: speed, looping: looping, weight: 1.0, fade: 0.0,
+        });
+    }
+
+    /**
+     * Blends from what is playing to the clip over duration seconds.
+     */
+    pub fn crossfade(&mut self, clip: Rc<Clip>, speed: f32, looping: bool, duration: f32) {
+        if self.layers.is_empty() || duration <= 0.0 {
+            return self.play(clip, speed, looping);
+        }
+        for layer in self.layers.iter_mut() {
+            layer.fade = -1.0 / duration;
+        }
+        self.layers.push(Layer {
+            clip: clip, time: 0.0, speed: speed, looping: looping, weight: 0.0,
+            fade: 1.0 / duration,
+        });
+    }
+
+    pub fn stop(&mut self) {
+        self.layers.clear();
+    }
+
+    pub fn update(&mut self, dt: f32) {
+        for layer in self.layers.iter_mut() {
+            layer.time += dt * layer.speed;
+            if layer.looping && layer.clip.duration > 0.0 {
+                layer.time %= layer.clip.duration;
+            }
+            else {
+                layer.time = layer.time.min(layer.clip.duration);
+            }
+            layer.weight = (layer.weight + layer.fade * dt).max(0.0).min(1.0);
+        }
+        self.layers.retain(|layer| layer.fade >= 0.0 || layer.weight > 0.0);
+    }
+
+    /**
+     * The blended transform of the node, None if no playing clip animates it.
+     */
+    pub fn sample(&self, node: &str) -> Option<Transform> {
+        let mut result: Option<Transform> = None;
+        for layer in self.layers.iter() {
+            if let Some(transform) = layer.clip.sample(node, layer.time) {
+                result = Some(match result {
+                    Some(below) => below.blend(&transform, layer.weight),
+                    None => transform,
+                });
+            }
+        }
+        result
+    }
+}
+
+/**
+ * Reads clips from a file, see parse_clips for the format.
+ */
+pub fn load_clips(path: &str) -> Result<Vec<Clip>, String> {
+    let mut text = String::new();
+    File::open(path).and_then(|mut file| file.read_to_string(&mut text))
+        .map_err(|e| format!("Can't read {}: {}", path, e))?;
+    parse_clips(&text).map_err(|e| format!("{}:{}", path, e))
+}
+
+/**
+ * Parses clips from text like
+ *
+ *     clip firing 0.35
+ *     track throw_arm rotation linear
+ *     key 0.0  0 0 1 -78.75  ease_in
+ *     key 0.35 0 0 1 0
+ *
+ * A clip has a name and a duration in seconds, a track names the node, the
+ * channel (translation, rotation or scale) and the interpolation (step,
+ * linear or cubic). Keys give the time and x y z for translations and
+ * scales, or the axis and the angle in degrees for rotations, optionally
+ * followed by the easing towards the next key (linear, ease_in, ease_out or
+ * ease_in_out). Everything after a # is a comment.
+ */
+pub fn parse_clips(text: &str) -> Result<Vec<Clip>, String> {
+    let mut clips: Vec<Clip> = Vec::new();
+    for (number, line) in text.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or("");
+        let words = line.split_whitespace().collect::<Vec<_>>();
+        if words.is_empty() {
+            continue;
+        }
+        let error = |message: &str| Err(format!("{}: {}", number + 1, message));
+        match words[0] {
+            "clip" if words.len() == 3 => {
+                clips.push(Clip {
+                    name: words[1].to_string(),
+                    duration: parse_number(words[2], number)?,
+                    tracks: Vec::new(),
+                });
+            },
+            "track" if words.len() == 4 => {
+                let channel = match words[2] {
+                    "translation" => Channel::Translation,
+                    "rotation" => Channel::Rotation,
+                    "scale" => Channel::Scale,
+                    _ => return error("unknown channel"),
+                };
+                let interpolation = match words[3] {
+                    "step" => Interpolation::Step,
+                    "linear" => Interpolation::Linear,
+                    "cubic" => Interpolation::Cubic,
+                    _ => return error("unknown interpolation"),
+                };
+                match clips.last_mut() {
+                    Some(clip) => clip.tracks.push(Track {
+                        node: words[1].to_string(),
+                        channel: channel,
+                        interpolation: interpolation,
+                        keyframes: Vec::new(),
+                    }),
+                    None => return error("track outside of a clip"),
+                }
+            },
+            "key" => {
+                let track = match clips.last_mut().and_then(|clip| clip.tracks.last_mut()) {
+                    Some(track) => track,
+                    None => return error("key outside of a track"),
+                };
+                let count = if track.channel == Channel::Rotation { 4 } else { 3 };
+                if words.len() != count + 2 && words.len() != count + 3 {
+                    return error("wrong number of values");
+                }
+                let time = parse_number(words[1], number)?;
+                if track.keyframes.last().map_or(false, |last| last.time >= time) {
+                    return error("keys have to be in order");
+                }
+                let mut v = [0.0f32; 4];
+                for i in 0..count {
+                    v[i] = parse_number(words[2 + i], number)?;
+                }
+                let value = if track.channel == Channel::Rotation {
+                    let length = (v[0] * v[0] + v[1] * v[1] + v[2] * v[2]).sqrt();
+                    if length == 0.0 {
+                        return error("rotation axis is zero");
+                    }
+                    let axis = [v[0] / length, v[1] / length, v[2] / length];
+                    quat_to_array(quaternion::axis_angle(axis, v[3].to_radians()))
+                }
+                else {
+                    v
+                };
+                let easing = match words.get(count + 2) {
+                    None => Easing::Linear,
+                    Some(name) => match Easing::parse(name) {
+                        Some(easing) => easing,
+                        None => return error("unknown easing"),
+                    },
+                };
+                track.keyframes.push(Keyframe { time: time, value: value, easing: easing });
+            },
+            _ => return error("expected clip, track or key"),
+        }
+    }
+    for clip in clips.iter() {
+        if clip.tracks.iter().any(|track| track.keyframes.is_empty()) {
+            return Err(format!("clip {} has a track without keys", clip.name));
+        }
+    }
+    Ok(clips)
+}
+
+fn parse_number<T: FromStr>(word: &str, number: usize) -> Result<T, String> {
+    word.parse().map_err(|_| format!("{}: invalid number {}", number + 1, word))
+}
+
+fn quat_to_array(q: Quaternion<f32>) -> [f32; 4] {
+    [q.0, q.1[0], q.1[1], q.1[2]]
+}
+
+fn array_to_quat(v: [f32; 4]) -> Quaternion<f32> {
+    (v[0], [v[1], v[2], v[3]])
+}
+
+fn dot4(a: [f32; 4], b: [f32; 4]) -> f32 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2] + a[3] * b[3]
+}
+
+fn scale4(a: [f32; 4], s: f32) -> [f32; 4] {
+    [a[0] * s, a[1] * s, a[2] * s, a[3] * s]
+}
+
+fn mix4(a: [f32; 4], b: [f32; 4], t: f32) -> [f32; 4] {
+    [lerp(a[0], b[0], t), lerp(a[1], b[1], t), lerp(a[2], b[2], t), lerp(a[3], b[3], t)]
+}
+
+fn mix3(a: [f32; 3], b: [f32; 3], t: f32) -> [f32; 3] {
+    [lerp(a[0], b[0], t), lerp(a[1], b[1], t), lerp(a[2], b[2], t)]
+}
+
+/**
+ * Point on the Catmull-Rom spline through p1 and p2 at t.
+ */
+pub fn catmull_rom(p0: [f32; 4], p1: [f32; 4], p2: [f32; 4], p3: [f32; 4], t: f32) -> [f32; 4] {
+    let t2 = t * t;
+    let t3 = t2 * t;
+    let mut result = [0.0; 4];
+    for i in 0..4 {
+        result[i] = 0.5 * (2.0 * p1[i] + (p2[i] - p0[i]) * t +
+            (2.0 * p0[i] - 5.0 * p1[i] + 4.0 * p2[i] - p3[i]) * t2 +
+            (3.0 * p1[i] - p0[i] - 3.0 * p2[i] + p3[i]) * t3);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error_of(text: &str) -> String {
+        parse_clips(text).err().expect("should not parse")
+    }
+
+    #[test]
+    fn keys_interpolate_with_easing() {
+        let clips = parse_clips("
+            clip move 2   # seconds
+            track arm translation linear
+            key 0 0 0 0 ease_in_out
+            key 2 4 0 0
+        ").unwrap();
+        assert_eq!(clips.len(), 1);
+        let track = &clips[0].tracks[0];
+        assert_eq!(track.keyframes[0].easing, Easing::EaseInOut);
+        assert_eq!(track.keyframes[1].easing, Easing::Linear);
+        let middle = clips[0].sample("arm", 1.0).unwrap();
+        assert!((middle.translation[0] - 2.0).abs() < 1e-5);
+        assert!(clips[0].sample("other", 1.0).is_none());
+    }
+
+    #[test]
+    fn errors_name_the_line() {
+        assert_eq!(error_of("clip a 1\ntrack arm rotation linear\nkey 0 0 0 1 0 wobbly"),
+                   "3: unknown easing");
+        assert_eq!(error_of("clip a 1\ntrack arm scale linear\nkey 0.5 1 1 1\nkey 0.5 2 2 2"),
+                   "4: keys have to be in order");
+        assert_eq!(error_of("clip a 1\ntrack arm rotation linear\nkey 0 0 0 0 90"),
+                   "3: rotation axis is zero");
+        assert_eq!(error_of("clip a 1\ntrack arm spin linear"), "2: unknown channel");
+        assert_eq!(error_of("track arm scale step"), "1: track outside of a clip");
+        assert_eq!(error_of("clip a 1\nkey 0 1 1 1"), "2: key outside of a track");
+        assert_eq!(error_of("clip a 1\ntrack arm scale step\nkey 0 1 1"),
+                   "3: wrong number of values");
+        assert_eq!(error_of("clip a x"), "1: invalid number x");
+        assert_eq!(error_of("clip a 1\ntrack arm scale step"), "clip a has a track without keys");
+    }
+}
@@ -0,0 +1,189 @@
+use std::f32::consts::PI;
+use std::rc::Rc;
+use config::Config;
+use input::{Action, Input};
+use terrain::Terrain;
+
+// Density of air at sea level and 15°C, in kg/m³
+const SEA_LEVEL_DENSITY: f32 = 1.225;
+// Height over which the air density drops by a factor of e, in meters
+const SCALE_HEIGHT: f32 = 8500.0;
+// Height above the ground the configured wind speed is measured at, in meters
+const REFERENCE_HEIGHT: f32 = 10.0;
+// How much the keys change the wind
+const DIRECTION_STEP: f32 = PI / 12.0;
+const SPEED_STEP: f32 = 1.0;
+
+/**
+ * The air the projectiles fly through.
+ *
+ * The wind has a base speed and direction, can gust and gets stronger with
+ * the height above the ground following the wind profile power law. Gusts are a sum of sines,
+ * so the same time always gives the same wind.
+ */
+pub struct Atmosphere {
+    // Where the wind blows to, radians around the y axis, 0.0 is +x
+    pub direction: f32,
+    // Speed at REFERENCE_HEIGHT in m/s
+    pub speed: f32,
+    // Gusts add up to this fraction of the speed
+    pub gust_strength: f32,
+    pub gusting: bool,
+    // Seconds between the strongest gusts
+    pub gust_period: f32,
+    // Exponent of the wind profile power law, 0.0 makes it independent of height
+    pub shear_exponent: f32,
+    // Multiplies the air density, e.g. for hot days or high altitude fields
+    pub density_factor: f32,
+    // The wind profile starts at its surface
+    terrain: Rc<Terrain>,
+    time: f32,
+}
+
+impl Atmosphere {
+    pub fn from_config(config: &Config, terrain: Rc<Terrain>) -> Atmosphere {
+        Atmosphere {
+            direction: config.get("wind", "direction", 90.0f32).to_radians(),
+            speed: config.get("wind", "speed", 3.0),
+            gust_strength: config.get("wind", "gust_strength", 0.3),
+            gusting: config.get("wind", "gusting", true),
+            gust_period: config.get("wind", "gust_period", 8.0),
+            shear_exponent: config.get("wind", "shear_exponent", 0.143),
+            density_factor: config.get("wind", "density_factor", 1.0),
+            terrain: terrain,
+            time: 0.0,
+        }
+    }
+
+    pub fn update(&mut self, dt: f32) {
+        self.time += dt;
+    }
+
+    /**
+     * How strong the wind currently is relative to its base speed.
+     */
+    pub fn gust_factor(&self) -> f32 {
+        if !self.gusting || self.gust_period <= 0.0 {
+            return 1.0;
+        }
+        let t = 2.0 * PI * self.time / self.gust_period;
+        let wave = 0.6 * t.sin() + 0.3 * (2.3 * t + 1.3).sin() + 0.1 * (5.7 * t + 0.4).sin();
+        // Shift the wave to 0.0..1.0, gusts only add to the wind
+        1.0 + self.gust_strength * (wave + 1.0) * 0.5
+    }
+
+    /**
+     * The wind velocity at the given position, in m/s.
+     */
+    pub fn wind_at(&self, position: [f32; 3]) -> [f32; 3] {
+        let ground = self.terrain.height_at(position[0], position[2]);
+        let height = (position[1] - ground).max(0.1);
+        let profile = (height / REFERENCE_HEIGHT).powf(self.shear_exponent);
+        let speed = self.speed * profile * self.gust_factor();
+        [self.direction.cos() * speed, 0.0, self.direction.sin() * speed]
+    }
+
+    /**
+     * Density of the air at the given altitude, in kg/m³.
+     */
+    pub fn density_at(&self, height: f32) -> f32 {
+        SEA_LEVEL_DENSITY * self.density_factor * (-height.max(0.0) / SCALE_HEIGHT).exp()
+    }
+
+    /**
+     * Short description of the wind 10m above the ground, for the window title.
+     */
+    pub fn describe(&self) -> String {
+        let ground = self.terrain.height_at(0.0, 0.0);
+        let wind = self.wind_at([0.0, ground + REFERENCE_HEIGHT, 0.0]);
+        let speed = (wind[0] * wind[0] + wind[2] * wind[2]).sqrt();
+        let degrees = self.direction.to_degrees().rem_euclid(360.0);
+        let gusts = if self.gusting { ", gusting" } else { "" };
+        format!("Wind {:.1} m/s towards {:.0}°{}", speed, degrees, gusts)
+    }
+
+    /**
+     * turn_wind_left and turn_wind_right turn the wind, weaker_wind and
+     * stronger_wind change its speed, toggle_gusts switches gusts on and off.
+     */
+    pub fn process_input(&mut self, input: &Input) {
+        match *input {
+            Input::Pressed(Action::TurnWindLeft) => {
+                self.direction -= DIRECTION_STEP;
+            },
+            Input::Pressed(Action::TurnWindRight) => {
+                self.direction += DIRECTION_STEP;
+            },
+            Input::Pressed(Action::WeakerWind) => {
+                self.speed = (self.speed - SPEED_STEP).max(0.0);
+            },
+            Input::Pressed(Action::StrongerWind) => {
+                self.speed += SPEED_STEP;
+            },
+            Input::Pressed(Action::ToggleGusts) => {
+                self.gusting = !self.gusting;
+            },
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn atmosphere(text: &str, terrain: &str) -> Atmosphere {
+        let terrain = Rc::new(Terrain::from_config(&Config::parse(terrain)));
+        Atmosphere::from_config(&Config::parse(text), terrain)
+    }
+
+    fn speed(wind: [f32; 3]) -> f32 {
+        (wind[0] * wind[0] + wind[1] * wind[1] + wind[2] * wind[2]).sqrt()
+    }
+
+    #[test]
+    fn gusts_only_add_up_to_their_strength() {
+        let mut air = atmosphere("[wind]\ngust_strength = 0.5\ngust_period = 4", "");
+        let (mut low, mut high) = (f32::MAX, f32::MIN);
+        for _ in 0..2000 {
+            air.update(0.01);
+            low = low.min(air.gust_factor());
+            high = high.max(air.gust_factor());
+        }
+        assert!(low >= 1.0 && high <= 1.5, "{} to {}", low, high);
+        // Over a few periods the gusts come close to both bounds
+        assert!(low < 1.05 && high > 1.45, "{} to {}", low, high);
+
+        air.gusting = false;
+        assert_eq!(air.gust_factor(), 1.0);
+    }
+
+    #[test]
+    fn wind_grows_with_the_height_above_the_ground() {
+        let air = atmosphere("[wind]\ngusting = false\nspeed = 4\ndirection = 0", "");
+        let (x, z) = (-120.0, 80.0);
+        let ground = air.terrain.height_at(x, z);
+        assert!(ground > 0.5, "{} high", ground);
+
+        let reference = air.wind_at([x, ground + REFERENCE_HEIGHT, z]);
+        assert!((reference[0] - 4.0).abs() < 1e-4 && reference[2].abs() < 1e-4);
+        let high = air.wind_at([x, ground + 4.0 * REFERENCE_HEIGHT, z]);
+        assert!((speed(high) - 4.0 * 4.0f32.powf(0.143)).abs() < 1e-3);
+        // Below the ground it keeps the weak wind right above it
+        let low = speed(air.wind_at([x, ground - 1.0, z]));
+        assert!(low > 0.0 && low < 2.5, "{} m/s", low);
+
+        let calm = atmosphere("[wind]\ngusting = false\nshear_exponent = 0", "");
+        assert_eq!(speed(calm.wind_at([x, 0.5, z])), speed(calm.wind_at([x, 200.0, z])));
+    }
+
+    #[test]
+    fn air_thins_out_with_altitude() {
+        let air = atmosphere("[wind]\ndensity_factor = 2", "");
+        assert!((air.density_at(0.0) - 2.0 * SEA_LEVEL_DENSITY).abs() < 1e-6);
+        let thinner = 2.0 * SEA_LEVEL_DENSITY / ::std::f32::consts::E;
+        assert!((air.density_at(SCALE_HEIGHT) - thinner).abs() < 1e-4);
+        assert_eq!(air.density_at(-10.0), air.density_at(0.0));
+        assert!(air.density_at(100.0) < air.density_at(50.0));
+    }
+}
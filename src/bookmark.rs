@@ -0,0 +1,185 @@
+use std::fs::File;
+use std::io::{Read, Write};
+use vecmath::Vector3;
+use projection::{Projection, ProjectionMode};
+
+pub const BOOKMARK_FILE: &'static str = "camera.bookmarks";
+// store_bookmark_1 to 9 store a bookmark
+pub const SLOTS: usize = 9;
+
+/**
+ * A stored view: where the camera is, the direction it looks in, which way
+ * is up and how it projects.
+ */
+#[derive(Copy, Clone, Debug)]
+pub struct Bookmark {
+    pub position: Vector3<f32>,
+    // Both of unit length and perpendicular
+    pub direction: Vector3<f32>,
+    pub up: Vector3<f32>,
+    pub projection: Projection,
+}
+
+/**
+ * Numbered slots of bookmarks, kept in camera.bookmarks between runs.
+ */
+pub struct Bookmarks {
+    slots: [Option<Bookmark>; SLOTS],
+}
+
+impl Bookmarks {
+    /**
+     * The bookmarks saved in the file, none if there is no file.
+     */
+    pub fn load(path: &str) -> Bookmarks {
+        let mut bookmarks = Bookmarks { slots: [None; SLOTS] };
+        let mut text = String::new();
+        if File::open(path).and_then(|mut file| file.read_to_string(&mut text)).is_err() {
+            return bookmarks;
+        }
+        for (number, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("");
+            if line.trim().is_empty() {
+                continue;
+            }
+            match parse_bookmark(line) {
+                Some((slot, bookmark)) => bookmarks.slots[slot] = Some(bookmark),
+                None => println!("{}:{}: invalid bookmark", path, number + 1),
+            }
+        }
+        bookmarks
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        let mut text = String::from(
+            "# slot  position  direction  up  projection  fov  near  far  ortho_height\n"
+        );
+        for (slot, bookmark) in self.slots.iter().enumerate() {
+            if let Some(ref b) = *bookmark {
+                let mode = match b.projection.mode {
+                    ProjectionMode::Perspective => "perspective",
+                    ProjectionMode::Orthographic => "orthographic",
+                };
+                text.push_str(&format!(
+                    "{}  {} {} {}  {} {} {}  {} {} {}  {}  {}  {}  {}  {}\n", slot + 1,
+                    b.position[0], b.position[1], b.position[2],
+                    b.direction[0], b.direction[1], b.direction[2],
+                    b.up[0], b.up[1], b.up[2], mode, b.projection.fov.to_degrees(),
+                    b.projection.near, b.projection.far, b.projection.ortho_height
+                ));
+            }
+        }
+        File::create(path).and_then(|mut file| file.write_all(text.as_bytes()))
+            .map_err(|e| format!("Can't write {}: {}", path, e))
+    }
+
+    /**
+     * The bookmark in the slot, counting from 0.
+     */
+    pub fn get(&self, slot: usize) -> Option<Bookmark> {
+        self.slots.get(slot).and_then(|bookmark| *bookmark)
+    }
+
+    pub fn set(&mut self, slot: usize, bookmark: Bookmark) {
+        if slot < SLOTS {
+            self.slots[slot] = Some(bookmark);
+        }
+    }
+}
+
+/**
+ * Parses a line written by Bookmarks::save.
+ */
+fn parse_bookmark(line: &str) -> Option<(usize, Bookmark)> {
+    let words = line.split_whitespace().collect::<Vec<_>>();
+    if words.len() != 15 {
+        return None;
+    }
+    let slot = words[0].parse::<usize>().ok()?;
+    if slot < 1 || slot > SLOTS {
+        return None;
+    }
+    let mut v = [0.0f32; 9];
+    for i in 0..9 {
+        v[i] = words[1 + i].parse().ok()?;
+    }
+    let mode = match words[10] {
+        "perspective" => ProjectionMode::Perspective,
+        "orthographic" => ProjectionMode::Orthographic,
+        _ => return None,
+    };
+    let fov: f32 = words[11].parse().ok()?;
+    let projection = Projection {
+        mode: mode,
+        fov: fov.to_radians(),
+        near: words[12].parse().ok()?,
+        far: words[13].parse().ok()?,
+        ortho_height: words[14].parse().ok()?,
+    };
+    Some((slot - 1, Bookmark {
+        position: [v[0], v[1], v[2]],
+        direction: [v[3], v[4], v[5]],
+        up: [v[6], v[7], v[8]],
+        projection: projection,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+
+    fn temp_file(name: &str) -> String {
+        let file = env::temp_dir().join(format!("catapult-{}-{}", name, std::process::id()));
+        file.to_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn bookmarks_survive_saving() {
+        let projection = Projection {
+            mode: ProjectionMode::Orthographic,
+            fov: 50.0f32.to_radians(),
+            near: 0.25,
+            far: 800.0,
+            ortho_height: 12.5,
+        };
+        let bookmark = Bookmark {
+            position: [-5.0, 1.5, 2.25],
+            direction: [0.0, -1.0, 0.0],
+            up: [1.0, 0.0, 0.0],
+            projection: projection,
+        };
+        let mut bookmarks = Bookmarks::load(&temp_file("missing"));
+        bookmarks.set(0, bookmark);
+        bookmarks.set(8, Bookmark { position: [7.0, 0.0, 0.0], .. bookmark });
+        bookmarks.set(SLOTS, bookmark);
+
+        let file = temp_file("bookmarks");
+        bookmarks.save(&file).unwrap();
+        let loaded = Bookmarks::load(&file);
+        let _ = fs::remove_file(&file);
+
+        for slot in 0..SLOTS {
+            assert_eq!(loaded.get(slot).is_some(), slot == 0 || slot == 8, "slot {}", slot);
+        }
+        let first = loaded.get(0).unwrap();
+        assert_eq!(first.position, bookmark.position);
+        assert_eq!(first.direction, bookmark.direction);
+        assert_eq!(first.up, bookmark.up);
+        assert_eq!(first.projection.mode, ProjectionMode::Orthographic);
+        assert!((first.projection.fov - projection.fov).abs() < 1e-6);
+        assert_eq!(first.projection.ortho_height, 12.5);
+        assert_eq!(loaded.get(8).unwrap().position, [7.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn broken_lines_are_skipped() {
+        assert!(parse_bookmark("1  0 0 0  1 0 0  0 1 0  perspective 60 0.1 1000").is_none());
+        assert!(parse_bookmark("0  0 0 0  1 0 0  0 1 0  perspective 60 0.1 1000 20").is_none());
+        assert!(parse_bookmark("10  0 0 0  1 0 0  0 1 0  perspective 60 0.1 1000 20").is_none());
+        assert!(parse_bookmark("1  0 0 0  1 0 0  0 1 0  fisheye 60 0.1 1000 20").is_none());
+        assert_eq!(parse_bookmark("9  0 0 0  1 0 0  0 1 0  perspective 60 0.1 1000 20")
+                   .map(|(slot, _)| slot), Some(8));
+    }
+}
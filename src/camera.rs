@@ -1,345 +1,1129 @@
-extern crate glium;
-use glium::glutin;
 use quaternion;
+use quaternion::Quaternion;
 use vecmath;
+use clock::lerp;
+use config::Config;
+use collision::{Aabb, Collider};
+use constraint::Constraints;
+use terrain::Terrain;
+use std::sync::mpsc::Receiver;
+use bookmark::{Bookmark, Bookmarks, BOOKMARK_FILE};
+use chase::Chase;
+use path::{CameraPath, PATH_FILE};
+use projection::{Projection, ProjectionMode};
+use firing::FiringEvent;
+use input::{Action, Input};
+use world::WorldEvent;
+
+// The camera can't look further up or down than this, so it never flips
+const MAX_PITCH: f32 = 1.5;
+// The scroll wheel changes the moving speed by this factor per line
+const SCROLL_FACTOR: f32 = 1.2;
+const MIN_MOVING_SPEED: f32 = 0.5;
+const MAX_MOVING_SPEED: f32 = 100.0;
+// How quickly the camera speeds up and slows down, per second. Being equal
+// the camera levels out at its moving speed.
+const ACCELERATION: f32 = 8.0;
+const DAMPING: f32 = 8.0;
+// Holding sprint multiplies the moving speed
+const SPRINT_FACTOR: f32 = 3.0;
+// Keyframes dropped in free flight look at the point this far ahead
+const LOOK_DISTANCE: f32 = 10.0;
+// Seconds it takes to fly to a recalled bookmark
+const TRANSITION_TIME: f32 = 1.0;
+// How far the orbit camera stays from its focus
+const MIN_DISTANCE: f32 = 1.0;
+const MAX_DISTANCE: f32 = 500.0;
+// Meters the focus moves per pixel dragged, per meter of distance
+const PAN_PER_PIXEL: f32 = 0.0015;
+// Framing leaves this much room around the part
+pub const FRAME_MARGIN: f32 = 1.2;
+// Seconds a message stays in the window title
+const MESSAGE_TIME: f32 = 3.0;
+
+/**
+ * How the mouse turns the camera, read from the [camera] section, and where
+ * the camera path and the bookmarks are kept.
+ */
+#[derive(Clone, Debug)]
+pub struct CameraConfig {
+    // Radians per pixel the mouse moves
+    pub mouse_sensitivity: f32,
+    // Moving the mouse up looks down
+    pub invert_y: bool,
+    pub projection: Projection,
+    pub constraints: Constraints,
+    pub path_file: String,
+    pub bookmark_file: String,
+}
+
+impl CameraConfig {
+    pub fn from_config(config: &Config) -> CameraConfig {
+        CameraConfig {
+            mouse_sensitivity: config.get("camera", "mouse_sensitivity", 0.003),
+            invert_y: config.get("camera", "invert_y", false),
+            projection: Projection::from_config(config),
+            constraints: Constraints::from_config(config),
+            path_file: PATH_FILE.to_string(),
+            bookmark_file: BOOKMARK_FILE.to_string(),
+        }
+    }
+}
 
 /**
  * Controlls the Camera.
  *
- * The rotation isnt quite right, it always tries to rotate by the global axis,
- * not the local ones.
- * Havent got it correctly working yet, hence the commented code.
+ * The orientation is a quaternion turning the local axes of the camera into
+ * world ones: locally the camera looks along x, y is up and z to the right.
+ * Yaw, pitch and roll rotate around these local axes.
+ *
+ * The camera speeds up while a movement action is held and slows down once
+ * it is let go, holding sprint makes it faster.
+ *
+ * mouse_look toggles mouse-look: the cursor gets grabbed or hidden and kept
+ * in the window, and moving the mouse turns the camera. The scroll wheel
+ * changes how fast the camera moves.
+ *
+ * orbit toggles orbiting around the selected part, by default the whole
+ * catapult. Holding orbit_drag while moving the mouse turns around it,
+ * pan_drag pans and the scroll wheel zooms. select_next selects the next
+ * part and frame_selected frames the selected one.
+ *
+ * chase toggles chasing the thrown projectiles, afterwards the camera flies
+ * back to where it was.
+ *
+ * add_keyframe drops a keyframe of the camera path at the camera,
+ * remove_keyframe removes the last one and play_path flies along the path.
+ *
+ * zoom_in and zoom_out change the field of view, or the size of the
+ * orthographic view that toggle_projection switches to.
+ *
+ * store_bookmark_1 to 9 store the view as a bookmark, bookmark_1 to 9 fly
+ * back to it.
+ *
+ * toggle_constraints toggles keeping the camera above the ground, within the
+ * world and out of everything solid, see Constraints.
  */
 
 pub struct CameraState {
+    // Width of the window divided by its height
     aspect_ratio: f32,
     position: (f32, f32, f32),
-    direction: (f32, f32, f32),
-    up: (f32, f32, f32),
+    orientation: Quaternion<f32>,
+    // State of the last simulation step, used to interpolate the view
+    previous_position: (f32, f32, f32),
+    previous_orientation: Quaternion<f32>,
     moving_up: bool,
     moving_left: bool,
     moving_down: bool,
     moving_right: bool,
     moving_forward: bool,
     moving_backward: bool,
+    // Top speed in m/s
     moving_speed: f32,
+    // Meters per second the free camera moves
+    velocity: [f32; 3],
+    sprinting: bool,
     rotating_up: bool,
     rotating_left: bool,
     rotating_down: bool,
     rotating_right: bool,
+    rolling_left: bool,
+    rolling_right: bool,
     rotating_speed: f32,
+    config: CameraConfig,
+    mouse_look: bool,
+    // Where the cursor was at the last mouse event
+    last_mouse: Option<(i32, i32)>,
+    // Asked for the cursor to be put back in the middle, main hasn't done it yet
+    warp_requested: bool,
+    // Waiting for the mouse event of the cursor arriving in the middle
+    recentering: bool,
+    // What changed last and the seconds left to show it
+    message: Option<(String, f32)>,
+    window_size: (u32, u32),
+    // Turned by the mouse since the last update
+    mouse_yaw: f32,
+    mouse_pitch: f32,
+    // Dragged with the right mouse button since the last update, in pixels
+    mouse_pan: (f32, f32),
+    // Lines scrolled since the last update
+    zoom: f32,
+    orbit: Option<Orbit>,
+    // Held mouse buttons, for orbiting and panning
+    dragging: bool,
+    panning: bool,
+    // Parts the camera can focus on, and which one is selected
+    targets: Vec<(String, Aabb)>,
+    selected: usize,
+    chase: Chase,
+    // Id, position and velocity of the newest projectile
+    projectile: Option<(u64, [f32; 3], [f32; 3])>,
+    // Where the camera was before a chase started
+    saved_pose: Option<((f32, f32, f32), Quaternion<f32>, Option<Orbit>)>,
+    projection: Projection,
+    previous_projection: Projection,
+    path: CameraPath,
+    bookmarks: Bookmarks,
+    transition: Option<Transition>,
+    constraints: Constraints,
 }
 
-fn normalize(vec: [f32; 3]) -> [f32; 3] {
-    let len = vec[0] * vec[0] + vec[1] * vec[1] + vec[2] * vec[2];
-    let len = len.sqrt();
-    [vec[0] / len, vec[1] / len, vec[2] / len]
+/**
+ * Flies from one view to another, eased in and out.
+ */
+struct Transition {
+    from: ((f32, f32, f32), Quaternion<f32>, Projection),
+    to: ((f32, f32, f32), Quaternion<f32>, Projection),
+    // Seconds since it started
+    time: f32,
 }
 
-// fn rotate(vec: (f32, f32, f32), rot: (f32, f32, f32)) -> (f32, f32, f32) {
-    // let vec_vec: vecmath::Vector3<f32> = normalize(vec);
-    // let vec_rot: vecmath::Vector3<f32> = normalize(rot);
-    // let q = quaternion::rotation_from_to(vec_vec, vec_rot);
-    // let rotated_vec = quaternion::rotate_vector(q, vec_vec);
-    // (rotated_vec[0], rotated_vec[1], rotated_vec[2])
-
-    // Use Rodrigues formula
-    // let x: f32 = 0.0 * vec.0 + -rot.2 * vec.1 + rot.1 * vec.2;
-    // let y: f32 = rot.2 * vec.0 + 0.0 * vec.1 + -rot.0 * vec.2;
-    // let z: f32 = -rot.1 * vec.0 + rot.0 * vec.1 + 0.0 * vec.2;
-    // (x, y, z)
-
-// }
+/**
+ * The camera looks at the focus from distance away, from the direction yaw
+ * and pitch say.
+ */
+#[derive(Copy, Clone, Debug)]
+struct Orbit {
+    focus: [f32; 3],
+    distance: f32,
+    yaw: f32,
+    pitch: f32,
+}
 
 impl CameraState {
-    pub fn new() -> CameraState {
+    pub fn new(config: CameraConfig) -> CameraState {
+        let orientation = orientation_towards((2.0, 0.0, 0.0));
+        let path = CameraPath::load(&config.path_file);
+        let bookmarks = Bookmarks::load(&config.bookmark_file);
         CameraState {
             aspect_ratio: 1024.0 / 768.0,
             position: (-5.0, 1.0, 1.0),
-            direction: (2.0, 0.0, 0.0),
-            up: (0.0, 1.0, 0.0),
+            orientation: orientation,
+            previous_position: (-5.0, 1.0, 1.0),
+            previous_orientation: orientation,
             moving_up: false,
             moving_left: false,
             moving_down: false,
             moving_right: false,
             moving_forward: false,
             moving_backward: false,
-            moving_speed: 0.05,
+            moving_speed: 3.0,
+            velocity: [0.0, 0.0, 0.0],
+            sprinting: false,
             rotating_up: false,
             rotating_left: false,
             rotating_down: false,
             rotating_right: false,
-            rotating_speed: 0.05,
+            rolling_left: false,
+            rolling_right: false,
+            rotating_speed: 3.0,
+            mouse_look: false,
+            last_mouse: None,
+            warp_requested: false,
+            recentering: false,
+            message: None,
+            window_size: (1024, 768),
+            mouse_yaw: 0.0,
+            mouse_pitch: 0.0,
+            mouse_pan: (0.0, 0.0),
+            zoom: 0.0,
+            orbit: None,
+            dragging: false,
+            panning: false,
+            targets: Vec::new(),
+            selected: 0,
+            chase: Chase::new(),
+            projectile: None,
+            saved_pose: None,
+            projection: config.projection,
+            previous_projection: config.projection,
+            path: path,
+            bookmarks: bookmarks,
+            transition: None,
+            constraints: config.constraints,
+            config: config,
         }
     }
 
-    pub fn set_position(&mut self, pos: (f32, f32, f32)) {
-        self.position = pos;
+    pub fn set_window_size(&mut self, width: u32, height: u32) {
+        self.window_size = (width, height);
+        self.aspect_ratio = width as f32 / height.max(1) as f32;
     }
 
-    pub fn set_direction(&mut self, dir: (f32, f32, f32)) {
-        self.direction = dir;
+    /**
+     * The point the cursor gets put back to during mouse-look, in pixels.
+     */
+    pub fn window_center(&self) -> (i32, i32) {
+        (self.window_size.0 as i32 / 2, self.window_size.1 as i32 / 2)
     }
 
-    pub fn set_aspect_ratio(&mut self, aspect_ratio: f32) {
-        self.aspect_ratio = aspect_ratio;
+    pub fn mouse_look(&self) -> bool {
+        self.mouse_look
     }
 
-    pub fn get_perspective(&self) -> [[f32; 4]; 4] {
-        let fov: f32 = 3.141592 / 2.0;
-        let zfar = 1024.0;
-        let znear = 0.1;
+    /**
+     * What the window title should show about the camera: the last change
+     * for a few seconds after it happened.
+     */
+    pub fn status(&self) -> Option<String> {
+        self.message.as_ref().map(|&(ref message, _)| message.clone())
+    }
 
-        let f = 1.0 / (fov / 2.0).tan();
+    fn show(&mut self, message: String) {
+        self.message = Some((message, MESSAGE_TIME));
+    }
 
-        // note: remember that this is column-major, so the lines of code are actually columns
-        [
-            [f / self.aspect_ratio,    0.0,              0.0              ,   0.0],
-            [         0.0         ,     f ,              0.0              ,   0.0],
-            [         0.0         ,    0.0,  (zfar+znear)/(zfar-znear)    ,   1.0],
-            [         0.0         ,    0.0, -(2.0*zfar*znear)/(zfar-znear),   0.0],
-        ]
+    /**
+     * Where the cursor has to be moved to, once every time mouse-look needs
+     * it back in the middle of the window.
+     */
+    pub fn take_cursor_warp(&mut self) -> Option<(i32, i32)> {
+        if self.warp_requested {
+            self.warp_requested = false;
+            Some(self.window_center())
+        }
+        else {
+            None
+        }
     }
 
-    pub fn get_view(&self) -> [[f32; 4]; 4] {
-        let f = {
-            let f = self.direction;
-            let len = f.0 * f.0 + f.1 * f.1 + f.2 * f.2;
-            let len = len.sqrt();
-            (f.0 / len, f.1 / len, f.2 / len)
-        };
+    /**
+     * The cursor couldn't be moved, so no move back to the middle is coming
+     * and the next one that strays asks again.
+     */
+    pub fn cursor_warp_failed(&mut self) {
+        self.recentering = false;
+    }
 
+    fn set_mouse_look(&mut self, mouse_look: bool) {
+        self.mouse_look = mouse_look;
+        self.last_mouse = None;
+        self.warp_requested = false;
+        self.recentering = false;
+        if mouse_look {
+            self.orbit = None;
+        }
+    }
 
-        let s = (f.1 * self.up.2 - f.2 * self.up.1,
-                 f.2 * self.up.0 - f.0 * self.up.2,
-                 f.0 * self.up.1 - f.1 * self.up.0);
+    /**
+     * Chases the projectiles the catapult releases, see Chase::listen.
+     */
+    pub fn listen(&mut self, catapult_events: Receiver<FiringEvent>,
+                  world_events: Receiver<WorldEvent>) {
+        self.chase.listen(catapult_events, world_events);
+    }
 
-        let s_norm = {
-            let len = s.0 * s.0 + s.1 * s.1 + s.2 * s.2;
-            let len = len.sqrt();
-            (s.0 / len, s.1 / len, s.2 / len)
+    /**
+     * The position and velocity of the newest projectile, for chasing it.
+     */
+    pub fn track_projectile(&mut self, projectile: Option<(u64, [f32; 3], [f32; 3])>) {
+        self.projectile = projectile;
+    }
+
+    /**
+     * The parts the camera can focus on, see Drawable::parts.
+     */
+    pub fn set_targets(&mut self, targets: Vec<(String, Aabb)>) {
+        if self.selected >= targets.len() {
+            self.selected = 0;
+        }
+        self.targets = targets;
+    }
+
+    fn select_next(&mut self) {
+        if self.targets.is_empty() {
+            return;
+        }
+        self.selected = (self.selected + 1) % self.targets.len();
+        let message = format!("Selected {}", self.targets[self.selected].0);
+        self.show(message);
+    }
+
+    /**
+     * The center of the selected part, or a point ahead if there is none.
+     */
+    fn focus_point(&self) -> [f32; 3] {
+        match self.targets.get(self.selected) {
+            Some(&(_, ref bounds)) => bounds.center(),
+            None => {
+                let forward = quaternion::rotate_vector(self.orientation, [1.0, 0.0, 0.0]);
+                let position = [self.position.0, self.position.1, self.position.2];
+                vecmath::vec3_add(position, vecmath::vec3_scale(forward, 10.0))
+            },
+        }
+    }
+
+    /**
+     * The bounds of the selected part.
+     */
+    pub fn selected_target(&self) -> Option<Aabb> {
+        self.targets.get(self.selected).map(|&(_, bounds)| bounds)
+    }
+
+    /**
+     * Starts orbiting around the focus from where the camera is now.
+     */
+    fn start_orbit(&mut self, focus: [f32; 3]) {
+        self.set_mouse_look(false);
+        let position = [self.position.0, self.position.1, self.position.2];
+        let offset = vecmath::vec3_sub(focus, position);
+        let distance = vecmath::vec3_len(offset).max(MIN_DISTANCE).min(MAX_DISTANCE);
+        let direction = vecmath::vec3_normalized(offset);
+        self.orbit = Some(Orbit {
+            focus: focus,
+            distance: distance,
+            yaw: (-direction[2]).atan2(direction[0]),
+            pitch: direction[1].max(-1.0).min(1.0).asin().max(-MAX_PITCH).min(MAX_PITCH),
+        });
+    }
+
+    fn toggle_orbit(&mut self) {
+        if self.orbit.is_some() {
+            self.orbit = None;
+        }
+        else {
+            let focus = self.focus_point();
+            self.start_orbit(focus);
+        }
+    }
+
+    /**
+     * Orbits around the selected part from just far enough away to see all
+     * of it.
+     */
+    fn frame_selected(&mut self) {
+        let bounds = match self.targets.get(self.selected) {
+            Some(&(_, bounds)) => bounds,
+            None => return,
         };
+        let radius = vecmath::vec3_len(bounds.half_extents());
+        let distance = self.projection.fit(radius * FRAME_MARGIN, self.aspect_ratio);
+        self.start_orbit(bounds.center());
+        if let Some(ref mut orbit) = self.orbit {
+            orbit.distance = distance.max(MIN_DISTANCE).min(MAX_DISTANCE);
+        }
+    }
 
-        let u = (s_norm.1 * f.2 - s_norm.2 * f.1,
-                 s_norm.2 * f.0 - s_norm.0 * f.2,
-                 s_norm.0 * f.1 - s_norm.1 * f.0);
-
-        let p = (-self.position.0 * s.0 - self.position.1 * s.1 - self.position.2 * s.2,
-                 -self.position.0 * u.0 - self.position.1 * u.1 - self.position.2 * u.2,
-                 -self.position.0 * f.0 - self.position.1 * f.1 - self.position.2 * f.2);
-
-        // note: remember that this is column-major, so the lines of code are actually columns
-        [
-            [s_norm.0, u.0, f.0, 0.0],
-            [s_norm.1, u.1, f.1, 0.0],
-            [s_norm.2, u.2, f.2, 0.0],
-            [p.0, p.1,  p.2, 1.0],
-        ]
-    }
-
-    pub fn update(&mut self) {
-        let f = {
-            let f = self.direction;
-            let len = f.0 * f.0 + f.1 * f.1 + f.2 * f.2;
-            let len = len.sqrt();
-            (f.0 / len, f.1 / len, f.2 / len)
+    /**
+     * The view between the last two simulation steps, alpha being 0.0 for
+     * the previous and 1.0 for the current one.
+     */
+    pub fn get_interpolated_view(&self, alpha: f32) -> [[f32; 4]; 4] {
+        let position = lerp3(self.previous_position, self.position, alpha);
+        let orientation = nlerp(self.previous_orientation, self.orientation, alpha);
+        view_matrix(position, orientation)
+    }
+
+    /**
+     * The projection between the last two simulation steps, for a view with
+     * the aspect ratio.
+     */
+    pub fn get_interpolated_projection(&self, aspect_ratio: f32, alpha: f32) -> [[f32; 4]; 4] {
+        self.previous_projection.lerp(&self.projection, alpha).matrix(aspect_ratio)
+    }
+
+    fn zoom_view(&mut self, steps: f32) {
+        self.projection.zoom(steps);
+        let message = self.projection.describe();
+        self.show(message);
+    }
+
+    fn toggle_projection(&mut self) {
+        self.projection.toggle_mode();
+        self.previous_projection = self.projection;
+        let message = self.projection.describe();
+        self.show(message);
+    }
+
+    /**
+     * Adds a keyframe at the camera to the path, looking at the focus when
+     * orbiting, and saves the path.
+     */
+    fn add_path_keyframe(&mut self) {
+        let position = [self.position.0, self.position.1, self.position.2];
+        let look_at = match self.orbit {
+            Some(orbit) => orbit.focus,
+            None => {
+                let forward = quaternion::rotate_vector(self.orientation, [1.0, 0.0, 0.0]);
+                vecmath::vec3_add(position, vecmath::vec3_scale(forward, LOOK_DISTANCE))
+            },
         };
+        self.path.add_keyframe(position, look_at, self.projection.fov);
+        self.save_path();
+    }
+
+    fn remove_path_keyframe(&mut self) {
+        self.path.remove_last();
+        self.save_path();
+    }
 
-        let up = (0.0, 1.0, 0.0);
+    fn save_path(&mut self) {
+        let message = match self.path.save(&self.config.path_file) {
+            Ok(()) => format!("Camera path has {} keyframes", self.path.keyframes.len()),
+            Err(e) => e,
+        };
+        self.show(message);
+    }
 
-        let s = (f.1 * up.2 - f.2 * up.1,
-                 f.2 * up.0 - f.0 * up.2,
-                 f.0 * up.1 - f.1 * up.0);
+    fn toggle_path(&mut self) {
+        if let Err(e) = self.path.toggle_playing() {
+            self.show(e);
+        }
+    }
 
-        let s = {
-            let len = s.0 * s.0 + s.1 * s.1 + s.2 * s.2;
-            let len = len.sqrt();
-            (s.0 / len, s.1 / len, s.2 / len)
+    /**
+     * Stores the view in the slot, counting from 0, and saves the bookmarks.
+     */
+    fn store_bookmark(&mut self, slot: usize) {
+        let bookmark = Bookmark {
+            position: [self.position.0, self.position.1, self.position.2],
+            direction: quaternion::rotate_vector(self.orientation, [1.0, 0.0, 0.0]),
+            up: quaternion::rotate_vector(self.orientation, [0.0, 1.0, 0.0]),
+            projection: self.projection,
+        };
+        self.bookmarks.set(slot, bookmark);
+        let message = match self.bookmarks.save(&self.config.bookmark_file) {
+            Ok(()) => format!("Stored view {}", slot + 1),
+            Err(e) => e,
         };
+        self.show(message);
+    }
 
-        let u = (s.1 * f.2 - s.2 * f.1,
-                 s.2 * f.0 - s.0 * f.2,
-                 s.0 * f.1 - s.1 * f.0);
-
-        if self.moving_up {
-            self.position.0 += u.0 * self.moving_speed;
-            self.position.1 += u.1 * self.moving_speed;
-            self.position.2 += u.2 * self.moving_speed;
-        }
-
-        if self.moving_left {
-            self.position.0 -= s.0 * self.moving_speed;
-            self.position.1 -= s.1 * self.moving_speed;
-            self.position.2 -= s.2 * self.moving_speed;
-        }
-
-        if self.moving_down {
-            self.position.0 -= u.0 * self.moving_speed;
-            self.position.1 -= u.1 * self.moving_speed;
-            self.position.2 -= u.2 * self.moving_speed;
-        }
-
-        if self.moving_right {
-            self.position.0 += s.0 * self.moving_speed;
-            self.position.1 += s.1 * self.moving_speed;
-            self.position.2 += s.2 * self.moving_speed;
-        }
-
-        if self.moving_forward {
-            self.position.0 += f.0 * self.moving_speed;
-            self.position.1 += f.1 * self.moving_speed;
-            self.position.2 += f.2 * self.moving_speed;
-        }
-
-        if self.moving_backward {
-            self.position.0 -= f.0 * self.moving_speed;
-            self.position.1 -= f.1 * self.moving_speed;
-            self.position.2 -= f.2 * self.moving_speed;
-        }
-
-        if self.rotating_up {
-
-            // let null_pos: vecmath::Vector3<f32> = [1.0, 0.0, 0.0];
-            // let change: vecmath::Vector3<f32> = normalize([
-            //     null_pos[0],
-            //     null_pos[1] + self.rotating_speed,
-            //     null_pos[2],
-            // ]);
-            // let q = quaternion::rotation_from_to(null_pos, change);
-            // let direction_q = quaternion::rotation_from_to([1.0, 0.0, 0.0], normalize([self.direction.0, self.direction.1, self.direction.2]));
-            // let local_axis: vecmath::Vector3<f32> = [0.0, 0.0, 1.0];
-            // let world_axis: vecmath::Vector3<f32> = quaternion::rotate_vector(direction_q, local_axis);
-            // let world_rotation = quaternion::axis_angle(world_axis, self.rotating_speed);
-            // let new_direction = quaternion::mul(world_rotation, direction_q).1;
-            // println!("{:?}", direction_q);
-            // let modifier = quaternion::euler_angles(0.0, PI / 2.0, 0.0);
-            // let q = quaternion::mul(coord_sys, modifier);
-            // let q = quaternion::axis_angle([self.direction.0, self.direction.1, self.direction.2], -self.rotating_speed);
-            // let dir: vecmath::Vector3<f32> = normalize([
-            //     self.direction.0, self.direction.1, self.direction.2
-            // ]);
-            // let new_direction = quaternion::rotate_vector(q, dir);
-            // println!("{:?}", new_direction);
-            // self.direction = (new_direction[0], new_direction[1], new_direction[2])
-            // let vec_vec: vecmath::Vector3<f32> = normalize(vec);
-            // let vec_rot: vecmath::Vector3<f32> = normalize(rot);
-            // let q = quaternion::rotation_from_to(vec_vec, vec_rot);
-            // let rotated_vec = quaternion::rotate_vector(q, vec_vec);
-            // (rotated_vec[0], rotated_vec[1], rotated_vec[2])
-
-
-            // self.direction = rotate(
-            //     self.direction,
-            //     ((f.0 * (-self.rotating_speed).cos()) -
-            //     (f.1 * (-self.rotating_speed).sin()),
-            //     (f.0 * (-self.rotating_speed).sin()) +
-            //     (f.1 * (-self.rotating_speed).cos()),
-            //     self.direction.2)
-            // )
-            self.direction.0 = {
-                (f.0 * (-self.rotating_speed).cos()) -
-                (f.1 * (-self.rotating_speed).sin())
-            };
-            self.direction.1 = {
-                (f.0 * (-self.rotating_speed).sin()) +
-                (f.1 * (-self.rotating_speed).cos())
-            };
-        }
-        if self.rotating_down {
-            self.direction.0 = {
-                (f.0 * self.rotating_speed.cos()) -
-                (f.1 * self.rotating_speed.sin())
-            };
-            self.direction.1 = {
-                (f.0 * self.rotating_speed.sin()) +
-                (f.1 * self.rotating_speed.cos())
-            };
-        }
-        if self.rotating_left {
-            self.direction.0 = {
-                (f.0 * self.rotating_speed.cos()) +
-                (f.2 * self.rotating_speed.sin())
-            };
-            self.direction.2 = {
-                (-f.0 * self.rotating_speed.sin()) +
-                (f.2 * self.rotating_speed.cos())
-            };
-        }
-        if self.rotating_right {
-            self.direction.0 = {
-                (f.0 * (-self.rotating_speed).cos()) +
-                (f.2 * (-self.rotating_speed).sin())
-            };
-            self.direction.2 = {
-                (- f.0 * (-self.rotating_speed).sin()) +
-                (f.2 * (-self.rotating_speed).cos())
-            };
-        }
-    }
-
-    pub fn process_input(&mut self, event: &glutin::Event) {
-        match event {
-            &glutin::Event::KeyboardInput(glutin::ElementState::Pressed, _, Some(glutin::VirtualKeyCode::Q)) => {
-                self.moving_up = true;
-            },
-            &glutin::Event::KeyboardInput(glutin::ElementState::Released, _, Some(glutin::VirtualKeyCode::Q)) => {
-                self.moving_up = false;
-            },
-            &glutin::Event::KeyboardInput(glutin::ElementState::Pressed, _, Some(glutin::VirtualKeyCode::E)) => {
-                self.moving_down = true;
-            },
-            &glutin::Event::KeyboardInput(glutin::ElementState::Released, _, Some(glutin::VirtualKeyCode::E)) => {
-                self.moving_down = false;
+    /**
+     * Flies to the view stored in the slot.
+     */
+    fn recall_bookmark(&mut self, slot: usize) {
+        let bookmark = match self.bookmarks.get(slot) {
+            Some(bookmark) => bookmark,
+            None => {
+                self.show(format!("No view stored at {}", slot + 1));
+                return;
             },
-            &glutin::Event::KeyboardInput(glutin::ElementState::Pressed, _, Some(glutin::VirtualKeyCode::A)) => {
-                self.moving_left = true;
-            },
-            &glutin::Event::KeyboardInput(glutin::ElementState::Released, _, Some(glutin::VirtualKeyCode::A)) => {
-                self.moving_left = false;
-            },
-            &glutin::Event::KeyboardInput(glutin::ElementState::Pressed, _, Some(glutin::VirtualKeyCode::D)) => {
-                self.moving_right = true;
-            },
-            &glutin::Event::KeyboardInput(glutin::ElementState::Released, _, Some(glutin::VirtualKeyCode::D)) => {
-                self.moving_right = false;
-            },
-            &glutin::Event::KeyboardInput(glutin::ElementState::Pressed, _, Some(glutin::VirtualKeyCode::W)) => {
-                self.moving_forward = true;
-            },
-            &glutin::Event::KeyboardInput(glutin::ElementState::Released, _, Some(glutin::VirtualKeyCode::W)) => {
-                self.moving_forward = false;
-            },
-            &glutin::Event::KeyboardInput(glutin::ElementState::Pressed, _, Some(glutin::VirtualKeyCode::S)) => {
-                self.moving_backward = true;
-            },
-            &glutin::Event::KeyboardInput(glutin::ElementState::Released, _, Some(glutin::VirtualKeyCode::S)) => {
-                self.moving_backward = false;
-            },
-            &glutin::Event::KeyboardInput(glutin::ElementState::Pressed, _, Some(glutin::VirtualKeyCode::Up)) => {
-                self.rotating_up = true;
-            },
-            &glutin::Event::KeyboardInput(glutin::ElementState::Released, _, Some(glutin::VirtualKeyCode::Up)) => {
-                self.rotating_up = false;
-            },
-            &glutin::Event::KeyboardInput(glutin::ElementState::Pressed, _, Some(glutin::VirtualKeyCode::Down)) => {
-                self.rotating_down = true;
+        };
+        let p = bookmark.position;
+        self.transition = Some(Transition {
+            from: (self.position, self.orientation, self.projection),
+            to: (
+                (p[0], p[1], p[2]),
+                orientation_from_axes(bookmark.direction, bookmark.up),
+                bookmark.projection
+            ),
+            time: 0.0,
+        });
+        self.orbit = None;
+    }
+
+    /**
+     * Pushes the camera out of the obstacles and the ground if the
+     * constraints are on, call after update. It stops moving into whatever
+     * it got pushed off.
+     */
+    pub fn constrain(&mut self, terrain: &Terrain, obstacles: &[Collider]) {
+        let position = [self.position.0, self.position.1, self.position.2];
+        let (position, normals) = self.constraints.apply(position, terrain, obstacles);
+        self.position = (position[0], position[1], position[2]);
+        for normal in normals {
+            let into = vecmath::vec3_dot(self.velocity, normal);
+            if into < 0.0 {
+                self.velocity = vecmath::vec3_sub(self.velocity, vecmath::vec3_scale(normal, into));
+            }
+        }
+    }
+
+    /**
+     * Moves the camera along the transition while there is one. Returns
+     * whether it has the camera.
+     */
+    fn update_transition(&mut self, dt: f32) -> bool {
+        let mut transition = match self.transition.take() {
+            Some(transition) => transition,
+            None => return false,
+        };
+        transition.time += dt;
+        let t = (transition.time / TRANSITION_TIME).min(1.0);
+        let t = t * t * (3.0 - 2.0 * t);
+        let (from, to) = (transition.from, transition.to);
+        self.position = lerp3(from.0, to.0, t);
+        self.orientation = nlerp(from.1, to.1, t);
+        self.projection = from.2.lerp(&to.2, t);
+        if transition.time < TRANSITION_TIME {
+            self.transition = Some(transition);
+        }
+        true
+    }
+
+    /**
+     * Moves and rotates the camera, dt being the passed time in seconds.
+     */
+    pub fn update(&mut self, dt: f32) {
+        if let Some((_, ref mut left)) = self.message {
+            *left -= dt;
+        }
+        if self.message.as_ref().map_or(false, |&(_, left)| left <= 0.0) {
+            self.message = None;
+        }
+        self.previous_position = self.position;
+        self.previous_orientation = self.orientation;
+        self.previous_projection = self.projection;
+        if let Some((position, look_at, fov)) = self.path.update(dt) {
+            self.look_from(position, look_at);
+            self.projection.fov = fov;
+            self.velocity = [0.0, 0.0, 0.0];
+            return;
+        }
+        if self.update_transition(dt) || self.update_chase(dt) {
+            self.velocity = [0.0, 0.0, 0.0];
+            return;
+        }
+        if self.orbit.is_some() {
+            self.velocity = [0.0, 0.0, 0.0];
+            return self.update_orbit(dt);
+        }
+        self.mouse_pan = (0.0, 0.0);
+        if self.zoom != 0.0 {
+            let lines = self.zoom;
+            self.scroll(lines);
+            self.zoom = 0.0;
+        }
+        let rotating_speed = self.rotating_speed * dt;
+
+        let f = quaternion::rotate_vector(self.orientation, [1.0, 0.0, 0.0]);
+        let u = quaternion::rotate_vector(self.orientation, [0.0, 1.0, 0.0]);
+        let s = quaternion::rotate_vector(self.orientation, [0.0, 0.0, 1.0]);
+
+        let mut movement = [0.0, 0.0, 0.0];
+        let axes = [
+            (self.moving_forward, f, 1.0), (self.moving_backward, f, -1.0),
+            (self.moving_right, s, 1.0), (self.moving_left, s, -1.0),
+            (self.moving_up, u, 1.0), (self.moving_down, u, -1.0),
+        ];
+        for &(moving, axis, sign) in axes.iter() {
+            if moving {
+                movement = vecmath::vec3_add(movement, vecmath::vec3_scale(axis, sign));
+            }
+        }
+        // Moving diagonally isn't any faster
+        if vecmath::vec3_len(movement) > 0.0 {
+            let thrust = self.top_speed() * ACCELERATION * dt;
+            movement = vecmath::vec3_scale(vecmath::vec3_normalized(movement), thrust);
+            self.velocity = vecmath::vec3_add(self.velocity, movement);
+        }
+        self.velocity = vecmath::vec3_scale(self.velocity, (-DAMPING * dt).exp());
+        self.position.0 += self.velocity[0] * dt;
+        self.position.1 += self.velocity[1] * dt;
+        self.position.2 += self.velocity[2] * dt;
+
+        let mut yaw = 0.0;
+        let mut pitch = 0.0;
+        let mut roll = 0.0;
+        if self.rotating_left { yaw += rotating_speed; }
+        if self.rotating_right { yaw -= rotating_speed; }
+        if self.rotating_up { pitch += rotating_speed; }
+        if self.rotating_down { pitch -= rotating_speed; }
+        if self.rolling_left { roll -= rotating_speed; }
+        if self.rolling_right { roll += rotating_speed; }
+        self.rotate(yaw + self.mouse_yaw, pitch + self.mouse_pitch, roll);
+        self.mouse_yaw = 0.0;
+        self.mouse_pitch = 0.0;
+    }
+
+    /**
+     * Moves the camera along with the chase while there is one, saving the
+     * pose when it starts and flying back to it at the end. Returns whether
+     * the chase has the camera.
+     */
+    fn update_chase(&mut self, dt: f32) -> bool {
+        let position = [self.position.0, self.position.1, self.position.2];
+        match self.chase.update(dt, position, self.projectile) {
+            Some((position, look_at)) => {
+                if self.saved_pose.is_none() {
+                    self.saved_pose = Some((self.position, self.orientation, self.orbit));
+                }
+                self.look_from(position, look_at);
+                true
             },
-            &glutin::Event::KeyboardInput(glutin::ElementState::Released, _, Some(glutin::VirtualKeyCode::Down)) => {
-                self.rotating_down = false;
+            None => {
+                // The orbit takes over again once the camera is back
+                if let Some((position, orientation, orbit)) = self.saved_pose.take() {
+                    self.transition = Some(Transition {
+                        from: (self.position, self.orientation, self.projection),
+                        to: (position, orientation, self.projection),
+                        time: 0.0,
+                    });
+                    self.orbit = orbit;
+                }
+                false
             },
-            &glutin::Event::KeyboardInput(glutin::ElementState::Pressed, _, Some(glutin::VirtualKeyCode::Left)) => {
-                self.rotating_left = true;
+        }
+    }
+
+    /**
+     * Puts the camera at the position, looking at the point.
+     */
+    fn look_from(&mut self, position: [f32; 3], look_at: [f32; 3]) {
+        let direction = vecmath::vec3_sub(look_at, position);
+        self.position = (position[0], position[1], position[2]);
+        if vecmath::vec3_len(direction) > 0.01 {
+            self.orientation = orientation_towards((direction[0], direction[1], direction[2]));
+        }
+    }
+
+    /**
+     * Turns around the focus with the look actions or the dragged mouse, the
+     * other movement actions pan and camera_forward and camera_backward zoom.
+     */
+    fn update_orbit(&mut self, dt: f32) {
+        let mut orbit = match self.orbit {
+            Some(orbit) => orbit,
+            None => return,
+        };
+        let rotating_speed = self.rotating_speed * dt;
+        let mut yaw = self.mouse_yaw;
+        let mut pitch = self.mouse_pitch;
+        if self.rotating_left { yaw += rotating_speed; }
+        if self.rotating_right { yaw -= rotating_speed; }
+        if self.rotating_up { pitch += rotating_speed; }
+        if self.rotating_down { pitch -= rotating_speed; }
+        orbit.yaw += yaw;
+        orbit.pitch = (orbit.pitch + pitch).max(-MAX_PITCH).min(MAX_PITCH);
+
+        let orientation = orientation_from(orbit.yaw, orbit.pitch);
+        let u = quaternion::rotate_vector(orientation, [0.0, 1.0, 0.0]);
+        let s = quaternion::rotate_vector(orientation, [0.0, 0.0, 1.0]);
+        // Dragging moves the scene along with the cursor
+        let per_pixel = orbit.distance * PAN_PER_PIXEL;
+        let mut right = -self.mouse_pan.0 * per_pixel;
+        let mut up = self.mouse_pan.1 * per_pixel;
+        let moving_speed = self.top_speed() * dt;
+        if self.moving_right { right += moving_speed; }
+        if self.moving_left { right -= moving_speed; }
+        if self.moving_up { up += moving_speed; }
+        if self.moving_down { up -= moving_speed; }
+        orbit.focus = vecmath::vec3_add(
+            orbit.focus,
+            vecmath::vec3_add(vecmath::vec3_scale(s, right), vecmath::vec3_scale(u, up))
+        );
+
+        let mut zoom = SCROLL_FACTOR.powf(-self.zoom);
+        if self.moving_forward { zoom *= (-dt).exp(); }
+        if self.moving_backward { zoom *= dt.exp(); }
+        // Moving closer doesn't make anything bigger in an orthographic view
+        if self.projection.mode == ProjectionMode::Orthographic {
+            self.projection.zoom_ortho(zoom);
+        }
+        else {
+            orbit.distance = (orbit.distance * zoom).max(MIN_DISTANCE).min(MAX_DISTANCE);
+        }
+
+        let forward = quaternion::rotate_vector(orientation, [1.0, 0.0, 0.0]);
+        let position = vecmath::vec3_sub(orbit.focus, vecmath::vec3_scale(forward, orbit.distance));
+        self.position = (position[0], position[1], position[2]);
+        self.orientation = orientation;
+        self.orbit = Some(orbit);
+        self.mouse_yaw = 0.0;
+        self.mouse_pitch = 0.0;
+        self.mouse_pan = (0.0, 0.0);
+        self.zoom = 0.0;
+    }
+
+    /**
+     * Turns the camera by the distance the cursor moved. During mouse-look
+     * the cursor gets put back in the middle before it reaches the border of
+     * the window, only the move that brings it back there doesn't count.
+     */
+    fn mouse_moved(&mut self, x: i32, y: i32) {
+        let center = self.window_center();
+        if self.recentering && (x, y) == center {
+            self.recentering = false;
+            self.last_mouse = Some((x, y));
+            return;
+        }
+        if let Some((last_x, last_y)) = self.last_mouse {
+            let dx = (x - last_x) as f32;
+            let dy = (y - last_y) as f32;
+            let orbiting = self.orbit.is_some();
+            if self.mouse_look || (self.dragging && orbiting) {
+                let sensitivity = self.config.mouse_sensitivity;
+                let dy = if self.config.invert_y { dy } else { -dy };
+                self.mouse_yaw -= dx * sensitivity;
+                self.mouse_pitch += dy * sensitivity;
+            }
+            if self.panning && orbiting {
+                self.mouse_pan.0 += dx;
+                self.mouse_pan.1 += dy;
+            }
+        }
+        self.last_mouse = Some((x, y));
+        let strayed = (x - center.0).abs() > center.0 / 2 || (y - center.1).abs() > center.1 / 2;
+        if self.mouse_look && strayed && !self.recentering {
+            self.warp_requested = true;
+            self.recentering = true;
+        }
+    }
+
+    fn top_speed(&self) -> f32 {
+        if self.sprinting { self.moving_speed * SPRINT_FACTOR } else { self.moving_speed }
+    }
+
+    fn scroll(&mut self, lines: f32) {
+        self.moving_speed = (self.moving_speed * SCROLL_FACTOR.powf(lines))
+            .max(MIN_MOVING_SPEED).min(MAX_MOVING_SPEED);
+    }
+
+    /**
+     * Turns the camera by the angles in radians around its own up, right
+     * and forward axes. Positive yaw turns left, positive pitch looks up and
+     * positive roll tilts to the right. The pitch gets limited so the
+     * camera never looks straight up or down.
+     */
+    pub fn rotate(&mut self, yaw: f32, pitch: f32, roll: f32) {
+        let forward = quaternion::rotate_vector(self.orientation, [1.0, 0.0, 0.0]);
+        let current_pitch = forward[1].max(-1.0).min(1.0).asin();
+        let pitch = pitch.max(-MAX_PITCH - current_pitch).min(MAX_PITCH - current_pitch);
+        // Multiplying from the right rotates around the local axes
+        let rotation = quaternion::mul(
+            quaternion::mul(
+                quaternion::axis_angle([0.0, 1.0, 0.0], yaw),
+                quaternion::axis_angle([0.0, 0.0, 1.0], pitch)
+            ),
+            quaternion::axis_angle([1.0, 0.0, 0.0], roll)
+        );
+        self.orientation = normalize(quaternion::mul(self.orientation, rotation));
+    }
+
+    pub fn process_input(&mut self, input: &Input) {
+        let (action, pressed) = match *input {
+            Input::Pressed(action) => (action, true),
+            Input::Released(action) => (action, false),
+            Input::MouseMoved(x, y) => return self.mouse_moved(x, y),
+            Input::Scrolled(lines) => return self.zoom += lines,
+            Input::Resized(width, height) => return self.set_window_size(width, height),
+            Input::Focused(focused) => {
+                if !focused {
+                    self.set_mouse_look(false);
+                }
+                return;
             },
-            &glutin::Event::KeyboardInput(glutin::ElementState::Released, _, Some(glutin::VirtualKeyCode::Left)) => {
-                self.rotating_left = false;
+        };
+        match action {
+            Action::CameraUp => self.moving_up = pressed,
+            Action::CameraDown => self.moving_down = pressed,
+            Action::CameraLeft => self.moving_left = pressed,
+            Action::CameraRight => self.moving_right = pressed,
+            Action::CameraForward => self.moving_forward = pressed,
+            Action::CameraBackward => self.moving_backward = pressed,
+            Action::RollLeft => self.rolling_left = pressed,
+            Action::RollRight => self.rolling_right = pressed,
+            Action::LookUp => self.rotating_up = pressed,
+            Action::LookDown => self.rotating_down = pressed,
+            Action::LookLeft => self.rotating_left = pressed,
+            Action::LookRight => self.rotating_right = pressed,
+            Action::Sprint => self.sprinting = pressed,
+            Action::OrbitDrag => self.dragging = pressed,
+            Action::PanDrag => self.panning = pressed,
+            // The rest happens once when pressed
+            _ if !pressed => {},
+            Action::MouseLook => {
+                let mouse_look = !self.mouse_look;
+                self.set_mouse_look(mouse_look);
             },
-            &glutin::Event::KeyboardInput(glutin::ElementState::Pressed, _, Some(glutin::VirtualKeyCode::Right)) => {
-                self.rotating_right = true;
+            Action::ReleaseMouse => self.set_mouse_look(false),
+            Action::Orbit => self.toggle_orbit(),
+            Action::SelectNext => self.select_next(),
+            Action::FrameSelected => self.frame_selected(),
+            Action::Chase => {
+                self.chase.toggle();
+                let message = format!("Chase camera {}", on_off(self.chase.enabled));
+                self.show(message);
             },
-            &glutin::Event::KeyboardInput(glutin::ElementState::Released, _, Some(glutin::VirtualKeyCode::Right)) => {
-                self.rotating_right = false;
+            Action::AddKeyframe => self.add_path_keyframe(),
+            Action::RemoveKeyframe => self.remove_path_keyframe(),
+            Action::PlayPath => self.toggle_path(),
+            Action::ZoomIn => self.zoom_view(1.0),
+            Action::ZoomOut => self.zoom_view(-1.0),
+            Action::ToggleProjection => self.toggle_projection(),
+            Action::ToggleConstraints => {
+                self.constraints.toggle();
+                let message = format!("Camera constraints {}", on_off(self.constraints.enabled));
+                self.show(message);
             },
+            Action::Bookmark(slot) => self.recall_bookmark(slot),
+            Action::StoreBookmark(slot) => self.store_bookmark(slot),
             _ => {}
         }
     }
 }
+
+/**
+ * The orientation looking along the direction with the horizon level.
+ */
+fn orientation_towards(direction: (f32, f32, f32)) -> Quaternion<f32> {
+    let d = vecmath::vec3_normalized([direction.0, direction.1, direction.2]);
+    let yaw = (-d[2]).atan2(d[0]);
+    let pitch = d[1].max(-1.0).min(1.0).asin().max(-MAX_PITCH).min(MAX_PITCH);
+    orientation_from(yaw, pitch)
+}
+
+/**
+ * The orientation turned by yaw to the left and by pitch up, without roll.
+ */
+pub fn orientation_from(yaw: f32, pitch: f32) -> Quaternion<f32> {
+    quaternion::mul(
+        quaternion::axis_angle([0.0, 1.0, 0.0], yaw),
+        quaternion::axis_angle([0.0, 0.0, 1.0], pitch)
+    )
+}
+
+/**
+ * The orientation looking along the direction, with the up vector turned to
+ * be perpendicular to it. An up along the direction says nothing about the
+ * roll, then the world y or x axis stands in for it.
+ */
+fn orientation_from_axes(direction: [f32; 3], up: [f32; 3]) -> Quaternion<f32> {
+    // The local axes are the columns of the rotation matrix
+    let f = vecmath::vec3_normalized(direction);
+    let side = [up, [0.0, 1.0, 0.0], [1.0, 0.0, 0.0]].iter()
+        .map(|&up| vecmath::vec3_cross(f, vecmath::vec3_normalized(up)))
+        .find(|&side| vecmath::vec3_len(side) > 1e-3)
+        .unwrap();
+    let s = vecmath::vec3_normalized(side);
+    let u = vecmath::vec3_cross(s, f);
+    let trace = f[0] + u[1] + s[2];
+    let q = if trace > 0.0 {
+        let r = (trace + 1.0).sqrt() * 2.0;
+        (0.25 * r, [(u[2] - s[1]) / r, (s[0] - f[2]) / r, (f[1] - u[0]) / r])
+    }
+    else if f[0] > u[1] && f[0] > s[2] {
+        let r = (1.0 + f[0] - u[1] - s[2]).sqrt() * 2.0;
+        ((u[2] - s[1]) / r, [0.25 * r, (u[0] + f[1]) / r, (s[0] + f[2]) / r])
+    }
+    else if u[1] > s[2] {
+        let r = (1.0 + u[1] - f[0] - s[2]).sqrt() * 2.0;
+        ((s[0] - f[2]) / r, [(u[0] + f[1]) / r, 0.25 * r, (s[1] + u[2]) / r])
+    }
+    else {
+        let r = (1.0 + s[2] - f[0] - u[1]).sqrt() * 2.0;
+        ((f[1] - u[0]) / r, [(s[0] + f[2]) / r, (s[1] + u[2]) / r, 0.25 * r])
+    };
+    normalize(q)
+}
+
+/**
+ * The view of a camera at the position, turned by the orientation.
+ */
+pub fn view_matrix(position: (f32, f32, f32), orientation: Quaternion<f32>) -> [[f32; 4]; 4] {
+    let f = quaternion::rotate_vector(orientation, [1.0, 0.0, 0.0]);
+    let u = quaternion::rotate_vector(orientation, [0.0, 1.0, 0.0]);
+    let s = quaternion::rotate_vector(orientation, [0.0, 0.0, 1.0]);
+
+    let p = (-position.0 * s[0] - position.1 * s[1] - position.2 * s[2],
+             -position.0 * u[0] - position.1 * u[1] - position.2 * u[2],
+             -position.0 * f[0] - position.1 * f[1] - position.2 * f[2]);
+
+    // note: remember that this is column-major, so the lines of code are actually columns
+    [
+        [s[0], u[0], f[0], 0.0],
+        [s[1], u[1], f[1], 0.0],
+        [s[2], u[2], f[2], 0.0],
+        [p.0, p.1,  p.2, 1.0],
+    ]
+}
+
+fn normalize(q: Quaternion<f32>) -> Quaternion<f32> {
+    quaternion::scale(q, 1.0 / quaternion::len(q))
+}
+
+/**
+ * Interpolates between two orientations, taking the shorter way around.
+ */
+fn nlerp(from: Quaternion<f32>, to: Quaternion<f32>, alpha: f32) -> Quaternion<f32> {
+    let dot = from.0 * to.0 + vecmath::vec3_dot(from.1, to.1);
+    let to = if dot < 0.0 { quaternion::scale(to, -1.0) } else { to };
+    normalize(quaternion::add(
+        quaternion::scale(from, 1.0 - alpha), quaternion::scale(to, alpha)
+    ))
+}
+
+fn on_off(enabled: bool) -> &'static str {
+    if enabled { "on" } else { "off" }
+}
+
+fn lerp3(from: (f32, f32, f32), to: (f32, f32, f32), alpha: f32) -> (f32, f32, f32) {
+    (lerp(from.0, to.0, alpha), lerp(from.1, to.1, alpha), lerp(from.2, to.2, alpha))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, fs, process};
+
+    fn temp_file(name: &str) -> String {
+        let file = env::temp_dir().join(format!("catapult-camera-{}-{}", name, process::id()));
+        file.to_str().unwrap().to_string()
+    }
+
+    // Keeps the files of the tests apart from the ones next to the game
+    fn config(name: &str) -> CameraConfig {
+        CameraConfig {
+            path_file: temp_file(&format!("{}.path", name)),
+            bookmark_file: temp_file(&format!("{}.bookmarks", name)),
+            .. CameraConfig::from_config(&Config::parse(""))
+        }
+    }
+
+    fn camera() -> CameraState {
+        let mut camera = CameraState::new(config("missing"));
+        camera.process_input(&Input::Resized(1024, 768));
+        camera.process_input(&Input::Pressed(Action::MouseLook));
+        camera
+    }
+
+    #[test]
+    fn mouse_look_puts_back_the_cursor_once_it_strays() {
+        let mut camera = camera();
+        camera.process_input(&Input::MouseMoved(512, 384));
+        camera.process_input(&Input::MouseMoved(600, 384));
+        assert_eq!(camera.take_cursor_warp(), None);
+        camera.process_input(&Input::MouseMoved(800, 384));
+        assert_eq!(camera.take_cursor_warp(), Some((512, 384)));
+        assert_eq!(camera.take_cursor_warp(), None);
+
+        let turned = camera.mouse_yaw;
+        camera.process_input(&Input::MouseMoved(512, 384));
+        assert_eq!(camera.mouse_yaw, turned);
+        camera.process_input(&Input::MouseMoved(500, 384));
+        assert!(camera.mouse_yaw > turned);
+    }
+
+    #[test]
+    fn failed_cursor_warps_get_asked_for_again() {
+        let mut camera = camera();
+        camera.process_input(&Input::MouseMoved(512, 384));
+        camera.process_input(&Input::MouseMoved(800, 384));
+        assert_eq!(camera.take_cursor_warp(), Some((512, 384)));
+        camera.cursor_warp_failed();
+
+        camera.process_input(&Input::MouseMoved(850, 384));
+        assert_eq!(camera.take_cursor_warp(), Some((512, 384)));
+        // The cursor moving back by hand turns the camera
+        camera.cursor_warp_failed();
+        let turned = camera.mouse_yaw;
+        camera.process_input(&Input::MouseMoved(512, 384));
+        assert!(camera.mouse_yaw > turned);
+    }
+
+    #[test]
+    fn changes_show_in_the_title_for_a_while() {
+        let mut camera = camera();
+        assert_eq!(camera.status(), None);
+        camera.process_input(&Input::Pressed(Action::Chase));
+        assert_eq!(camera.status(), Some("Chase camera on".to_string()));
+        camera.update(MESSAGE_TIME / 2.0);
+        assert!(camera.status().is_some());
+        camera.update(MESSAGE_TIME / 2.0);
+        assert_eq!(camera.status(), None);
+    }
+
+    #[test]
+    fn camera_flies_back_after_a_chase() {
+        use std::sync::mpsc::channel;
+        let mut camera = camera();
+        let (firing, catapult_events) = channel();
+        let (world, world_events) = channel();
+        camera.listen(catapult_events, world_events);
+        camera.process_input(&Input::Pressed(Action::Chase));
+        let start = camera.position;
+
+        let velocity = [10.0, 5.0, 0.0];
+        firing.send(FiringEvent::ReleasedProjectile { position: [0.0; 3], velocity: velocity })
+            .unwrap();
+        camera.track_projectile(Some((0, [20.0, 5.0, 0.0], velocity)));
+        camera.update(0.1);
+        world.send(WorldEvent::Landed { projectile: 0, position: [30.0, 0.0, 0.0] }).unwrap();
+        camera.update(0.1);
+        let chased = camera.position;
+        assert!(chased != start);
+
+        // Gives the camera back without jumping
+        camera.update(10.0);
+        assert_eq!(camera.position, chased);
+        camera.update(TRANSITION_TIME);
+        let distance = vecmath::vec3_len(vecmath::vec3_sub(
+            [camera.position.0, camera.position.1, camera.position.2],
+            [start.0, start.1, start.2]
+        ));
+        assert!(distance < 1e-4, "{:?} isn't back at {:?}", camera.position, start);
+    }
+
+    fn assert_axes(direction: [f32; 3], up: [f32; 3], side: [f32; 3]) {
+        let q = orientation_from_axes(direction, up);
+        let axes = [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]];
+        for (&axis, &expected) in axes.iter().zip([direction, up, side].iter()) {
+            let actual = quaternion::rotate_vector(q, axis);
+            let error = vecmath::vec3_len(vecmath::vec3_sub(actual, expected));
+            assert!(error < 1e-5, "{:?} turned {:?} to {:?} instead of {:?}",
+                    (direction, up), axis, actual, expected);
+        }
+    }
+
+    #[test]
+    fn orientation_from_axes_covers_every_branch() {
+        // Positive trace
+        assert_axes([1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]);
+        // The forward axis is the largest diagonal element
+        assert_axes([1.0, 0.0, 0.0], [0.0, -1.0, 0.0], [0.0, 0.0, -1.0]);
+        // The up axis is
+        assert_axes([-1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, -1.0]);
+        // The side axis is
+        assert_axes([-1.0, 0.0, 0.0], [0.0, -1.0, 0.0], [0.0, 0.0, 1.0]);
+    }
+
+    #[test]
+    fn orientation_from_axes_looks_straight_down() {
+        assert_axes([0.0, -1.0, 0.0], [1.0, 0.0, 0.0], [0.0, 0.0, 1.0]);
+        assert_axes([0.0, -1.0, 0.0], [0.0, 0.0, -1.0], [1.0, 0.0, 0.0]);
+        // An up that isn't perpendicular gets straightened
+        let q = orientation_from_axes([0.0, -2.0, 0.0], [1.0, 1.0, 0.0]);
+        let up = quaternion::rotate_vector(q, [0.0, 1.0, 0.0]);
+        assert!(vecmath::vec3_len(vecmath::vec3_sub(up, [1.0, 0.0, 0.0])) < 1e-5, "{:?}", up);
+    }
+
+    #[test]
+    fn orientation_from_axes_picks_another_up_along_the_direction() {
+        let up_of = |direction, up| {
+            quaternion::rotate_vector(orientation_from_axes(direction, up), [0.0, 1.0, 0.0])
+        };
+        let close = |a: [f32; 3], b: [f32; 3]| vecmath::vec3_len(vecmath::vec3_sub(a, b)) < 1e-5;
+        // Looking straight down or up with the world up, the x axis stands in
+        assert!(close(up_of([0.0, -1.0, 0.0], [0.0, 1.0, 0.0]), [1.0, 0.0, 0.0]));
+        assert!(close(up_of([0.0, 3.0, 0.0], [0.0, -1.0, 0.0]), [1.0, 0.0, 0.0]));
+        // Otherwise the world up does, also for a missing up
+        assert!(close(up_of([2.0, 0.0, 0.0], [-1.0, 0.0, 0.0]), [0.0, 1.0, 0.0]));
+        assert!(close(up_of([0.0, 0.0, 1.0], [0.0, 0.0, 0.0]), [0.0, 1.0, 0.0]));
+        assert_axes([0.0, 0.0, 1.0], [0.0, 1.0, 0.0], [-1.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn bookmarks_and_the_path_go_to_the_configured_files() {
+        let config = config("stored");
+        let mut storing = CameraState::new(config.clone());
+        storing.process_input(&Input::Pressed(Action::StoreBookmark(2)));
+        storing.process_input(&Input::Pressed(Action::AddKeyframe));
+        let reloaded = CameraState::new(config.clone());
+        let _ = fs::remove_file(&config.path_file);
+        let _ = fs::remove_file(&config.bookmark_file);
+
+        assert!(reloaded.bookmarks.get(2).is_some());
+        assert_eq!(reloaded.path.keyframes.len(), 1);
+        assert!(camera().bookmarks.get(2).is_none());
+    }
+
+    #[test]
+    fn moving_onto_the_middle_turns() {
+        let mut camera = camera();
+        camera.process_input(&Input::MouseMoved(400, 384));
+        camera.process_input(&Input::MouseMoved(512, 384));
+        assert!(camera.mouse_yaw < 0.0);
+    }
+}
@@ -4,16 +4,28 @@ use glium::backend::glutin_backend;
 use glium::VertexBuffer;
 use glium::IndexBuffer;
 use glium::index::PrimitiveType::TrianglesList;
-use glium::glutin;
-use matrix::{mul_matrices, rot_matrix_by};
+use matrix::{mul_matrices, rot_matrix_by, transform_point, translation_matrix};
 use std::f32::consts::PI;
 use quaternion;
 use std::rc::Rc;
 use state::Settings;
+use viewport::Viewport;
 use drawable::*;
+use clock::lerp;
+use firing::{FiringConfig, FiringEvent, FiringMachine, FiringState};
+use std::sync::mpsc::Receiver;
+use collision::{bounds_of, Aabb, Collider, WOOD};
+use rope::{gen_tube, tube_indices, Rope};
+use terrain::Terrain;
+use joint::{Joint, Rig};
+use input::{Action, Input};
+use linkage::Linkage;
+use mass::{MassProperties, ASH_DENSITY, NO_MASS, OAK_DENSITY, PINE_DENSITY};
+use animation::{load_clips, parse_clips, AnimationPlayer, Clip};
+use std::path::Path;
 
 #[derive(Copy, Clone)]
-struct Normal {
+pub struct Normal {
     pub normal: (f32, f32, f32)
 }
 
@@ -22,312 +34,979 @@ implement_vertex!(Normal, normal);
 
 #[derive(Copy, Clone)]
 pub struct Vertex {
-    position: (f32, f32, f32),
-    tex_coords: [f32; 2],
+    pub position: (f32, f32, f32),
+    pub tex_coords: [f32; 2],
 }
 implement_vertex!(Vertex, position, tex_coords);
 
+/**
+ * The catapult as it gets created by `init_catapult`.
+ */
+pub type CatapultObject = Catapult<Vertex, Normal, u16>;
+
+// Where the arm rotates around, in model coordinates
+const ARM_PIVOT: [f32; 3] = [3.65, 0.5, 0.0];
+// How far the arm can be wound back from rest, it can't swing past the
+// stopper
+const ARM_LIMIT: f32 = -(PI / 2.0) + (PI / 16.0);
+// The winder and the drum turn around the same axis
+const WINDER_PIVOT: [f32; 3] = [0.5, 0.8, -0.125];
+// Turns of the drum per turn of the arm, and of the crank per turn of the drum.
+// Winding the arm all the way reels in nearly all of the rope.
+const DRUM_RATIO: f32 = 22.0;
+const CRANK_RATIO: f32 = 2.0;
+// The pawl clicks over a tooth of the ratchet every this many radians
+const RATCHET_TOOTH: f32 = PI / 6.0;
+// The trigger lever swings back by this many radians once the arm is
+// cocked, the cam on it pushes the latch over the arm
+const TRIGGER_PIVOT: [f32; 3] = [7.45, 1.35, 3.6];
+const TRIGGER_THROW: f32 = 0.5;
+const TRIGGER_DAMPING: f32 = 0.08;
+const LATCH_TRAVEL: f32 = 0.6;
+// Center of the bucket at the end of the arm, in model coordinates
+const BUCKET_CENTER: [f32; 3] = [3.5, 5.5, 1.75];
+// Where the rope is tied to the arm, in model coordinates
+const ROPE_ANCHOR: [f32; 3] = [3.65, 4.5, 1.75];
+// The rope runs from the top of the drum to the arm
+const ROPE_DRUM: [f32; 3] = [0.5, 0.95, 1.75];
+// The rope winds onto the drum at this distance from its axis
+const DRUM_RADIUS: f32 = 0.15;
+const ROPE_PARTICLES: usize = 16;
+const ROPE_RADIUS: f32 = 0.05;
+const ROPE_SIDES: usize = 6;
+// How much longer the rope is than needed to reach the arm at rest
+const ROPE_SLACK: f32 = 1.15;
+// Clips moving the arm, the file overrides the ones built in
+const ANIMATION_FILE: &'static str = "catapult.anim";
+const DEFAULT_ANIMATIONS: &'static str = include_str!("../catapult.anim");
+// Seconds to blend into the idle clip after recoiling
+const IDLE_BLEND: f32 = 0.5;
+// The catapult turns around the vertical axis through this point
+const AIM_PIVOT: [f32; 3] = [3.65, 0.0, 1.75];
+// Radians per second while turn_left or turn_right is held
+const YAW_SPEED: f32 = 0.5;
+const WHEEL_RADIUS: f32 = 0.6;
+const WHEEL_WIDTH: f32 = 0.25;
+const WHEEL_SIDES: usize = 16;
+// Height of the axles, the wheels reach a bit below the planks
+const AXLE_HEIGHT: f32 = 0.5;
+// The front axle is on the side the catapult throws to
+const FRONT_AXLE: f32 = 1.0;
+const REAR_AXLE: f32 = 6.5;
+// z of the right and the left wheels, outside the planks
+const WHEEL_TRACK: [f32; 2] = [-0.45, 3.95];
+// Driving, in m/s and m/s²
+const MAX_SPEED: f32 = 4.0;
+const MAX_REVERSE_SPEED: f32 = 1.5;
+const DRIVE_ACCELERATION: f32 = 2.0;
+const ROLLING_FRICTION: f32 = 1.5;
+// Radius of the circle driven at full steering and 1 m/s
+const TURN_RADIUS: f32 = 6.0;
+// Names of the parts the joints connect, the planks all belong to the frame
+const FRAME: &'static str = "frame";
+const THROW_ARM: &'static str = "throw_arm";
+const WINDER: &'static str = "winder";
+const DRUM: &'static str = "drum";
+const TRIGGER: &'static str = "trigger";
+const LATCH: &'static str = "latch";
+const AXLES: &'static str = "axles";
+const WHEELS: [&'static str; 4] = ["wheel_rf", "wheel_lf", "wheel_rr", "wheel_lr"];
+// How much more_power and less_power change the launch power
+const POWER_STEP: f32 = 0.1;
+const MIN_POWER: f32 = 0.2;
+
 /**
  * Our catapult! Quite hardcoded right now.
  */
 pub struct Catapult<V, N, I> where
     V: glium::vertex::Vertex, N: glium::vertex::Vertex, I: glium::index::Index
 {
-    throw_rotation: f32,
-    throw_rotation_limits: (f32, f32),
-    throwing: bool,
-    winding_throw_up: bool,
+    // How the moving parts are attached, see the node names below
+    rig: Rig,
+    nodes: Nodes,
+    // How the arm and the trigger drive the other parts
+    linkages: Vec<Linkage>,
+    // The mass properties of the catapult and of the arm for the title,
+    // None until they are first worked out
+    mass: Option<(MassProperties, (MassProperties, f32))>,
+    firing: FiringMachine,
+    // Clips named after the firing states, played when the state is entered
+    clips: Vec<Rc<Clip>>,
+    animation: AnimationPlayer,
+    // The state the current clip was started for
+    animated_state: FiringState,
+    // Where the catapult stands in the world
+    pose: Pose,
+    previous_pose: Pose,
+    // Held actions, turn or steer and in drive mode accelerate and brake
+    turning_left: bool,
+    turning_right: bool,
+    driving_forward: bool,
+    driving_backward: bool,
+    // Whether the catapult drives around instead of winding and aiming
+    driving: bool,
+    speed: f32,
+    terrain: Rc<Terrain>,
+    // How far the arm gets wound back, 1.0 is all the way to the limit
+    power: f32,
+    // The power of the current firing cycle, taken when winding starts
+    wind_power: f32,
     fat_plank_right: DrawObject<V, N, I>,
     fat_plank_left: DrawObject<V, N, I>,
     standup_plank_right: DrawObject<V, N, I>,
     standup_plank_left: DrawObject<V, N, I>,
     stopper_plank: DrawObject<V, N, I>,
+    rear_beam: DrawObject<V, N, I>,
     standup_strut_right: DrawObject<V, N, I>,
     standup_strut_left: DrawObject<V, N, I>,
     throw_arm: DrawObject<V, N, I>,
     // Contains children
     winder: DrawObject<V, N, I>,
+    drum: DrawObject<V, N, I>,
+    trigger: DrawObject<V, N, I>,
+    latch: DrawObject<V, N, I>,
     // Contains children
+    axles: DrawObject<V, N, I>,
+    // Right front, left front, right rear, left rear
+    wheels: Vec<DrawObject<Vertex, Normal, u16>>,
     model_matrix: [[f32; 4]; 4],
+    // The rope between the drum and the arm, the rest is wound onto the drum
+    rope: Rope,
+    // Its vertices get rewritten every frame
+    rope_object: DrawObject<Vertex, Normal, u16>,
 }
 
 impl<V, N, I> Drawable for Catapult<V, N, I> where
     V: glium::vertex::Vertex, N: glium::vertex::Vertex, I: glium::index::Index
 {
-    fn update<'a>(&mut self, keyboard_events: &Vec<glium::glutin::Event>) {
-        for ev in keyboard_events {
-            match ev {
-                &glutin::Event::KeyboardInput(
-                    glutin::ElementState::Pressed, _,
-                    Some(glutin::VirtualKeyCode::Space)
-                ) => {
-                    self.throwing = true;
+    fn update(&mut self, inputs: &[Input], dt: f32) {
+        for input in inputs {
+            match *input {
+                Input::Pressed(Action::Fire) => {
+                    let _ = self.firing.fire();
                 },
-                &glutin::Event::KeyboardInput(
-                    glutin::ElementState::Pressed, _,
-                    Some(glutin::VirtualKeyCode::Back)
-                ) => {
-                    self.winding_throw_up = true;
+                Input::Pressed(Action::Wind) => {
+                    // Winds the arm down when idle, loads it when cocked
+                    match self.firing.state() {
+                        FiringState::Idle if !self.driving => {
+                            self.wind_power = self.power;
+                            self.firing.wind().unwrap();
+                        },
+                        FiringState::Cocked => {
+                            let _ = self.firing.load();
+                        },
+                        _ => {}
+                    }
                 },
+                Input::Pressed(Action::TurnLeft) => self.turning_left = true,
+                Input::Released(Action::TurnLeft) => self.turning_left = false,
+                Input::Pressed(Action::TurnRight) => self.turning_right = true,
+                Input::Released(Action::TurnRight) => self.turning_right = false,
+                Input::Pressed(Action::MorePower) if !self.driving => {
+                    let power = self.power + POWER_STEP;
+                    self.set_power(power);
+                },
+                Input::Pressed(Action::LessPower) if !self.driving => {
+                    let power = self.power - POWER_STEP;
+                    self.set_power(power);
+                },
+                Input::Pressed(Action::DriveForward) => self.driving_forward = true,
+                Input::Released(Action::DriveForward) => self.driving_forward = false,
+                Input::Pressed(Action::DriveBackward) => self.driving_backward = true,
+                Input::Released(Action::DriveBackward) => self.driving_backward = false,
+                Input::Pressed(Action::ToggleDriving) => self.toggle_driving(),
                 _ => {}
             }
         }
 
-        if self.winding_throw_up {
-            if self.throw_rotation > self.throw_rotation_limits.0 + 0.01 {
-                self.throw_rotation -= 0.01;
-            }
-            else {
-                self.winding_throw_up = false;
-            }
+        self.previous_pose = self.pose;
+        self.rig.save_previous();
+        if self.driving {
+            self.drive(dt);
         }
-        else if self.throwing {
-            if self.throw_rotation < self.throw_rotation_limits.1 - 0.15 {
-                self.throw_rotation += 0.15;
-            }
-            else {
-                self.throwing = false;
-            }
+        // Turning while the arm swings would fling the projectile sideways
+        else if self.firing.state() != FiringState::Firing {
+            self.pose.yaw += self.yaw_input() * YAW_SPEED * dt;
+        }
+        self.pose = self.pose.on_terrain(&self.terrain);
+        self.model_matrix = self.pose.matrix();
+
+        self.firing.update(dt);
+        if self.firing.state() != self.animated_state {
+            self.animated_state = self.firing.state();
+            self.start_animation();
+        }
+        else {
+            self.animation.update(dt);
+        }
+        let target = self.arm_target();
+        self.rig.joint_mut(self.nodes.throw_arm).drive(target, dt);
+        let trigger_target = match self.firing.state() {
+            FiringState::Cocked | FiringState::Loaded => TRIGGER_THROW,
+            _ => 0.0,
+        };
+        self.rig.joint_mut(self.nodes.trigger).drive(trigger_target, dt);
+        // Firing lifts the pawl and lets the drum run free
+        let engaged = match self.firing.state() {
+            FiringState::Winding | FiringState::Cocked | FiringState::Loaded => true,
+            _ => false,
+        };
+        let mut clicks = 0;
+        for linkage in self.linkages.iter_mut() {
+            linkage.set_engaged(engaged);
+            clicks += linkage.update(&mut self.rig, dt);
         }
+        for _ in 0..clicks {
+            self.firing.emit(FiringEvent::PawlClicked);
+        }
+        self.update_rope(dt);
+        self.update_mass();
+        if self.firing.should_release() {
+            let arm = self.rig.joint(self.nodes.throw_arm);
+            let position = self.bucket_position(arm.position());
+            let previous = self.bucket_position(arm.previous_position());
+            let velocity = [
+                (position[0] - previous[0]) / dt,
+                (position[1] - previous[1]) / dt,
+                (position[2] - previous[2]) / dt,
+            ];
+            self.firing.emit(FiringEvent::ReleasedProjectile {
+                position: position, velocity: velocity
+            });
+        }
+    }
+
+    /**
+     * The parts a projectile can hit. The throw arm is left out, the
+     * projectile leaves it while it is still moving.
+     */
+    fn colliders(&self, world_matrix: [[f32; 4]; 4]) -> Vec<Collider> {
+        let context = mul_matrices(world_matrix, self.model_matrix);
+        let mut colliders = Vec::new();
+        colliders.extend(self.fat_plank_right.colliders(context));
+        colliders.extend(self.fat_plank_left.colliders(context));
+        colliders.extend(self.standup_plank_right.colliders(context));
+        colliders.extend(self.standup_plank_left.colliders(context));
+        colliders.extend(self.standup_strut_right.colliders(context));
+        colliders.extend(self.standup_strut_left.colliders(context));
+        colliders.extend(self.stopper_plank.colliders(context));
+        colliders.extend(self.rear_beam.colliders(context));
+        colliders.extend(self.winder.colliders(
+            mul_matrices(context, self.rig.matrix(self.nodes.winder, 1.0))
+        ));
+        colliders.extend(self.drum.colliders(
+            mul_matrices(context, self.rig.matrix(self.nodes.drum, 1.0))
+        ));
+        colliders.extend(self.trigger.colliders(
+            mul_matrices(context, self.rig.matrix(self.nodes.trigger, 1.0))
+        ));
+        colliders.extend(self.latch.colliders(
+            mul_matrices(context, self.rig.matrix(self.nodes.latch, 1.0))
+        ));
+        colliders
     }
 
-    fn draw(&self, settings: &Settings, target: &mut glium::Frame, world_matrix: [[f32; 4]; 4])
+    /**
+     * The whole catapult, its frame and every moving part.
+     */
+    fn parts(&self, world_matrix: [[f32; 4]; 4]) -> Vec<(String, Aabb)> {
+        let context = mul_matrices(world_matrix, self.model_matrix);
+        let part_matrix = |node| mul_matrices(context, self.rig.matrix(node, 1.0));
+        let frame = self.frame_parts().iter()
+            .flat_map(|part| part.colliders(context))
+            .collect::<Vec<_>>();
+        let mut parts = vec![
+            ("frame", bounds_of(&frame)),
+            (THROW_ARM, bounds_of(&self.throw_arm.colliders(part_matrix(self.nodes.throw_arm)))),
+            (WINDER, bounds_of(&self.winder.colliders(part_matrix(self.nodes.winder)))),
+            (DRUM, bounds_of(&self.drum.colliders(part_matrix(self.nodes.drum)))),
+            (TRIGGER, bounds_of(&self.trigger.colliders(part_matrix(self.nodes.trigger)))),
+            (LATCH, bounds_of(&self.latch.colliders(part_matrix(self.nodes.latch)))),
+            (AXLES, bounds_of(&self.axles.colliders(part_matrix(self.nodes.axles)))),
+        ];
+        for (i, wheel) in self.wheels.iter().enumerate() {
+            let bounds = bounds_of(&wheel.colliders(part_matrix(self.nodes.wheels[i])));
+            parts.push((WHEELS[i], bounds));
+        }
+        let parts = parts.into_iter()
+            .filter_map(|(name, bounds)| bounds.map(|bounds| (name.to_string(), bounds)))
+            .collect::<Vec<_>>();
+        let whole = parts.iter().skip(1).fold(parts[0].1, |whole, part| whole.union(&part.1));
+        let mut result = vec![("catapult".to_string(), whole)];
+        result.extend(parts);
+        result
+    }
+
+    fn draw(
+            &self,
+            settings: &Settings,
+            viewport: &Viewport,
+            target: &mut glium::Frame,
+            world_matrix: [[f32; 4]; 4]
+    )
         -> Result<(), glium::DrawError>
     {
-        let context = mul_matrices(world_matrix, self.model_matrix);
-        self.fat_plank_right.draw(settings, target, context).unwrap();
-        self.fat_plank_left.draw(settings, target, context).unwrap();
-        self.standup_plank_right.draw(settings, target, context).unwrap();
-        self.standup_plank_left.draw(settings, target, context).unwrap();
-        self.standup_strut_right.draw(settings, target, context).unwrap();
-        self.standup_strut_left.draw(settings, target, context).unwrap();
-        self.stopper_plank.draw(settings, target, context).unwrap();
-        let rot = quaternion::axis_angle(
-            [0.0, 0.0, 1.0], self.throw_rotation
+        let pose = self.previous_pose.lerp(&self.pose, settings.alpha);
+        let context = mul_matrices(world_matrix, pose.matrix());
+        self.fat_plank_right.draw(settings, viewport, target, context).unwrap();
+        self.fat_plank_left.draw(settings, viewport, target, context).unwrap();
+        self.standup_plank_right.draw(settings, viewport, target, context).unwrap();
+        self.standup_plank_left.draw(settings, viewport, target, context).unwrap();
+        self.standup_strut_right.draw(settings, viewport, target, context).unwrap();
+        self.standup_strut_left.draw(settings, viewport, target, context).unwrap();
+        self.stopper_plank.draw(settings, viewport, target, context).unwrap();
+        self.rear_beam.draw(settings, viewport, target, context).unwrap();
+        let part_matrix = |node| mul_matrices(context, self.rig.matrix(node, settings.alpha));
+        self.axles.draw(settings, viewport, target, part_matrix(self.nodes.axles)).unwrap();
+        for (wheel, &node) in self.wheels.iter().zip(self.nodes.wheels.iter()) {
+            wheel.draw(settings, viewport, target, part_matrix(node)).unwrap();
+        }
+        self.throw_arm.draw(settings, viewport, target, part_matrix(self.nodes.throw_arm)).unwrap();
+        self.winder.draw(settings, viewport, target, part_matrix(self.nodes.winder)).unwrap();
+        self.drum.draw(settings, viewport, target, part_matrix(self.nodes.drum)).unwrap();
+        self.trigger.draw(settings, viewport, target, part_matrix(self.nodes.trigger)).unwrap();
+        self.latch.draw(settings, viewport, target, part_matrix(self.nodes.latch)).unwrap();
+        self.rope_object.draw(settings, viewport, target, context).unwrap();
+        Ok(())
+    }
+
+    fn prepare(&self, alpha: f32) {
+        self.rope_object.data.positions.write(
+            &gen_tube(&self.rope.interpolated(alpha), ROPE_RADIUS, ROPE_SIDES)
+        );
+    }
+
+    fn status(&self) -> Option<String> {
+        let (mass, (arm, arm_moment)) = self.mass.unwrap();
+        let mode = if self.driving {
+            "Driving, drive_forward and drive_backward drive, turn_left and turn_right steer"
+                .to_string()
+        }
+        else {
+            format!("Launch power {:.0}%", self.power * 100.0)
+        };
+        Some(format!("{}, catapult {:.0} kg, arm {:.0} kg with {:.0} kg·m² about the pivot",
+                     mode, mass.mass, arm.mass, arm_moment))
+    }
+}
+
+impl<V, N, I> Catapult<V, N, I> where
+    V: glium::vertex::Vertex, N: glium::vertex::Vertex, I: glium::index::Index
+{
+    /**
+     * Returns a receiver for the events of the firing cycle.
+     */
+    pub fn subscribe(&mut self) -> Receiver<FiringEvent> {
+        self.firing.subscribe()
+    }
+
+    /**
+     * Drive mode can only be entered while the arm is at rest.
+     */
+    fn toggle_driving(&mut self) {
+        if self.driving {
+            self.driving = false;
+            self.speed = 0.0;
+        }
+        else if self.firing.state() == FiringState::Idle {
+            self.driving = true;
+        }
+    }
+
+    /**
+     * -1.0, 0.0 or 1.0 depending on the held turn actions, positive to the
+     * left.
+     */
+    fn yaw_input(&self) -> f32 {
+        self.turning_left as i32 as f32 - self.turning_right as i32 as f32
+    }
+
+    /**
+     * Like yaw_input for the drive actions, positive forward.
+     */
+    fn throttle_input(&self) -> f32 {
+        self.driving_forward as i32 as f32 - self.driving_backward as i32 as f32
+    }
+
+    /**
+     * Rolls the catapult along the ground for dt seconds.
+     */
+    fn drive(&mut self, dt: f32) {
+        // Going downhill speeds it up
+        let slope = 9.81 * self.pose.pitch.sin();
+        let throttle = self.throttle_input();
+        let mut acceleration = throttle * DRIVE_ACCELERATION + slope;
+        if throttle == 0.0 {
+            let friction = ROLLING_FRICTION * dt;
+            self.speed = if self.speed.abs() < friction {
+                0.0
+            }
+            else {
+                self.speed - friction * self.speed.signum()
+            };
+        }
+        else if throttle * self.speed < 0.0 {
+            // Braking is stronger than accelerating
+            acceleration += throttle * ROLLING_FRICTION;
+        }
+        self.speed = (self.speed + acceleration * dt).max(-MAX_REVERSE_SPEED).min(MAX_SPEED);
+
+        let turn_rate = self.speed * self.yaw_input() / TURN_RADIUS;
+        self.pose.yaw += turn_rate * dt;
+        // Forward is the direction the catapult throws to
+        let forward = [-self.pose.yaw.cos(), self.pose.yaw.sin()];
+        self.pose.position[0] += forward[0] * self.speed * dt;
+        self.pose.position[2] += forward[1] * self.speed * dt;
+
+        // The wheels on the inside of a curve travel less
+        let half_track = (WHEEL_TRACK[1] - WHEEL_TRACK[0]) / 2.0;
+        let right = (self.speed + turn_rate * half_track) * dt;
+        let left = (self.speed - turn_rate * half_track) * dt;
+        for (i, &wheel) in self.nodes.wheels.iter().enumerate() {
+            let travelled = if i % 2 == 0 { right } else { left };
+            self.rig.joint_mut(wheel).advance(travelled / WHEEL_RADIUS);
+        }
+    }
+
+    /**
+     * Sets how far the arm gets wound back from the next winding on.
+     */
+    fn set_power(&mut self, power: f32) {
+        self.power = power.max(MIN_POWER).min(1.0);
+    }
+
+    /**
+     * Plays the clip of the state the firing cycle just entered, stretched
+     * to the duration of the state. States without a clip keep the arm where
+     * the previous one left it, except Idle, which returns it to rest.
+     */
+    fn start_animation(&mut self) {
+        let state = self.firing.state();
+        let name = format!("{:?}", state).to_lowercase();
+        let clip = match self.clips.iter().find(|clip| clip.name == name) {
+            Some(clip) => clip.clone(),
+            None => {
+                if state == FiringState::Idle {
+                    self.animation.stop();
+                }
+                return;
+            },
+        };
+        let config = &self.firing.config;
+        let duration = match state {
+            FiringState::Winding => config.wind_duration,
+            FiringState::Firing => config.fire_duration,
+            FiringState::Recoiling => config.recoil_duration,
+            _ => clip.duration,
+        };
+        let speed = if duration > 0.0 { clip.duration / duration } else { 1.0 };
+        match state {
+            FiringState::Idle => self.animation.crossfade(clip, speed, true, IDLE_BLEND),
+            _ => self.animation.play(clip, speed, false),
+        }
+    }
+
+    /**
+     * Rotation of the throw arm as animated by the clips. While winding and
+     * firing it gets scaled by the power of the shot.
+     */
+    fn arm_target(&self) -> f32 {
+        let joint = self.rig.joint(self.nodes.throw_arm);
+        let rotation = self.animation.sample(&joint.child)
+            .map_or(0.0, |transform| joint.position_for(&transform));
+        match self.firing.state() {
+            FiringState::Winding | FiringState::Cocked | FiringState::Loaded |
+            FiringState::Firing => rotation * self.wind_power,
+            _ => rotation,
+        }
+    }
+
+    /**
+     * The winder reels the rope onto the drum while winding the arm down.
+     * Once fired the drum runs free and the rope goes slack.
+     */
+    fn update_rope(&mut self, dt: f32) {
+        let anchor = transform_point(self.rig.matrix(self.nodes.throw_arm, 1.0), ROPE_ANCHOR);
+        self.rope.length = match self.firing.state() {
+            FiringState::Winding | FiringState::Cocked | FiringState::Loaded => {
+                rope_length(self.rig.joint(self.nodes.drum).position())
+            },
+            _ => rope_length(0.0),
+        };
+        self.rope.update(dt, ROPE_DRUM, anchor);
+    }
+
+    /**
+     * Position of the bucket in world coordinates for the given arm rotation.
+     */
+    fn bucket_position(&self, throw_rotation: f32) -> [f32; 3] {
+        let arm_matrix = mul_matrices(
+            self.model_matrix, self.rig.joint(self.nodes.throw_arm).matrix_at(throw_rotation)
         );
-        self.throw_arm.draw(
-            settings, target,
-            mul_matrices(context, rot_matrix_by(&rot, [3.65, 0.5, 0.0]))
-        ).unwrap();
-        let rot = quaternion::axis_angle(
-            [0.0, 0.0, 1.0], self.throw_rotation * 4.0
+        transform_point(arm_matrix, BUCKET_CENTER)
+    }
+
+    /**
+     * The planks that don't move.
+     */
+    fn frame_parts(&self) -> [&DrawObject<V, N, I>; 8] {
+        [
+            &self.fat_plank_right, &self.fat_plank_left,
+            &self.standup_plank_right, &self.standup_plank_left,
+            &self.standup_strut_right, &self.standup_strut_left,
+            &self.stopper_plank, &self.rear_beam,
+        ]
+    }
+
+    /**
+     * Mass properties of the whole catapult in model coordinates, with the
+     * moving parts where they are now. The rope is too light to count.
+     */
+    pub fn mass_properties(&self) -> MassProperties {
+        let n = self.nodes;
+        let moving = [
+            (&self.throw_arm, n.throw_arm, ASH_DENSITY),
+            (&self.winder, n.winder, PINE_DENSITY),
+            (&self.drum, n.drum, PINE_DENSITY),
+            (&self.trigger, n.trigger, PINE_DENSITY),
+            (&self.latch, n.latch, PINE_DENSITY),
+            (&self.axles, n.axles, OAK_DENSITY),
+        ];
+        let mut total = NO_MASS;
+        for part in self.frame_parts().iter() {
+            total = total.combine(&part.mass_properties(DEFAULT_MATRIX, OAK_DENSITY));
+        }
+        for &(part, node, density) in moving.iter() {
+            total = total.combine(&part.mass_properties(self.rig.matrix(node, 1.0), density));
+        }
+        for (wheel, &node) in self.wheels.iter().zip(n.wheels.iter()) {
+            let wheel = wheel.mass_properties(self.rig.matrix(node, 1.0), OAK_DENSITY);
+            total = total.combine(&wheel);
+        }
+        total
+    }
+
+    /**
+     * Mass properties of the throw arm with its bucket and axle, and its
+     * moment of inertia around the pivot.
+     */
+    pub fn arm_mass_properties(&self) -> (MassProperties, f32) {
+        let joint = self.rig.joint(self.nodes.throw_arm);
+        let arm = self.throw_arm.mass_properties(
+            self.rig.matrix(self.nodes.throw_arm, 1.0), ASH_DENSITY
         );
-        self.winder.draw(
-            settings, target,
-            mul_matrices(context, rot_matrix_by(&rot, [0.5, 0.8, -0.125]))
-        ).unwrap();
-        Ok(())
+        (arm, arm.moment_about(joint.pivot, joint.axis))
+    }
+
+    /**
+     * Works out the mass properties shown in the title again, once the
+     * parts moved since they last were.
+     */
+    fn update_mass(&mut self) {
+        if self.mass.is_none() || self.rig.moved() {
+            self.mass = Some((self.mass_properties(), self.arm_mass_properties()));
+        }
     }
 }
 
 /**
  * Generates the catapult.
  */
-pub fn init_catapult(display: &glutin_backend::GlutinFacade, _: &Settings)
-    -> Box<Drawable>
+pub fn init_catapult(
+    display: &glutin_backend::GlutinFacade,
+    settings: &Settings,
+    texture: Rc<glium::texture::Texture2d>,
+    firing: FiringConfig
+)
+    -> CatapultObject
 {
-    use std::io::Cursor;
-    use image;
-    let image = image::load(
-        Cursor::new(&include_bytes!("../wood.png")[..]), image::PNG
-    ).unwrap().to_rgba();
-    let image_dimensions = image.dimensions();
-    let image = glium::texture::RawImage2d::from_raw_rgba_reversed(
-        image.into_raw(),
-        image_dimensions
-    );
-    let texture = Rc::new(
-        glium::texture::Texture2d::new(display, image).unwrap()
+    let terrain = settings.world.terrain.clone();
+    let rig = Rig::new(catapult_joints()).unwrap();
+    let nodes = Nodes::resolve(&rig).unwrap();
+    let pose = Pose { position: AIM_PIVOT, yaw: 0.0, pitch: 0.0, roll: 0.0 }
+        .on_terrain(&terrain);
+    let axle_length = (
+        WHEEL_TRACK[0] - WHEEL_WIDTH / 2.0 - 0.1, WHEEL_TRACK[1] + WHEEL_WIDTH / 2.0 + 0.1
     );
 
-    Box::new(Catapult {
-        model_matrix: DEFAULT_MATRIX,
-        fat_plank_right: DrawObject {
-            data: RenderData {
-                positions: VertexBuffer::new(
-                    display, &gen_rectangle((0.0, 0.0, 0.0), (7.5, 1.0, 0.5))
-                ).unwrap(),
-                normals: VertexBuffer::new(display, &NO_NORMALS).unwrap(),
-                indices: IndexBuffer::new(
-                    display, TrianglesList, &RECTANGLE_INDICES
-                ).unwrap(),
-            },
-            model_matrix: DEFAULT_MATRIX,
-            texture: texture.clone(),
-            children: Vec::new(),
-        },
-        fat_plank_left: DrawObject {
-            data: RenderData {
-                positions: VertexBuffer::new(
-                        display, &gen_rectangle((0.0, 0.0, 3.0), (7.5, 1.0, 3.5))
-                    ).unwrap(),
-                normals: VertexBuffer::new(display, &NO_NORMALS).unwrap(),
-                indices: IndexBuffer::new(
-                        display, TrianglesList, &RECTANGLE_INDICES
-                    ).unwrap(),
-            },
-            model_matrix: DEFAULT_MATRIX,
-            texture: texture.clone(),
-            children: Vec::new(),
-        },
-        standup_plank_right: DrawObject {
-            data: RenderData {
-                positions: VertexBuffer::new(
-                    display, &gen_rectangle((4.0, 1.0, 0.0), (5.0, 3.75, 0.5))
-                ).unwrap(),
-                normals: VertexBuffer::new(display, &NO_NORMALS).unwrap(),
-                indices: IndexBuffer::new(
-                    display, TrianglesList, &RECTANGLE_INDICES
-                ).unwrap(),
-            },
-            model_matrix: DEFAULT_MATRIX,
-            texture: texture.clone(),
-            children: Vec::new(),
-        },
-        standup_plank_left: DrawObject {
-            data: RenderData {
-                positions: VertexBuffer::new(
-                    display, &gen_rectangle((4.0, 1.0, 3.0), (5.0, 3.75, 3.5))
-                ).unwrap(),
-                normals: VertexBuffer::new(display, &NO_NORMALS).unwrap(),
-                indices: IndexBuffer::new(
-                    display, TrianglesList, &RECTANGLE_INDICES
-                ).unwrap(),
-            },
-            model_matrix: DEFAULT_MATRIX,
-            texture: texture.clone(),
-            children: Vec::new(),
-        },
-        stopper_plank: DrawObject {
-            data: RenderData {
-                positions: VertexBuffer::new(
-                    display, &gen_rectangle((3.75, 3.0, -0.25), (4.5, 3.5, 3.75))
-                ).unwrap(),
-                normals: VertexBuffer::new(display, &NO_NORMALS).unwrap(),
-                indices: IndexBuffer::new(
-                    display, TrianglesList, &RECTANGLE_INDICES
-                ).unwrap(),
-            },
-            model_matrix: DEFAULT_MATRIX,
-            texture: texture.clone(),
-            children: Vec::new(),
-        },
+    let strut_matrix = [
+        [0.55f32.cos(), 0.55f32.sin(), 0.0, 0.0],
+        [-0.55f32.sin(), 0.55f32.cos(), 0.0, 0.0],
+        [0.0, 0.0, 1.0, 0.0],
+        [1.9, -3.0, 0.0, 1.0],
+    ];
+
+    let mut catapult = Catapult {
+        model_matrix: pose.matrix(),
+        fat_plank_right: gen_box(
+            display, &texture, (0.0, 0.0, 0.0), (7.5, 1.0, 0.5)
+        ),
+        fat_plank_left: gen_box(
+            display, &texture, (0.0, 0.0, 3.0), (7.5, 1.0, 3.5)
+        ),
+        standup_plank_right: gen_box(
+            display, &texture, (4.0, 1.0, 0.0), (5.0, 3.75, 0.5)
+        ),
+        standup_plank_left: gen_box(
+            display, &texture, (4.0, 1.0, 3.0), (5.0, 3.75, 3.5)
+        ),
+        stopper_plank: gen_box(
+            display, &texture, (3.75, 3.0, -0.25), (4.5, 3.5, 3.75)
+        ),
+        rear_beam: gen_box(
+            display, &texture, (7.0, 1.0, 0.0), (7.4, 1.3, 3.5)
+        ),
         standup_strut_right: DrawObject {
-            data: RenderData {
-                positions: VertexBuffer::new(
-                    display, &gen_rectangle((5.5, 1.0, 0.1), (6.0, 4.0, 0.4))
-                ).unwrap(),
-                normals: VertexBuffer::new(display, &NO_NORMALS).unwrap(),
-                indices: IndexBuffer::new(
-                    display, TrianglesList, &RECTANGLE_INDICES
-                ).unwrap(),
-            },
-            model_matrix: [
-                [0.55f32.cos(), 0.55f32.sin(), 0.0, 0.0],
-                [-0.55f32.sin(), 0.55f32.cos(), 0.0, 0.0],
-                [0.0, 0.0, 1.0, 0.0],
-                [1.9, -3.0, 0.0, 1.0],
-            ],
-            texture: texture.clone(),
-            children: Vec::new(),
+            model_matrix: strut_matrix,
+            .. gen_box(display, &texture, (5.5, 1.0, 0.1), (6.0, 4.0, 0.4))
         },
         standup_strut_left: DrawObject {
-            data: RenderData {
-                positions: VertexBuffer::new(
-                    display, &gen_rectangle((5.5, 1.0, 3.1), (6.0, 4.0, 3.4))
-                ).unwrap(),
-                normals: VertexBuffer::new(display, &NO_NORMALS).unwrap(),
-                indices: IndexBuffer::new(
-                    display, TrianglesList, &RECTANGLE_INDICES
-                ).unwrap(),
-            },
-            model_matrix: [
-                [0.55f32.cos(), 0.55f32.sin(), 0.0, 0.0],
-                [-0.55f32.sin(), 0.55f32.cos(), 0.0, 0.0],
-                [0.0, 0.0, 1.0, 0.0],
-                [1.9, -3.0, 0.0, 1.0],
-            ],
-            texture: texture.clone(),
-            children: Vec::new(),
+            model_matrix: strut_matrix,
+            .. gen_box(display, &texture, (5.5, 1.0, 3.1), (6.0, 4.0, 3.4))
         },
         throw_arm: DrawObject {
-            data: RenderData {
-                positions: VertexBuffer::new(
-                    display, &gen_rectangle((3.5, 0.5, 1.5), (3.8, 5.15, 2.0))
-                ).unwrap(),
-                normals: VertexBuffer::new(display, &NO_NORMALS).unwrap(),
-                indices: IndexBuffer::new(
-                    display, TrianglesList, &RECTANGLE_INDICES
-                ).unwrap(),
-            },
-            model_matrix: DEFAULT_MATRIX,
-            texture: texture.clone(),
             children: vec![
-                DrawObject {
-                    data: RenderData {
-                        positions: VertexBuffer::new(
-                            display, &gen_rectangle((3.25, 5.0, 1.25), (3.75, 6.0, 2.25))
-                        ).unwrap(),
-                        normals: VertexBuffer::new(display, &NO_NORMALS).unwrap(),
-                        indices: IndexBuffer::new(
-                            display, TrianglesList, &RECTANGLE_INDICES
-                        ).unwrap(),
-                    },
-                    model_matrix: DEFAULT_MATRIX,
-                    texture: texture.clone(),
-                    children: Vec::new(),
-                },
-                DrawObject {
-                    data: RenderData {
-                        positions: VertexBuffer::new(
-                            display, &gen_rectangle((3.4, 0.25, -0.25), (3.9, 0.75, 3.75))
-                        ).unwrap(),
-                        normals: VertexBuffer::new(display, &NO_NORMALS).unwrap(),
-                        indices: IndexBuffer::new(
-                            display, TrianglesList, &RECTANGLE_INDICES
-                        ).unwrap(),
-                    },
-                    model_matrix: DEFAULT_MATRIX,
-                    texture: texture.clone(),
-                    children: Vec::new(),
-                },
+                // Bucket
+                gen_box(display, &texture, (3.25, 5.0, 1.25), (3.75, 6.0, 2.25)),
+                // Axle
+                gen_box(display, &texture, (3.4, 0.25, -0.25), (3.9, 0.75, 3.75)),
             ],
+            .. gen_box(display, &texture, (3.5, 0.5, 1.5), (3.8, 5.15, 2.0))
         },
         winder: DrawObject {
+            children: vec![
+                gen_box(display, &texture, (-0.3, 0.7, -0.2), (1.3, 0.9, -0.05)),
+            ],
+            .. gen_box(display, &texture, (0.2, 0.5, -0.25), (0.8, 1.1, 0.0))
+        },
+        // The rope winds onto it
+        drum: gen_box(display, &texture, (0.35, 0.65, 0.0), (0.65, 0.95, 3.5)),
+        trigger: gen_box(display, &texture, (7.4, 1.3, 3.5), (7.5, 2.1, 3.7)),
+        latch: gen_box(
+            display, &texture, (7.05, 1.45, 2.3), (7.35, 1.6, 3.5)
+        ),
+        axles: DrawObject {
+            children: vec![
+                gen_box(
+                    display, &texture,
+                    (REAR_AXLE - 0.1, AXLE_HEIGHT - 0.1, axle_length.0),
+                    (REAR_AXLE + 0.1, AXLE_HEIGHT + 0.1, axle_length.1)
+                ),
+            ],
+            .. gen_box(
+                display, &texture,
+                (FRONT_AXLE - 0.1, AXLE_HEIGHT - 0.1, axle_length.0),
+                (FRONT_AXLE + 0.1, AXLE_HEIGHT + 0.1, axle_length.1)
+            )
+        },
+        wheels: (0..4).map(|i| gen_wheel(
+            display, &texture, wheel_center(i), WHEEL_RADIUS, WHEEL_WIDTH, WHEEL_SIDES
+        )).collect(),
+        rope: Rope::new(ROPE_DRUM, ROPE_ANCHOR, ROPE_PARTICLES, rope_length(0.0)),
+        rope_object: DrawObject {
             data: RenderData {
-                positions: VertexBuffer::new(
-                    display, &gen_rectangle((0.2, 0.5, -0.25), (0.8, 1.1, 0.0))
+                positions: VertexBuffer::dynamic(
+                    display, &gen_tube(&[ROPE_DRUM; ROPE_PARTICLES], ROPE_RADIUS, ROPE_SIDES)
                 ).unwrap(),
                 normals: VertexBuffer::new(display, &NO_NORMALS).unwrap(),
                 indices: IndexBuffer::new(
-                    display, TrianglesList, &RECTANGLE_INDICES
+                    display, TrianglesList, &tube_indices(ROPE_PARTICLES, ROPE_SIDES)
                 ).unwrap(),
             },
-            // model_matrix: DEFAULT_MATRIX,
             model_matrix: DEFAULT_MATRIX,
             texture: texture.clone(),
-            children: vec![
-                DrawObject {
-                    data: RenderData {
-                        positions: VertexBuffer::new(
-                            display, &gen_rectangle((-0.3, 0.7, -0.2), (1.3, 0.9, -0.05))
-                        ).unwrap(),
-                        normals: VertexBuffer::new(display, &NO_NORMALS).unwrap(),
-                        indices: IndexBuffer::new(
-                            display, TrianglesList, &RECTANGLE_INDICES
-                        ).unwrap(),
-                    },
-                    model_matrix: DEFAULT_MATRIX,
-                    texture: texture.clone(),
-                    children: Vec::new(),
-                },
+            children: Vec::new(),
+            bounds: Aabb::new((0.0, 0.0, 0.0), (0.0, 0.0, 0.0)),
+            material: WOOD,
+        },
+        linkages: vec![
+            Linkage::ratchet(nodes.throw_arm, nodes.drum, DRUM_RATIO, RATCHET_TOOTH, -1.0),
+            Linkage::gear(nodes.drum, nodes.winder, CRANK_RATIO),
+            Linkage::cam(
+                nodes.trigger, nodes.latch, vec![(0.1, 0.0), (TRIGGER_THROW, LATCH_TRAVEL)]
+            ),
+        ],
+        rig: rig,
+        nodes: nodes,
+        mass: None,
+        firing: FiringMachine::new(firing),
+        clips: load_animations(),
+        animation: AnimationPlayer::new(),
+        animated_state: FiringState::Idle,
+        pose: pose,
+        previous_pose: pose,
+        turning_left: false,
+        turning_right: false,
+        driving_forward: false,
+        driving_backward: false,
+        driving: false,
+        speed: 0.0,
+        terrain: terrain,
+        power: 1.0,
+        wind_power: 1.0,
+    };
+    catapult.update_mass();
+    catapult
+}
+
+/**
+ * The joints of the moving parts in the rig, resolved from their names once.
+ */
+#[derive(Copy, Clone)]
+struct Nodes {
+    throw_arm: usize,
+    winder: usize,
+    drum: usize,
+    trigger: usize,
+    latch: usize,
+    axles: usize,
+    // In the order of WHEELS
+    wheels: [usize; 4],
+}
+
+impl Nodes {
+    fn resolve(rig: &Rig) -> Result<Nodes, String> {
+        Ok(Nodes {
+            throw_arm: rig.index(THROW_ARM)?,
+            winder: rig.index(WINDER)?,
+            drum: rig.index(DRUM)?,
+            trigger: rig.index(TRIGGER)?,
+            latch: rig.index(LATCH)?,
+            axles: rig.index(AXLES)?,
+            wheels: [
+                rig.index(WHEELS[0])?, rig.index(WHEELS[1])?,
+                rig.index(WHEELS[2])?, rig.index(WHEELS[3])?,
+            ],
+        })
+    }
+}
+
+/**
+ * How the moving parts hang together. The arm turns between the stopper and
+ * the rear beam, the latch slides in from the left over the wound arm
+ * and the trigger lever next to it pushes it there.
+ */
+fn catapult_joints() -> Vec<Joint> {
+    let mut joints = vec![
+        Joint::hinge(FRAME, THROW_ARM, ARM_PIVOT, [0.0, 0.0, 1.0]).with_limits(ARM_LIMIT, 0.0),
+        Joint::hinge(FRAME, WINDER, WINDER_PIVOT, [0.0, 0.0, 1.0]),
+        Joint::hinge(FRAME, DRUM, WINDER_PIVOT, [0.0, 0.0, 1.0]),
+        Joint::hinge(FRAME, TRIGGER, TRIGGER_PIVOT, [0.0, 0.0, 1.0])
+            .with_limits(0.0, TRIGGER_THROW)
+            .with_damping(TRIGGER_DAMPING),
+        Joint::slider(FRAME, LATCH, [0.0, 0.0, -1.0]).with_limits(0.0, LATCH_TRAVEL),
+        Joint::fixed(FRAME, AXLES),
+    ];
+    for (i, &node) in WHEELS.iter().enumerate() {
+        joints.push(Joint::hinge(AXLES, node, wheel_center(i), [0.0, 0.0, 1.0]));
+    }
+    joints
+}
+
+/**
+ * The clips from ANIMATION_FILE, or the built in ones if it is missing or
+ * broken. Without any clips the arm stays where it is.
+ */
+fn load_animations() -> Vec<Rc<Clip>> {
+    let clips = if Path::new(ANIMATION_FILE).exists() {
+        load_clips(ANIMATION_FILE).or_else(|e| {
+            println!("{}, using the built in animations", e);
+            parse_clips(DEFAULT_ANIMATIONS)
+        })
+    }
+    else {
+        parse_clips(DEFAULT_ANIMATIONS)
+    };
+    match clips {
+        Ok(clips) => clips.into_iter().map(Rc::new).collect(),
+        Err(e) => {
+            println!("Built in animations:{}", e);
+            Vec::new()
+        },
+    }
+}
+
+/**
+ * Where the catapult stands: AIM_PIVOT is on the ground at position, the
+ * catapult is turned by yaw around the vertical axis and tilted by pitch
+ * (around its axles) and roll to rest on all four wheels.
+ */
+#[derive(Copy, Clone)]
+struct Pose {
+    position: [f32; 3],
+    yaw: f32,
+    pitch: f32,
+    roll: f32,
+}
+
+impl Pose {
+    fn lerp(&self, to: &Pose, alpha: f32) -> Pose {
+        Pose {
+            position: [
+                lerp(self.position[0], to.position[0], alpha),
+                lerp(self.position[1], to.position[1], alpha),
+                lerp(self.position[2], to.position[2], alpha),
             ],
+            yaw: lerp(self.yaw, to.yaw, alpha),
+            pitch: lerp(self.pitch, to.pitch, alpha),
+            roll: lerp(self.roll, to.roll, alpha),
+        }
+    }
+
+    /**
+     * The pose with height and tilt taken from the ground under the wheels.
+     */
+    fn on_terrain(&self, terrain: &Terrain) -> Pose {
+        let (sin, cos) = self.yaw.sin_cos();
+        let height_under = |x: f32, z: f32| {
+            let dx = x - AIM_PIVOT[0];
+            let dz = z - AIM_PIVOT[2];
+            terrain.height_at(
+                self.position[0] + dx * cos + dz * sin,
+                self.position[2] - dx * sin + dz * cos
+            )
+        };
+        let right_front = height_under(FRONT_AXLE, WHEEL_TRACK[0]);
+        let left_front = height_under(FRONT_AXLE, WHEEL_TRACK[1]);
+        let right_rear = height_under(REAR_AXLE, WHEEL_TRACK[0]);
+        let left_rear = height_under(REAR_AXLE, WHEEL_TRACK[1]);
+
+        let front = (right_front + left_front) / 2.0;
+        let rear = (right_rear + left_rear) / 2.0;
+        let right = (right_front + right_rear) / 2.0;
+        let left = (left_front + left_rear) / 2.0;
+        Pose {
+            position: [self.position[0], (front + rear) / 2.0, self.position[2]],
+            yaw: self.yaw,
+            pitch: (rear - front).atan2(REAR_AXLE - FRONT_AXLE),
+            roll: (right - left).atan2(WHEEL_TRACK[1] - WHEEL_TRACK[0]),
+        }
+    }
+
+    fn matrix(&self) -> [[f32; 4]; 4] {
+        let rot = quaternion::mul(
+            quaternion::axis_angle([0.0, 1.0, 0.0], self.yaw),
+            quaternion::mul(
+                quaternion::axis_angle([0.0, 0.0, 1.0], self.pitch),
+                quaternion::axis_angle([1.0, 0.0, 0.0], self.roll)
+            )
+        );
+        // The bottom of the wheels below AIM_PIVOT touches the ground
+        let ground = [AIM_PIVOT[0], AXLE_HEIGHT - WHEEL_RADIUS, AIM_PIVOT[2]];
+        mul_matrices(
+            translation_matrix(self.position),
+            mul_matrices(
+                rot_matrix_by(&rot, [0.0, 0.0, 0.0]),
+                translation_matrix([-ground[0], -ground[1], -ground[2]])
+            )
+        )
+    }
+}
+
+/**
+ * Center of one of the wheels, in the order of `Catapult::wheels`.
+ */
+fn wheel_center(wheel: usize) -> [f32; 3] {
+    let x = if wheel < 2 { FRONT_AXLE } else { REAR_AXLE };
+    [x, AXLE_HEIGHT, WHEEL_TRACK[wheel % 2]]
+}
+
+/**
+ * Generates a wheel: a cylinder around the z axis through center.
+ */
+pub fn gen_wheel(
+    display: &glutin_backend::GlutinFacade,
+    texture: &Rc<glium::texture::Texture2d>,
+    center: [f32; 3],
+    radius: f32,
+    width: f32,
+    sides: usize
+)
+    -> DrawObject<Vertex, Normal, u16>
+{
+    let mut vertices = Vec::with_capacity(sides * 2 + 2);
+    for &dz in [-width / 2.0, width / 2.0].iter() {
+        vertices.push(Vertex {
+            position: (center[0], center[1], center[2] + dz), tex_coords: [0.5, 0.5]
+        });
+        for i in 0..sides {
+            let angle = i as f32 / sides as f32 * 2.0 * PI;
+            vertices.push(Vertex {
+                position: (
+                    center[0] + angle.cos() * radius,
+                    center[1] + angle.sin() * radius,
+                    center[2] + dz
+                ),
+                tex_coords: [0.5 + angle.cos() * 0.5, 0.5 + angle.sin() * 0.5],
+            });
+        }
+    }
+    let back = sides as u16 + 1;
+    let mut indices = Vec::with_capacity(sides * 12);
+    for i in 0..sides as u16 {
+        let a = 1 + i;
+        let b = 1 + (i + 1) % sides as u16;
+        // The two caps and the tread between them
+        indices.extend_from_slice(&[0, a, b]);
+        indices.extend_from_slice(&[back, back + b, back + a]);
+        indices.extend_from_slice(&[a, back + a, back + b, a, back + b, b]);
+    }
+
+    DrawObject {
+        data: RenderData {
+            positions: VertexBuffer::new(display, &vertices).unwrap(),
+            normals: VertexBuffer::new(display, &NO_NORMALS).unwrap(),
+            indices: IndexBuffer::new(display, TrianglesList, &indices).unwrap(),
+        },
+        model_matrix: DEFAULT_MATRIX,
+        texture: texture.clone(),
+        children: Vec::new(),
+        bounds: Aabb::new(
+            (center[0] - radius, center[1] - radius, center[2] - width / 2.0),
+            (center[0] + radius, center[1] + radius, center[2] + width / 2.0)
+        ),
+        material: WOOD,
+    }
+}
+
+/**
+ * Generates a textured box between the two given points.
+ */
+pub fn gen_box(
+    display: &glutin_backend::GlutinFacade,
+    texture: &Rc<glium::texture::Texture2d>,
+    min: (f32, f32, f32),
+    max: (f32, f32, f32)
+)
+    -> DrawObject<Vertex, Normal, u16>
+{
+    DrawObject {
+        data: RenderData {
+            positions: VertexBuffer::new(display, &gen_rectangle(min, max)).unwrap(),
+            normals: VertexBuffer::new(display, &NO_NORMALS).unwrap(),
+            indices: IndexBuffer::new(
+                display, TrianglesList, &RECTANGLE_INDICES
+            ).unwrap(),
         },
-        throw_rotation: 0.0,
-        throw_rotation_limits: (-(PI / 2.0) + (PI / 16.0), 0.0),
-        throwing: false,
-        winding_throw_up: false,
-    })
+        model_matrix: DEFAULT_MATRIX,
+        texture: texture.clone(),
+        children: Vec::new(),
+        bounds: Aabb::new(min, max),
+        material: WOOD,
+    }
+}
+
+/**
+ * Length of the rope from the drum to the arm with the drum turned by the
+ * given angle, winding turns it backwards.
+ */
+fn rope_length(drum_rotation: f32) -> f32 {
+    distance(ROPE_DRUM, ROPE_ANCHOR) * ROPE_SLACK + drum_rotation.min(0.0) * DRUM_RADIUS
+}
+
+fn distance(a: [f32; 3], b: [f32; 3]) -> f32 {
+    let d = [a[0] - b[0], a[1] - b[1], a[2] - b[2]];
+    (d[0] * d[0] + d[1] * d[1] + d[2] * d[2]).sqrt()
 }
 
 /*
@@ -348,9 +1027,9 @@ pub fn gen_rectangle(min: (f32, f32, f32), max: (f32, f32, f32)) -> [Vertex; 8]
     ]
 }
 
-const NO_NORMALS: [Normal; 0] = [];
+pub const NO_NORMALS: [Normal; 0] = [];
 
-const RECTANGLE_INDICES: [u16; 36] = [
+pub const RECTANGLE_INDICES: [u16; 36] = [
    // Bottom
    0, 1, 2,
    0, 3, 2,
@@ -370,3 +1049,37 @@ const RECTANGLE_INDICES: [u16; 36] = [
    0, 4, 7,
    0, 3, 7,
 ];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_moving_part_has_a_joint() {
+        let rig = Rig::new(catapult_joints()).unwrap();
+        assert!(Nodes::resolve(&rig).is_ok());
+    }
+
+    #[test]
+    fn winding_reels_the_rope_in() {
+        let rig = Rig::new(catapult_joints()).unwrap();
+        let arm = rig.joint(Nodes::resolve(&rig).unwrap().throw_arm);
+        assert!(rope_length(0.0) > distance(ROPE_DRUM, ROPE_ANCHOR));
+        for &wound in [0.5, 1.0].iter() {
+            let anchor = transform_point(arm.matrix_at(ARM_LIMIT * wound), ROPE_ANCHOR);
+            let taut = distance(ROPE_DRUM, anchor);
+            let length = rope_length(ARM_LIMIT * wound * DRUM_RATIO);
+            // Never pulls the arm further than it is wound, sags a little at most
+            assert!(length > taut && length < taut + 0.5, "{} m of rope for {} m", length, taut);
+        }
+    }
+
+    #[test]
+    fn built_in_animations_parse() {
+        let clips = parse_clips(DEFAULT_ANIMATIONS).unwrap();
+        for name in ["winding", "firing", "recoiling"].iter() {
+            let clip = clips.iter().find(|clip| clip.name == *name).expect(name);
+            assert!(clip.sample(THROW_ARM, 0.0).is_some(), "{} moves the arm", name);
+        }
+    }
+}
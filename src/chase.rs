@@ -0,0 +1,193 @@
+use std::sync::mpsc::Receiver;
+use vecmath::{Vector3, vec3_add, vec3_len, vec3_scale, vec3_sub};
+use firing::FiringEvent;
+use world::WorldEvent;
+
+// Where the camera wants to be while following, behind and above
+const CHASE_DISTANCE: f32 = 8.0;
+const CHASE_HEIGHT: f32 = 3.0;
+// Roughly the seconds the camera takes to catch up with where it wants to be
+const SMOOTH_TIME: f32 = 0.4;
+// Seconds to look at the impact before giving the camera back
+const HOLD_TIME: f32 = 2.0;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Phase {
+    Waiting,
+    Following,
+    // Seconds left to look at the impact
+    Holding(f32),
+}
+
+/**
+ * Follows the newest projectile from behind and above, then looks at where
+ * it landed for a moment. The camera moves on a critically damped spring,
+ * so it catches up quickly without overshooting.
+ */
+pub struct Chase {
+    pub enabled: bool,
+    phase: Phase,
+    // Id of the projectile being followed
+    chased: Option<u64>,
+    position: Vector3<f32>,
+    velocity: Vector3<f32>,
+    // Where the spring pulls the camera to
+    goal: Vector3<f32>,
+    look_at: Vector3<f32>,
+    catapult_events: Vec<Receiver<FiringEvent>>,
+    world_events: Vec<Receiver<WorldEvent>>,
+}
+
+impl Chase {
+    pub fn new() -> Chase {
+        Chase {
+            enabled: false,
+            phase: Phase::Waiting,
+            chased: None,
+            position: [0.0, 0.0, 0.0],
+            velocity: [0.0, 0.0, 0.0],
+            goal: [0.0, 0.0, 0.0],
+            look_at: [0.0, 0.0, 0.0],
+            catapult_events: Vec::new(),
+            world_events: Vec::new(),
+        }
+    }
+
+    /**
+     * Starts chasing on every release and holds on every landing.
+     */
+    pub fn listen(&mut self, catapult_events: Receiver<FiringEvent>,
+                  world_events: Receiver<WorldEvent>) {
+        self.catapult_events.push(catapult_events);
+        self.world_events.push(world_events);
+    }
+
+    pub fn toggle(&mut self) {
+        self.enabled = !self.enabled;
+        if !self.enabled {
+            self.phase = Phase::Waiting;
+        }
+    }
+
+    /**
+     * Advances the chase by dt seconds. The projectile is the id, position
+     * and velocity of the newest one, camera_position where the camera is
+     * when a chase starts. Returns where the camera should be and the point
+     * it should look at, None when there is nothing to chase.
+     */
+    pub fn update(&mut self, dt: f32, camera_position: Vector3<f32>,
+                  projectile: Option<(u64, Vector3<f32>, Vector3<f32>)>)
+        -> Option<(Vector3<f32>, Vector3<f32>)>
+    {
+        let released = self.catapult_events.iter()
+            .flat_map(|events| events.try_iter())
+            .any(|event| match event {
+                FiringEvent::ReleasedProjectile { .. } => true,
+                _ => false,
+            });
+        let landings = self.world_events.iter()
+            .flat_map(|events| events.try_iter())
+            .collect::<Vec<_>>();
+        if !self.enabled {
+            return None;
+        }
+        if let (true, Some((id, _, _))) = (released, projectile) {
+            self.phase = Phase::Following;
+            self.chased = Some(id);
+            self.position = camera_position;
+            self.velocity = [0.0, 0.0, 0.0];
+        }
+        let chased = self.chased;
+        let landed = landings.into_iter()
+            .filter_map(|event| match event {
+                WorldEvent::Landed { projectile, position } => {
+                    if Some(projectile) == chased { Some(position) } else { None }
+                },
+            })
+            .last();
+        // Once it is gone there is nothing more to follow
+        let projectile = projectile.and_then(|(id, position, velocity)| {
+            if Some(id) == chased { Some((position, velocity)) } else { None }
+        });
+
+        match self.phase {
+            Phase::Waiting => return None,
+            Phase::Following => {
+                if let Some(point) = landed {
+                    self.phase = Phase::Holding(HOLD_TIME);
+                    self.look_at = point;
+                }
+                else if let Some((position, velocity)) = projectile {
+                    self.look_at = position;
+                    self.goal = vec3_add(
+                        vec3_sub(position, vec3_scale(behind(velocity), CHASE_DISTANCE)),
+                        [0.0, CHASE_HEIGHT, 0.0]
+                    );
+                }
+                else {
+                    self.phase = Phase::Holding(HOLD_TIME);
+                }
+            },
+            Phase::Holding(left) => {
+                if left <= dt {
+                    self.phase = Phase::Waiting;
+                    return None;
+                }
+                self.phase = Phase::Holding(left - dt);
+            },
+        }
+
+        // Critically damped spring towards the goal
+        let omega = 2.0 / SMOOTH_TIME;
+        let acceleration = vec3_sub(
+            vec3_scale(vec3_sub(self.goal, self.position), omega * omega),
+            vec3_scale(self.velocity, 2.0 * omega)
+        );
+        self.velocity = vec3_add(self.velocity, vec3_scale(acceleration, dt));
+        self.position = vec3_add(self.position, vec3_scale(self.velocity, dt));
+        Some((self.position, self.look_at))
+    }
+}
+
+/**
+ * The horizontal direction of flight, the camera stays behind it.
+ */
+fn behind(velocity: Vector3<f32>) -> Vector3<f32> {
+    let horizontal = [velocity[0], 0.0, velocity[2]];
+    let length = vec3_len(horizontal);
+    if length < 0.01 {
+        [1.0, 0.0, 0.0]
+    }
+    else {
+        vec3_scale(horizontal, 1.0 / length)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc::channel;
+
+    #[test]
+    fn only_the_chased_projectile_landing_ends_following() {
+        let (firing, catapult_events) = channel();
+        let (world, world_events) = channel();
+        let mut chase = Chase::new();
+        chase.listen(catapult_events, world_events);
+        chase.toggle();
+        let velocity = [10.0, 5.0, 0.0];
+        firing.send(FiringEvent::ReleasedProjectile { position: [0.0; 3], velocity: velocity })
+            .unwrap();
+        let flying = Some((2, [1.0, 1.0, 0.0], velocity));
+        assert!(chase.update(0.01, [-5.0, 1.0, 0.0], flying).is_some());
+
+        world.send(WorldEvent::Landed { projectile: 1, position: [3.0, 0.0, 0.0] }).unwrap();
+        chase.update(0.01, [0.0; 3], flying);
+        assert_eq!(chase.phase, Phase::Following);
+
+        world.send(WorldEvent::Landed { projectile: 2, position: [30.0, 0.0, 0.0] }).unwrap();
+        let (_, look_at) = chase.update(0.01, [0.0; 3], flying).unwrap();
+        assert_eq!(look_at, [30.0, 0.0, 0.0]);
+        assert!(chase.update(HOLD_TIME, [0.0; 3], flying).is_none());
+    }
+}
@@ -0,0 +1,85 @@
+use std::time::Instant;
+
+// Frames taking longer than this get cut short, so a hiccup doesn't make the
+// simulation try to catch up forever
+const MAX_FRAME_TIME: f32 = 0.25;
+
+/**
+ * Runs the simulation at a fixed timestep independent of the frame rate.
+ *
+ * Real time gets collected in an accumulator and consumed in steps of
+ * `timestep`. What is left over tells how far rendering is between the last
+ * two simulation states.
+ */
+pub struct Clock {
+    timestep: f32,
+    accumulator: f32,
+    last_frame: Instant,
+    tick: u64,
+}
+
+impl Clock {
+    pub fn new(timestep: f32) -> Clock {
+        Clock {
+            timestep: timestep,
+            accumulator: 0.0,
+            last_frame: Instant::now(),
+            tick: 0,
+        }
+    }
+
+    /**
+     * Seconds per simulation step.
+     */
+    pub fn timestep(&self) -> f32 {
+        self.timestep
+    }
+
+    /**
+     * Number of simulation steps run since the start, the current step when
+     * called during one.
+     */
+    pub fn tick(&self) -> u64 {
+        self.tick
+    }
+
+    /**
+     * Adds the time passed since the last frame.
+     */
+    pub fn advance(&mut self) {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last_frame);
+        self.last_frame = now;
+        let seconds = elapsed.as_secs() as f32 +
+            elapsed.subsec_nanos() as f32 / 1_000_000_000.0;
+        self.accumulator += seconds.min(MAX_FRAME_TIME);
+    }
+
+    /**
+     * Takes one simulation step from the collected time. Returns false when
+     * there isn't enough left, the rest waits for the next frame.
+     */
+    pub fn step(&mut self) -> bool {
+        if self.accumulator < self.timestep {
+            return false;
+        }
+        self.accumulator -= self.timestep;
+        self.tick += 1;
+        true
+    }
+
+    /**
+     * How far the current frame is between the previous and the current
+     * simulation state, from 0.0 to 1.0.
+     */
+    pub fn alpha(&self) -> f32 {
+        self.accumulator / self.timestep
+    }
+}
+
+/**
+ * Linear interpolation between two values.
+ */
+pub fn lerp(from: f32, to: f32, alpha: f32) -> f32 {
+    from + (to - from) * alpha
+}
@@ -0,0 +1,498 @@
+use vecmath::{Vector3, vec3_add, vec3_sub, vec3_scale, vec3_dot, vec3_cross, vec3_len};
+use matrix::{transform_point, transform_vector};
+use std::rc::Rc;
+use terrain::Terrain;
+
+/**
+ * Collision detection between spheres (the projectiles) and the geometry of
+ * the scene. All shapes are in world coordinates.
+ */
+
+#[derive(Copy, Clone, Debug)]
+pub struct Sphere {
+    pub center: Vector3<f32>,
+    pub radius: f32,
+}
+
+/**
+ * Axis aligned box, used for the local bounds of the drawn boxes.
+ */
+#[derive(Copy, Clone, Debug)]
+pub struct Aabb {
+    pub min: Vector3<f32>,
+    pub max: Vector3<f32>,
+}
+
+impl Aabb {
+    pub fn new(min: (f32, f32, f32), max: (f32, f32, f32)) -> Aabb {
+        Aabb { min: [min.0, min.1, min.2], max: [max.0, max.1, max.2] }
+    }
+
+    pub fn center(&self) -> Vector3<f32> {
+        vec3_scale(vec3_add(self.min, self.max), 0.5)
+    }
+
+    pub fn half_extents(&self) -> Vector3<f32> {
+        vec3_scale(vec3_sub(self.max, self.min), 0.5)
+    }
+
+    /**
+     * The smallest box containing both.
+     */
+    pub fn union(&self, other: &Aabb) -> Aabb {
+        Aabb {
+            min: [
+                self.min[0].min(other.min[0]),
+                self.min[1].min(other.min[1]),
+                self.min[2].min(other.min[2]),
+            ],
+            max: [
+                self.max[0].max(other.max[0]),
+                self.max[1].max(other.max[1]),
+                self.max[2].max(other.max[2]),
+            ],
+        }
+    }
+
+    /**
+     * The box moved into the space of the given (column-major) matrix.
+     * Assumes the matrix doesn't scale.
+     */
+    pub fn transformed(&self, matrix: [[f32; 4]; 4]) -> Obb {
+        Obb {
+            center: transform_point(matrix, self.center()),
+            axes: [
+                transform_vector(matrix, [1.0, 0.0, 0.0]),
+                transform_vector(matrix, [0.0, 1.0, 0.0]),
+                transform_vector(matrix, [0.0, 0.0, 1.0]),
+            ],
+            half_extents: self.half_extents(),
+        }
+    }
+}
+
+/**
+ * Oriented box, the axes have to be normalized and orthogonal.
+ */
+#[derive(Copy, Clone, Debug)]
+pub struct Obb {
+    pub center: Vector3<f32>,
+    pub axes: [Vector3<f32>; 3],
+    pub half_extents: Vector3<f32>,
+}
+
+impl Obb {
+    /**
+     * The axis aligned box around it.
+     */
+    pub fn bounds(&self) -> Aabb {
+        let mut extent = [0.0; 3];
+        for k in 0..3 {
+            for i in 0..3 {
+                extent[i] += self.axes[k][i].abs() * self.half_extents[k];
+            }
+        }
+        Aabb { min: vec3_sub(self.center, extent), max: vec3_add(self.center, extent) }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct TriangleMesh {
+    pub triangles: Vec<[Vector3<f32>; 3]>,
+}
+
+impl TriangleMesh {
+    /**
+     * Builds the mesh from indexed triangles, transformed by the matrix.
+     */
+    pub fn from_indexed(positions: &[Vector3<f32>], indices: &[u16], matrix: [[f32; 4]; 4])
+        -> TriangleMesh
+    {
+        let triangles = indices.chunks(3).map(|tri| [
+            transform_point(matrix, positions[tri[0] as usize]),
+            transform_point(matrix, positions[tri[1] as usize]),
+            transform_point(matrix, positions[tri[2] as usize]),
+        ]).collect();
+        TriangleMesh { triangles: triangles }
+    }
+}
+
+/**
+ * How bouncy and how rough a surface is.
+ */
+#[derive(Copy, Clone, Debug)]
+pub struct Material {
+    pub restitution: f32,
+    pub friction: f32,
+}
+
+pub const WOOD: Material = Material { restitution: 0.4, friction: 0.5 };
+pub const GRASS: Material = Material { restitution: 0.25, friction: 0.7 };
+// The thrown clay balls are made of it too
+pub const STONE: Material = Material { restitution: 0.5, friction: 0.6 };
+
+impl Material {
+    /**
+     * The material of a contact between two surfaces, mixed like Box2D does.
+     */
+    pub fn combine(&self, other: &Material) -> Material {
+        Material {
+            restitution: self.restitution.max(other.restitution),
+            friction: (self.friction * other.friction).sqrt(),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum Shape {
+    Box(Obb),
+    Mesh(TriangleMesh),
+    Terrain(Rc<Terrain>),
+}
+
+#[derive(Clone, Debug)]
+pub struct Collider {
+    pub shape: Shape,
+    pub material: Material,
+}
+
+/**
+ * The axis aligned box around the boxes among the colliders, None if there
+ * are none.
+ */
+pub fn bounds_of(colliders: &[Collider]) -> Option<Aabb> {
+    colliders.iter().fold(None, |bounds: Option<Aabb>, collider| match collider.shape {
+        Shape::Box(ref obb) => Some(match bounds {
+            Some(bounds) => bounds.union(&obb.bounds()),
+            None => obb.bounds(),
+        }),
+        _ => bounds,
+    })
+}
+
+impl Collider {
+    pub fn collide_sphere(&self, sphere: &Sphere) -> Option<Contact> {
+        match self.shape {
+            Shape::Box(ref obb) => sphere_obb(sphere, obb),
+            Shape::Mesh(ref mesh) => sphere_mesh(sphere, mesh),
+            Shape::Terrain(ref terrain) => sphere_terrain(sphere, terrain),
+        }
+    }
+}
+
+/**
+ * A touching point. The normal points from the obstacle towards the sphere,
+ * depth is how far they overlap.
+ */
+#[derive(Copy, Clone, Debug)]
+pub struct Contact {
+    pub point: Vector3<f32>,
+    pub normal: Vector3<f32>,
+    pub depth: f32,
+}
+
+pub fn sphere_obb(sphere: &Sphere, obb: &Obb) -> Option<Contact> {
+    let offset = vec3_sub(sphere.center, obb.center);
+    let local = [
+        vec3_dot(offset, obb.axes[0]),
+        vec3_dot(offset, obb.axes[1]),
+        vec3_dot(offset, obb.axes[2]),
+    ];
+    let mut clamped = local;
+    let mut inside = true;
+    for i in 0..3 {
+        let e = obb.half_extents[i];
+        if clamped[i] < -e {
+            clamped[i] = -e;
+            inside = false;
+        }
+        else if clamped[i] > e {
+            clamped[i] = e;
+            inside = false;
+        }
+    }
+
+    if inside {
+        // The center is in the box, push out along the nearest face
+        let mut axis = 0;
+        let mut min_distance = ::std::f32::MAX;
+        for i in 0..3 {
+            let distance = obb.half_extents[i] - local[i].abs();
+            if distance < min_distance {
+                min_distance = distance;
+                axis = i;
+            }
+        }
+        let sign = if local[axis] < 0.0 { -1.0 } else { 1.0 };
+        let normal = vec3_scale(obb.axes[axis], sign);
+        return Some(Contact {
+            point: vec3_add(sphere.center, vec3_scale(normal, min_distance)),
+            normal: normal,
+            depth: min_distance + sphere.radius,
+        });
+    }
+
+    let closest = vec3_add(obb.center, vec3_add(
+        vec3_scale(obb.axes[0], clamped[0]),
+        vec3_add(vec3_scale(obb.axes[1], clamped[1]), vec3_scale(obb.axes[2], clamped[2]))
+    ));
+    // The face the center is outside of
+    let axis = (0..3).find(|&i| clamped[i] != local[i]).unwrap_or(0);
+    let face_normal = vec3_scale(obb.axes[axis], clamped[axis].signum());
+    contact_to_point(sphere, closest, face_normal)
+}
+
+pub fn sphere_triangle(sphere: &Sphere, triangle: &[Vector3<f32>; 3]) -> Option<Contact> {
+    let closest = closest_point_on_triangle(sphere.center, triangle);
+    contact_to_point(sphere, closest, triangle_normal(triangle))
+}
+
+/**
+ * Normal of the side the corners go around counter-clockwise. Degenerate
+ * triangles face up.
+ */
+pub fn triangle_normal(tri: &[Vector3<f32>; 3]) -> Vector3<f32> {
+    let normal = vec3_cross(vec3_sub(tri[1], tri[0]), vec3_sub(tri[2], tri[0]));
+    let length = vec3_len(normal);
+    if length > 0.0 {
+        vec3_scale(normal, 1.0 / length)
+    }
+    else {
+        [0.0, 1.0, 0.0]
+    }
+}
+
+/**
+ * The deepest contact with any of the triangles.
+ */
+pub fn sphere_mesh(sphere: &Sphere, mesh: &TriangleMesh) -> Option<Contact> {
+    let mut deepest: Option<Contact> = None;
+    for triangle in mesh.triangles.iter() {
+        if let Some(contact) = sphere_triangle(sphere, triangle) {
+            let deeper = match deepest {
+                Some(ref d) => contact.depth > d.depth,
+                None => true,
+            };
+            if deeper {
+                deepest = Some(contact);
+            }
+        }
+    }
+    deepest
+}
+
+/**
+ * The deepest contact with the triangles of the terrain under the sphere.
+ * A sphere that tunneled below the surface gets pushed back up.
+ */
+pub fn sphere_terrain(sphere: &Sphere, terrain: &Terrain) -> Option<Contact> {
+    let c = sphere.center;
+    let r = sphere.radius;
+    let height = terrain.height_at(c[0], c[2]);
+    // Also catches centers right on the ground, which give no direction
+    if c[1] <= height {
+        let normal = terrain.normal_at(c[0], c[2]);
+        return Some(Contact {
+            point: [c[0], height, c[2]],
+            normal: normal,
+            depth: (height - c[1]) * normal[1] + r,
+        });
+    }
+    let triangles = terrain.triangles_in([c[0] - r, c[2] - r], [c[0] + r, c[2] + r]);
+    sphere_mesh(sphere, &TriangleMesh { triangles: triangles })
+}
+
+/**
+ * The contact with the closest point of a surface. A center right on the
+ * surface gets pushed out along the normal of the face.
+ */
+fn contact_to_point(sphere: &Sphere, point: Vector3<f32>, face_normal: Vector3<f32>)
+    -> Option<Contact>
+{
+    let offset = vec3_sub(sphere.center, point);
+    let distance = vec3_len(offset);
+    if distance >= sphere.radius {
+        return None;
+    }
+    let normal = if distance > 0.0 { vec3_scale(offset, 1.0 / distance) } else { face_normal };
+    Some(Contact {
+        point: point,
+        normal: normal,
+        depth: sphere.radius - distance,
+    })
+}
+
+/**
+ * From Ericson, Real-Time Collision Detection, 5.1.5
+ */
+pub fn closest_point_on_triangle(p: Vector3<f32>, tri: &[Vector3<f32>; 3]) -> Vector3<f32> {
+    let (a, b, c) = (tri[0], tri[1], tri[2]);
+    let ab = vec3_sub(b, a);
+    let ac = vec3_sub(c, a);
+    let ap = vec3_sub(p, a);
+    let d1 = vec3_dot(ab, ap);
+    let d2 = vec3_dot(ac, ap);
+    if d1 <= 0.0 && d2 <= 0.0 {
+        return a;
+    }
+
+    let bp = vec3_sub(p, b);
+    let d3 = vec3_dot(ab, bp);
+    let d4 = vec3_dot(ac, bp);
+    if d3 >= 0.0 && d4 <= d3 {
+        return b;
+    }
+
+    let vc = d1 * d4 - d3 * d2;
+    if vc <= 0.0 && d1 >= 0.0 && d3 <= 0.0 {
+        let v = d1 / (d1 - d3);
+        return vec3_add(a, vec3_scale(ab, v));
+    }
+
+    let cp = vec3_sub(p, c);
+    let d5 = vec3_dot(ab, cp);
+    let d6 = vec3_dot(ac, cp);
+    if d6 >= 0.0 && d5 <= d6 {
+        return c;
+    }
+
+    let vb = d5 * d2 - d1 * d6;
+    if vb <= 0.0 && d2 >= 0.0 && d6 <= 0.0 {
+        let w = d2 / (d2 - d6);
+        return vec3_add(a, vec3_scale(ac, w));
+    }
+
+    let va = d3 * d6 - d5 * d4;
+    if va <= 0.0 && (d4 - d3) >= 0.0 && (d5 - d6) >= 0.0 {
+        let w = (d4 - d3) / ((d4 - d3) + (d5 - d6));
+        return vec3_add(b, vec3_scale(vec3_sub(c, b), w));
+    }
+
+    let denom = 1.0 / (va + vb + vc);
+    let v = vb * denom;
+    let w = vc * denom;
+    vec3_add(a, vec3_add(vec3_scale(ab, v), vec3_scale(ac, w)))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use config::Config;
+    use matrix::rot_matrix_by;
+    use quaternion;
+
+    fn close(a: Vector3<f32>, b: Vector3<f32>) -> bool {
+        (0..3).all(|i| (a[i] - b[i]).abs() < 1e-4)
+    }
+
+    fn unit_box() -> Obb {
+        Aabb::new((-1.0, -1.0, -1.0), (1.0, 1.0, 1.0)).transformed(IDENTITY)
+    }
+
+    const IDENTITY: [[f32; 4]; 4] = [
+        [1.0, 0.0, 0.0, 0.0],
+        [0.0, 1.0, 0.0, 0.0],
+        [0.0, 0.0, 1.0, 0.0],
+        [0.0, 0.0, 0.0, 1.0],
+    ];
+
+    // Wound counter-clockwise seen from above
+    const FLOOR: [Vector3<f32>; 3] = [[0.0, 0.0, 0.0], [0.0, 0.0, 2.0], [2.0, 0.0, 0.0]];
+
+    fn sphere(center: Vector3<f32>, radius: f32) -> Sphere {
+        Sphere { center: center, radius: radius }
+    }
+
+    #[test]
+    fn spheres_touch_box_faces_and_edges() {
+        let contact = sphere_obb(&sphere([1.4, 0.2, 0.0], 0.5), &unit_box()).unwrap();
+        assert!(close(contact.normal, [1.0, 0.0, 0.0]));
+        assert!(close(contact.point, [1.0, 0.2, 0.0]));
+        assert!((contact.depth - 0.1).abs() < 1e-4);
+        assert!(sphere_obb(&sphere([1.6, 0.0, 0.0], 0.5), &unit_box()).is_none());
+
+        let contact = sphere_obb(&sphere([1.2, 1.2, 0.0], 0.5), &unit_box()).unwrap();
+        let diagonal = 0.5f32.sqrt();
+        assert!(close(contact.normal, [diagonal, diagonal, 0.0]));
+        assert!((contact.depth - (0.5 - 0.08f32.sqrt())).abs() < 1e-4);
+        assert!(sphere_obb(&sphere([1.4, 1.4, 0.0], 0.5), &unit_box()).is_none());
+    }
+
+    #[test]
+    fn spheres_inside_boxes_leave_through_the_nearest_face() {
+        let contact = sphere_obb(&sphere([0.2, -0.7, 0.1], 0.5), &unit_box()).unwrap();
+        assert!(close(contact.normal, [0.0, -1.0, 0.0]));
+        assert!((contact.depth - 0.8).abs() < 1e-4);
+
+        let contact = sphere_obb(&sphere([0.0, 0.0, 1.0], 0.5), &unit_box()).unwrap();
+        assert!(close(contact.normal, [0.0, 0.0, 1.0]));
+        assert!((contact.depth - 0.5).abs() < 1e-4);
+    }
+
+    #[test]
+    fn rotated_boxes_collide_along_their_axes() {
+        let obb = Aabb::new((-1.0, -1.0, -1.0), (1.0, 1.0, 1.0))
+            .transformed(rot_matrix_by(&quaternion::axis_angle([0.0, 1.0, 0.0], 0.785), [0.0; 3]));
+        let axis = obb.axes[0];
+        let contact = sphere_obb(&sphere(vec3_scale(axis, 1.3), 0.5), &obb).unwrap();
+        assert!(close(contact.normal, axis));
+        assert!((contact.depth - 0.2).abs() < 1e-4);
+        // Just past the corner of the box the unrotated one would have
+        assert!(sphere_obb(&sphere([1.3, 0.0, 1.3], 0.5), &obb).is_none());
+    }
+
+    #[test]
+    fn closest_points_lie_on_corners_edges_or_the_face() {
+        assert!(close(closest_point_on_triangle([-1.0, 0.0, -1.0], &FLOOR), FLOOR[0]));
+        assert!(close(closest_point_on_triangle([0.0, 0.0, 3.0], &FLOOR), FLOOR[1]));
+        assert!(close(closest_point_on_triangle([3.0, 1.0, -0.5], &FLOOR), FLOOR[2]));
+        assert!(close(closest_point_on_triangle([-1.0, 0.0, 1.0], &FLOOR), [0.0, 0.0, 1.0]));
+        assert!(close(closest_point_on_triangle([1.0, 0.0, -1.0], &FLOOR), [1.0, 0.0, 0.0]));
+        assert!(close(closest_point_on_triangle([2.0, 0.0, 2.0], &FLOOR), [1.0, 0.0, 1.0]));
+        assert!(close(closest_point_on_triangle([0.5, 2.0, 0.5], &FLOOR), [0.5, 0.0, 0.5]));
+    }
+
+    #[test]
+    fn spheres_touch_triangles_and_meshes() {
+        let contact = sphere_triangle(&sphere([0.5, 0.3, 0.5], 0.5), &FLOOR).unwrap();
+        assert!(close(contact.normal, [0.0, 1.0, 0.0]));
+        assert!((contact.depth - 0.2).abs() < 1e-4);
+        assert!(sphere_triangle(&sphere([0.5, 0.6, 0.5], 0.5), &FLOOR).is_none());
+
+        // A center right on the face has no direction to the closest point
+        let contact = sphere_triangle(&sphere([0.5, 0.0, 0.5], 0.5), &FLOOR).unwrap();
+        assert!(close(contact.normal, [0.0, 1.0, 0.0]));
+        assert!((contact.depth - 0.5).abs() < 1e-4);
+
+        let wall = [[0.0, 0.0, 0.0], [0.0, 2.0, 0.0], [0.0, 0.0, 2.0]];
+        let mesh = TriangleMesh { triangles: vec![FLOOR, wall] };
+        let contact = sphere_mesh(&sphere([0.2, 0.4, 0.5], 0.5), &mesh).unwrap();
+        assert!(close(contact.normal, [1.0, 0.0, 0.0]));
+        assert!((contact.depth - 0.3).abs() < 1e-4);
+    }
+
+    #[test]
+    fn spheres_rest_on_and_get_pushed_out_of_the_terrain() {
+        let flat = Terrain::from_config(&Config::parse("[terrain]\nhill_height = 0"));
+        let contact = sphere_terrain(&sphere([10.0, 0.3, 10.0], 0.5), &flat).unwrap();
+        assert!(close(contact.normal, [0.0, 1.0, 0.0]));
+        assert!((contact.depth - 0.2).abs() < 1e-4);
+        assert!(sphere_terrain(&sphere([10.0, 0.6, 10.0], 0.5), &flat).is_none());
+
+        for &height in [0.0, -2.0].iter() {
+            let contact = sphere_terrain(&sphere([10.0, height, 10.0], 0.5), &flat).unwrap();
+            assert!(close(contact.normal, [0.0, 1.0, 0.0]));
+            assert!((contact.depth - 0.5 + height).abs() < 1e-4);
+        }
+
+        // Out in the hills, resting on the slope
+        let hills = Terrain::from_config(&Config::parse(""));
+        let (x, z) = (-119.3, 80.6);
+        let normal = hills.normal_at(x, z);
+        let center = vec3_add([x, hills.height_at(x, z), z], vec3_scale(normal, 0.4));
+        let contact = sphere_terrain(&sphere(center, 0.5), &hills).unwrap();
+        assert!(close(contact.normal, normal));
+        assert!((contact.depth - 0.1).abs() < 1e-3);
+    }
+}
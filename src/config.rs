@@ -0,0 +1,150 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::prelude::*;
+use std::str::FromStr;
+
+pub const CONFIG_FILE: &'static str = "catapult.cfg";
+
+/**
+ * Settings read from a simple ini-like file:
+ *
+ * ```text
+ * # Comment
+ * [section]
+ * key = value
+ * list = 1.0, 2.0, 3.0
+ * ```
+ *
+ * Keys outside of a section belong to the section "".
+ */
+pub struct Config {
+    values: HashMap<(String, String), String>,
+}
+
+impl Config {
+    pub fn empty() -> Config {
+        Config { values: HashMap::new() }
+    }
+
+    /**
+     * Loads the file at path. A missing file gives an empty config, so every
+     * lookup falls back to its default.
+     */
+    pub fn load(path: &str) -> Config {
+        let mut string = String::new();
+        match File::open(path) {
+            Ok(mut file) => {
+                file.read_to_string(&mut string).unwrap();
+            },
+            Err(_) => return Config::empty(),
+        };
+        Config::parse(&string)
+    }
+
+    pub fn parse(text: &str) -> Config {
+        let mut values = HashMap::new();
+        let mut section = String::new();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if line.starts_with('[') && line.ends_with(']') {
+                section = line[1..line.len() - 1].trim().to_string();
+                continue;
+            }
+            match line.find('=') {
+                Some(index) => {
+                    let key = line[..index].trim().to_string();
+                    let value = line[index + 1..].trim().to_string();
+                    values.insert((section.clone(), key), value);
+                },
+                None => println!("{}:{}: Ignoring line without '='", CONFIG_FILE, number + 1),
+            }
+        }
+        Config { values: values }
+    }
+
+    pub fn get_str(&self, section: &str, key: &str) -> Option<&str> {
+        self.values.get(&(section.to_string(), key.to_string())).map(|v| v.as_str())
+    }
+
+    /**
+     * The value parsed as T, or the default if it is missing or malformed.
+     */
+    pub fn get<T: FromStr>(&self, section: &str, key: &str, default: T) -> T {
+        match self.get_str(section, key) {
+            Some(value) => match value.parse() {
+                Ok(parsed) => parsed,
+                Err(_) => {
+                    println!("{}: [{}] {} = {} is invalid", CONFIG_FILE, section, key, value);
+                    default
+                },
+            },
+            None => default,
+        }
+    }
+
+    /**
+     * A comma separated list, or the default if it is missing or malformed.
+     */
+    pub fn get_list<T: FromStr>(&self, section: &str, key: &str, default: Vec<T>) -> Vec<T> {
+        match self.get_str(section, key) {
+            Some(value) => {
+                let parsed = value.split(',')
+                    .map(|item| item.trim().parse())
+                    .collect::<Result<Vec<T>, _>>();
+                match parsed {
+                    Ok(list) => list,
+                    Err(_) => {
+                        println!("{}: [{}] {} = {} is invalid", CONFIG_FILE, section, key, value);
+                        default
+                    },
+                }
+            },
+            None => default,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEXT: &'static str = "
+        top = 1
+        # comment
+        [range]
+        enabled = true
+        distances = 20, 35.5 , 50
+        broken = 1, x
+        [ wind ]
+        speed = fast
+        no equals sign
+    ";
+
+    #[test]
+    fn values_belong_to_their_section() {
+        let config = Config::parse(TEXT);
+        assert_eq!(config.get_str("", "top"), Some("1"));
+        assert_eq!(config.get("range", "enabled", false), true);
+        assert_eq!(config.get_str("range", "top"), None);
+        assert_eq!(config.get_str("wind", "speed"), Some("fast"));
+    }
+
+    #[test]
+    fn get_falls_back_to_the_default() {
+        let config = Config::parse(TEXT);
+        assert_eq!(config.get("wind", "speed", 3.0f32), 3.0);
+        assert_eq!(config.get("wind", "missing", 7u32), 7);
+        assert_eq!(Config::empty().get("range", "enabled", true), true);
+    }
+
+    #[test]
+    fn get_list_splits_at_commas() {
+        let config = Config::parse(TEXT);
+        assert_eq!(config.get_list("range", "distances", vec![1.0f32]), vec![20.0, 35.5, 50.0]);
+        assert_eq!(config.get_list("range", "broken", vec![1u32]), vec![1]);
+        assert_eq!(config.get_list("range", "missing", vec![2u32, 3]), vec![2, 3]);
+    }
+}
@@ -0,0 +1,206 @@
+use vecmath::{Vector3, vec3_add, vec3_scale};
+use collision::{Collider, Shape, Sphere};
+use config::Config;
+use terrain::Terrain;
+
+// Pushing out of one obstacle can push into another, this many tries settle it
+const ITERATIONS: usize = 4;
+
+/**
+ * Keeps the camera out of the scenery, read from the [camera] section: it
+ * stays clearance meters above the ground, within the terrain and below the
+ * ceiling, and gets pushed out of obstacles like a sphere of the radius.
+ * toggle_constraints toggles it.
+ */
+#[derive(Copy, Clone, Debug)]
+pub struct Constraints {
+    pub enabled: bool,
+    pub clearance: f32,
+    pub ceiling: f32,
+    pub radius: f32,
+}
+
+impl Constraints {
+    pub fn from_config(config: &Config) -> Constraints {
+        Constraints {
+            enabled: config.get("camera", "constrained", false),
+            clearance: config.get("camera", "clearance", 0.5f32).max(0.0),
+            ceiling: config.get("camera", "ceiling", 200.0),
+            radius: config.get("camera", "radius", 0.3f32).max(0.01),
+        }
+    }
+
+    pub fn toggle(&mut self) {
+        self.enabled = !self.enabled;
+    }
+
+    /**
+     * Moves the position to the nearest allowed one. Returns it together
+     * with the normals of the surfaces it got pushed off, empty if it
+     * already was allowed.
+     */
+    pub fn apply(&self, position: Vector3<f32>, terrain: &Terrain, obstacles: &[Collider])
+        -> (Vector3<f32>, Vec<Vector3<f32>>)
+    {
+        let mut p = position;
+        let mut normals = Vec::new();
+        if !self.enabled {
+            return (p, normals);
+        }
+        for _ in 0..ITERATIONS {
+            let mut pushed = false;
+            for obstacle in obstacles.iter() {
+                if let Shape::Terrain(_) = obstacle.shape {
+                    continue;
+                }
+                let sphere = Sphere { center: p, radius: self.radius };
+                if let Some(contact) = obstacle.collide_sphere(&sphere) {
+                    p = vec3_add(p, vec3_scale(contact.normal, contact.depth));
+                    normals.push(contact.normal);
+                    pushed = true;
+                }
+            }
+            if !pushed {
+                break;
+            }
+        }
+
+        // Horizontally the world ends with the terrain
+        let size = terrain.cells as f32 * terrain.cell_size;
+        for &(i, origin) in [(0, terrain.origin[0]), (2, terrain.origin[1])].iter() {
+            if p[i] < origin {
+                p[i] = origin;
+                normals.push(axis(i, 1.0));
+            }
+            else if p[i] > origin + size {
+                p[i] = origin + size;
+                normals.push(axis(i, -1.0));
+            }
+        }
+        let ground = terrain.height_at(p[0], p[2]) + self.clearance;
+        if p[1] < ground {
+            p[1] = ground;
+            normals.push(terrain.normal_at(p[0], p[2]));
+        }
+        if p[1] > self.ceiling {
+            p[1] = self.ceiling;
+            normals.push(axis(1, -1.0));
+        }
+        (p, normals)
+    }
+}
+
+fn axis(i: usize, sign: f32) -> Vector3<f32> {
+    let mut axis = [0.0, 0.0, 0.0];
+    axis[i] = sign;
+    axis
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::rc::Rc;
+    use collision::{Aabb, GRASS, WOOD};
+
+    const IDENTITY: [[f32; 4]; 4] = [
+        [1.0, 0.0, 0.0, 0.0],
+        [0.0, 1.0, 0.0, 0.0],
+        [0.0, 0.0, 1.0, 0.0],
+        [0.0, 0.0, 0.0, 1.0],
+    ];
+
+    fn constraints() -> Constraints {
+        Constraints::from_config(&Config::parse(
+            "[camera]\nconstrained = true\nclearance = 0.5\nceiling = 50\nradius = 0.3"
+        ))
+    }
+
+    fn flat() -> Terrain {
+        Terrain::from_config(&Config::parse("[terrain]\nhill_height = 0"))
+    }
+
+    fn crate_at(min: (f32, f32, f32), max: (f32, f32, f32)) -> Collider {
+        Collider { shape: Shape::Box(Aabb::new(min, max).transformed(IDENTITY)), material: WOOD }
+    }
+
+    fn close(a: Vector3<f32>, b: Vector3<f32>) -> bool {
+        (0..3).all(|i| (a[i] - b[i]).abs() < 1e-4)
+    }
+
+    #[test]
+    fn disabled_constraints_leave_the_camera_alone() {
+        let mut constraints = constraints();
+        constraints.toggle();
+        let inside = [0.5, -3.0, 0.5];
+        let obstacles = [crate_at((0.0, 0.0, 0.0), (1.0, 1.0, 1.0))];
+        let (p, normals) = constraints.apply(inside, &flat(), &obstacles);
+        assert_eq!(p, inside);
+        assert!(normals.is_empty());
+    }
+
+    #[test]
+    fn the_camera_keeps_its_clearance_above_the_ground() {
+        let constraints = constraints();
+        let (p, normals) = constraints.apply([3.0, 0.2, -4.0], &flat(), &[]);
+        assert!(close(p, [3.0, 0.5, -4.0]), "{:?}", p);
+        assert_eq!(normals.len(), 1);
+        assert!(close(normals[0], [0.0, 1.0, 0.0]));
+
+        // Among the hills it follows the slope
+        let hills = Terrain::from_config(&Config::parse(""));
+        let (x, z) = (-120.0, 80.0);
+        let ground = hills.height_at(x, z);
+        let (p, normals) = constraints.apply([x, ground, z], &hills, &[]);
+        assert!((p[1] - ground - 0.5).abs() < 1e-4);
+        assert!(close(normals[0], hills.normal_at(x, z)));
+
+        // High enough it is left alone
+        let (p, normals) = constraints.apply([3.0, 0.6, -4.0], &flat(), &[]);
+        assert_eq!(p, [3.0, 0.6, -4.0]);
+        assert!(normals.is_empty());
+    }
+
+    #[test]
+    fn the_world_ends_with_the_terrain_and_the_ceiling() {
+        let terrain = flat();
+        let (low, high) = (terrain.origin[0], terrain.origin[0] + 400.0);
+        let (p, normals) = constraints().apply([high + 5.0, 80.0, low - 5.0], &terrain, &[]);
+        assert!(close(p, [high, 50.0, low]), "{:?}", p);
+        assert_eq!(normals.len(), 3);
+        assert!(close(normals[0], [-1.0, 0.0, 0.0]));
+        assert!(close(normals[1], [0.0, 0.0, 1.0]));
+        assert!(close(normals[2], [0.0, -1.0, 0.0]));
+    }
+
+    #[test]
+    fn the_camera_gets_pushed_out_of_obstacles() {
+        let constraints = constraints();
+        let terrain = flat();
+        let obstacles = [crate_at((0.0, 0.0, 0.0), (2.0, 2.0, 2.0))];
+        // Just inside the top it leaves through the top
+        let (p, normals) = constraints.apply([1.0, 1.9, 1.0], &terrain, &obstacles);
+        assert!(close(p, [1.0, 2.3, 1.0]), "{:?}", p);
+        assert!(close(normals[0], [0.0, 1.0, 0.0]));
+        // Outside but closer than the radius it keeps its distance
+        let (p, _) = constraints.apply([2.1, 1.0, 1.0], &terrain, &obstacles);
+        assert!(close(p, [2.3, 1.0, 1.0]), "{:?}", p);
+
+        // Pushed out of a crate against a wall, it ends up clear of both
+        let obstacles = [
+            crate_at((0.0, 0.0, 0.0), (2.0, 2.0, 2.0)),
+            crate_at((2.0, 0.0, 0.0), (3.0, 4.0, 2.0)),
+        ];
+        let (p, normals) = constraints.apply([1.85, 1.95, 1.0], &terrain, &obstacles);
+        assert!(close(p, [1.7, 2.3, 1.0]), "{:?}", p);
+        assert!(close(normals[0], [0.0, 1.0, 0.0]) && close(normals[1], [-1.0, 0.0, 0.0]));
+        assert!(obstacles.iter().all(|obstacle| {
+            obstacle.collide_sphere(&Sphere { center: p, radius: 0.29 }).is_none()
+        }));
+
+        // The terrain only counts as ground, not as an obstacle to leave
+        let ground = [Collider { shape: Shape::Terrain(Rc::new(flat())), material: GRASS }];
+        let (p, normals) = constraints.apply([1.0, 0.5, 1.0], &terrain, &ground);
+        assert_eq!(p, [1.0, 0.5, 1.0]);
+        assert!(normals.is_empty());
+    }
+}
@@ -1,8 +1,12 @@
 use glium;
 use glium::Surface;
 use state::Settings;
+use viewport::Viewport;
 use std::rc::Rc;
 use matrix::mul_matrices;
+use collision::{Aabb, Collider, Material, Shape};
+use mass::MassProperties;
+use input::Input;
 
 /**
  * Defines Entity-representations for drawable things
@@ -21,15 +25,47 @@ pub struct RenderData<V, N, I> where
  */
 pub trait Drawable {
     /**
-     * Draws the drawable. The world_matrix will be used to transform the
-     * model-matrix, can be used to pass transformations of the parents
+     * Draws the drawable into the viewport. The world_matrix will be used to
+     * transform the model-matrix, can be used to pass transformations of the
+     * parents
      */
-    fn draw(&self, settings: &Settings, target: &mut glium::Frame, world_matrix: [[f32; 4]; 4])
+    fn draw(
+            &self,
+            settings: &Settings,
+            viewport: &Viewport,
+            target: &mut glium::Frame,
+            world_matrix: [[f32; 4]; 4]
+    )
         -> Result<(), glium::DrawError>;
     /**
-     * Updates the Drawable, gets called every tick
+     * Updates the Drawable, gets called every simulation step with the step
+     * length dt in seconds and the input of that step.
+     */
+    fn update(&mut self, inputs: &[Input], dt: f32);
+    /**
+     * The shapes projectiles can collide with, in world coordinates.
+     */
+    fn colliders(&self, _: [[f32; 4]; 4]) -> Vec<Collider> {
+        Vec::new()
+    }
+    /**
+     * Bounds of the parts the camera can focus on by name, in world
+     * coordinates. The first one covers the whole thing.
+     */
+    fn parts(&self, _: [[f32; 4]; 4]) -> Vec<(String, Aabb)> {
+        Vec::new()
+    }
+    /**
+     * Gets called once per frame before drawing into the viewports, with how
+     * far rendering is between the last two simulation steps.
+     */
+    fn prepare(&self, _: f32) {}
+    /**
+     * What the window title should show about the Drawable, if anything.
      */
-    fn update<'a>(&mut self, keyboard_events: &Vec<glium::glutin::Event>);
+    fn status(&self) -> Option<String> {
+        None
+    }
 }
 
 /**
@@ -42,6 +78,29 @@ pub struct DrawObject<V, N, I> where
     pub model_matrix: [[f32; 4]; 4],
     pub texture: Rc<glium::texture::Texture2d>,
     pub children: Vec<DrawObject<V, N, I>>,
+    // Bounds of the own vertices, in model coordinates
+    pub bounds: Aabb,
+    pub material: Material,
+}
+
+impl<V, N, I> DrawObject<V, N, I> where
+    V: glium::vertex::Vertex, N: glium::vertex::Vertex, I: glium::index::Index
+{
+    /**
+     * Mass properties of the object and its children, each a solid box of
+     * its bounds with the density in kg/m³. Like the colliders they are in
+     * the coordinates of the world_matrix.
+     */
+    pub fn mass_properties(&self, world_matrix: [[f32; 4]; 4], density: f32) -> MassProperties {
+        let context_matrix = mul_matrices(world_matrix, self.model_matrix);
+        let mut properties = MassProperties::of_box(
+            &self.bounds.transformed(context_matrix), density
+        );
+        for child in self.children.iter() {
+            properties = properties.combine(&child.mass_properties(context_matrix, density));
+        }
+        properties
+    }
 }
 
 impl<V, N, I> Drawable for DrawObject<V, N, I> where
@@ -49,13 +108,26 @@ impl<V, N, I> Drawable for DrawObject<V, N, I> where
     N: glium::vertex::Vertex,
     I: glium::index::Index,
 {
-    fn update<'b>(&mut self, _: &Vec<glium::glutin::Event>) {
+    fn update(&mut self, _: &[Input], _: f32) {
+
+    }
 
+    fn colliders(&self, world_matrix: [[f32; 4]; 4]) -> Vec<Collider> {
+        let context_matrix = mul_matrices(world_matrix, self.model_matrix);
+        let mut colliders = vec![Collider {
+            shape: Shape::Box(self.bounds.transformed(context_matrix)),
+            material: self.material,
+        }];
+        for child in self.children.iter() {
+            colliders.extend(child.colliders(context_matrix));
+        }
+        colliders
     }
 
     fn draw(
             &self,
             settings: &Settings,
+            viewport: &Viewport,
             target: &mut glium::Frame,
             world_matrix: [[f32; 4]; 4]
     )
@@ -64,18 +136,22 @@ impl<V, N, I> Drawable for DrawObject<V, N, I> where
         let context_matrix = mul_matrices(world_matrix, self.model_matrix);
         let uniforms = uniform! {
             model: context_matrix,
-            view: settings.camera.get_view(),
-            perspective: settings.perspective_matrix(&target),
+            view: viewport.view,
+            perspective: viewport.projection,
             u_light: settings.light,
             tex: &*self.texture
         };
+        let draw_params = glium::DrawParameters {
+            viewport: Some(viewport.rect),
+            .. settings.draw_params.clone()
+        };
         let res = target.draw(
             (&self.data.positions, &self.data.normals), &self.data.indices,
-            &settings.program, &uniforms, &settings.draw_params
+            &settings.program, &uniforms, &draw_params
         );
         if !self.children.is_empty() {
             for i in self.children.iter() {
-                i.draw(settings, target, context_matrix).unwrap();
+                i.draw(settings, viewport, target, context_matrix).unwrap();
             }
         }
         return res;
@@ -0,0 +1,400 @@
+use std::fmt;
+use config::Config;
+use std::sync::mpsc::{channel, Receiver, Sender};
+
+/**
+ * The states the catapult goes through while being operated.
+ *
+ * Idle -> Winding -> Cocked -> Loaded -> Firing -> Recoiling -> Idle
+ *
+ * Firing from Cocked is allowed too, it just throws nothing.
+ */
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum FiringState {
+    Idle,
+    Winding,
+    Cocked,
+    Loaded,
+    Firing,
+    Recoiling,
+}
+
+/**
+ * Things that happen during a firing cycle. Subscribers receive every event
+ * emitted after they subscribed.
+ */
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum FiringEvent {
+    StateChanged { from: FiringState, to: FiringState },
+    // Emitted when the arm lets go of the projectile, in world coordinates.
+    ReleasedProjectile { position: [f32; 3], velocity: [f32; 3] },
+    // Emitted when the arm slams into the stopper plank.
+    HitStopper,
+    // Emitted every time the pawl of the winch clicks over a tooth.
+    PawlClicked,
+}
+
+/**
+ * Returned when a transition is requested that the state machine doesn't allow
+ * from its current state.
+ */
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct InvalidTransition {
+    pub from: FiringState,
+    pub to: FiringState,
+}
+
+impl fmt::Display for InvalidTransition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Cannot go from {:?} to {:?}", self.from, self.to)
+    }
+}
+
+/**
+ * Durations of the timed states in seconds, read from the [firing] section.
+ */
+#[derive(Copy, Clone, Debug)]
+pub struct FiringConfig {
+    pub wind_duration: f32,
+    pub load_duration: f32,
+    pub fire_duration: f32,
+    pub recoil_duration: f32,
+    // Fraction of the firing swing at which the projectile gets released.
+    pub release_at: f32,
+}
+
+impl Default for FiringConfig {
+    fn default() -> FiringConfig {
+        FiringConfig {
+            wind_duration: 2.5,
+            load_duration: 0.5,
+            fire_duration: 0.35,
+            recoil_duration: 0.6,
+            release_at: 0.6,
+        }
+    }
+}
+
+impl FiringConfig {
+    pub fn from_config(config: &Config) -> FiringConfig {
+        let default = FiringConfig::default();
+        let duration = |key, default: f32| config.get("firing", key, default).max(0.0);
+        FiringConfig {
+            wind_duration: duration("wind_duration", default.wind_duration),
+            load_duration: duration("load_duration", default.load_duration),
+            fire_duration: duration("fire_duration", default.fire_duration),
+            recoil_duration: duration("recoil_duration", default.recoil_duration),
+            release_at: config.get("firing", "release_at", default.release_at).max(0.0).min(1.0),
+        }
+    }
+}
+
+/**
+ * Drives the firing cycle of the catapult. Only knows about time and states,
+ * the catapult maps the progress to the rotation of its arm.
+ */
+pub struct FiringMachine {
+    pub config: FiringConfig,
+    state: FiringState,
+    // Seconds spent in the current state
+    elapsed: f32,
+    loading: bool,
+    // Whether a projectile sits in the bucket, from Loaded until released
+    loaded: bool,
+    subscribers: Vec<Sender<FiringEvent>>,
+}
+
+impl FiringMachine {
+    pub fn new(config: FiringConfig) -> FiringMachine {
+        FiringMachine {
+            config: config,
+            state: FiringState::Idle,
+            elapsed: 0.0,
+            loading: false,
+            loaded: false,
+            subscribers: Vec::new(),
+        }
+    }
+
+    pub fn state(&self) -> FiringState {
+        self.state
+    }
+
+    /**
+     * Returns a receiver getting all events emitted from now on.
+     */
+    pub fn subscribe(&mut self) -> Receiver<FiringEvent> {
+        let (sender, receiver) = channel();
+        self.subscribers.push(sender);
+        receiver
+    }
+
+    /**
+     * Progress of the current state from 0.0 to 1.0. States without a
+     * duration are always at 1.0.
+     */
+    pub fn progress(&self) -> f32 {
+        let duration = match self.state {
+            FiringState::Winding => self.config.wind_duration,
+            FiringState::Firing => self.config.fire_duration,
+            FiringState::Recoiling => self.config.recoil_duration,
+            FiringState::Cocked if self.loading => self.config.load_duration,
+            _ => return 1.0,
+        };
+        if duration <= 0.0 {
+            1.0
+        }
+        else {
+            (self.elapsed / duration).min(1.0)
+        }
+    }
+
+    /**
+     * Whether the projectile should get released during this tick. Only
+     * returns true once per loaded shot, never when fired from Cocked.
+     */
+    pub fn should_release(&mut self) -> bool {
+        if !self.loaded || self.state != FiringState::Firing {
+            return false;
+        }
+        if self.progress() >= self.config.release_at {
+            self.loaded = false;
+            return true;
+        }
+        false
+    }
+
+    pub fn wind(&mut self) -> Result<(), InvalidTransition> {
+        self.transition(FiringState::Winding)
+    }
+
+    /**
+     * Starts loading a projectile, the machine becomes Loaded after the
+     * load duration has passed.
+     */
+    pub fn load(&mut self) -> Result<(), InvalidTransition> {
+        if self.state != FiringState::Cocked || self.loading {
+            return Err(InvalidTransition { from: self.state, to: FiringState::Loaded });
+        }
+        self.loading = true;
+        self.elapsed = 0.0;
+        Ok(())
+    }
+
+    pub fn fire(&mut self) -> Result<(), InvalidTransition> {
+        self.loading = false;
+        self.transition(FiringState::Firing)
+    }
+
+    /**
+     * Advances the timed states by dt seconds.
+     */
+    pub fn update(&mut self, dt: f32) {
+        self.elapsed += dt;
+        if self.progress() < 1.0 {
+            return;
+        }
+        match self.state {
+            FiringState::Winding => self.transition(FiringState::Cocked).unwrap(),
+            FiringState::Cocked if self.loading => {
+                self.loading = false;
+                self.transition(FiringState::Loaded).unwrap();
+            },
+            FiringState::Firing => {
+                self.emit(FiringEvent::HitStopper);
+                self.transition(FiringState::Recoiling).unwrap();
+            },
+            FiringState::Recoiling => self.transition(FiringState::Idle).unwrap(),
+            _ => {}
+        }
+    }
+
+    /**
+     * Sends an event to all subscribers, forgetting the ones that went away.
+     */
+    pub fn emit(&mut self, event: FiringEvent) {
+        self.subscribers.retain(|sender| sender.send(event).is_ok());
+    }
+
+    fn transition(&mut self, to: FiringState) -> Result<(), InvalidTransition> {
+        let from = self.state;
+        if !is_valid_transition(from, to) {
+            return Err(InvalidTransition { from: from, to: to });
+        }
+        self.state = to;
+        self.elapsed = 0.0;
+        if to == FiringState::Loaded {
+            self.loaded = true;
+        }
+        self.emit(FiringEvent::StateChanged { from: from, to: to });
+        Ok(())
+    }
+}
+
+fn is_valid_transition(from: FiringState, to: FiringState) -> bool {
+    use self::FiringState::*;
+    match (from, to) {
+        (Idle, Winding) |
+        (Winding, Cocked) |
+        (Cocked, Loaded) |
+        (Cocked, Firing) |
+        (Loaded, Firing) |
+        (Firing, Recoiling) |
+        (Recoiling, Idle) => true,
+        _ => false,
+    }
+}
+
+/**
+ * Follows the firing cycle by its events, for showing it in the window
+ * title: the state and how many teeth the pawl clicked over since winding
+ * started.
+ */
+pub struct FiringStatus {
+    events: Receiver<FiringEvent>,
+    state: FiringState,
+    teeth: u32,
+}
+
+impl FiringStatus {
+    pub fn new(events: Receiver<FiringEvent>) -> FiringStatus {
+        FiringStatus { events: events, state: FiringState::Idle, teeth: 0 }
+    }
+
+    /**
+     * Catches up with the events emitted since the last call.
+     */
+    pub fn update(&mut self) {
+        for event in self.events.try_iter() {
+            match event {
+                FiringEvent::StateChanged { to, .. } => {
+                    if to == FiringState::Winding {
+                        self.teeth = 0;
+                    }
+                    self.state = to;
+                },
+                FiringEvent::PawlClicked => self.teeth += 1,
+                _ => {}
+            }
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        match self.state {
+            FiringState::Winding | FiringState::Cocked | FiringState::Loaded => {
+                format!("{:?}, {} teeth wound", self.state, self.teeth)
+            },
+            state => format!("{:?}", state),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STATES: [FiringState; 6] = [
+        FiringState::Idle, FiringState::Winding, FiringState::Cocked,
+        FiringState::Loaded, FiringState::Firing, FiringState::Recoiling,
+    ];
+
+    fn run(machine: &mut FiringMachine, seconds: f32) -> usize {
+        let mut releases = 0;
+        let steps = (seconds / 0.01).ceil() as usize;
+        for _ in 0..steps {
+            machine.update(0.01);
+            if machine.should_release() {
+                releases += 1;
+            }
+        }
+        releases
+    }
+
+    #[test]
+    fn only_the_cycle_and_firing_unloaded_are_valid() {
+        use self::FiringState::*;
+        let valid = [
+            (Idle, Winding), (Winding, Cocked), (Cocked, Loaded), (Cocked, Firing),
+            (Loaded, Firing), (Firing, Recoiling), (Recoiling, Idle),
+        ];
+        for &from in STATES.iter() {
+            for &to in STATES.iter() {
+                assert_eq!(is_valid_transition(from, to), valid.contains(&(from, to)),
+                           "{:?} -> {:?}", from, to);
+            }
+        }
+    }
+
+    #[test]
+    fn loaded_shot_releases_once() {
+        let mut machine = FiringMachine::new(FiringConfig::default());
+        let events = machine.subscribe();
+        machine.wind().unwrap();
+        run(&mut machine, 3.0);
+        assert_eq!(machine.state(), FiringState::Cocked);
+        machine.load().unwrap();
+        run(&mut machine, 1.0);
+        assert_eq!(machine.state(), FiringState::Loaded);
+        machine.fire().unwrap();
+        assert_eq!(run(&mut machine, 0.3), 1);
+        assert_eq!(run(&mut machine, 2.0), 0);
+        assert_eq!(machine.state(), FiringState::Idle);
+
+        let events = events.try_iter().collect::<Vec<_>>();
+        assert!(events.contains(&FiringEvent::HitStopper));
+        assert_eq!(events.last(), Some(&FiringEvent::StateChanged {
+            from: FiringState::Recoiling, to: FiringState::Idle
+        }));
+    }
+
+    #[test]
+    fn firing_cocked_releases_nothing() {
+        let mut machine = FiringMachine::new(FiringConfig::default());
+        machine.wind().unwrap();
+        run(&mut machine, 3.0);
+        machine.fire().unwrap();
+        assert_eq!(run(&mut machine, 2.0), 0);
+        assert_eq!(machine.state(), FiringState::Idle);
+    }
+
+    #[test]
+    fn status_counts_the_teeth_of_a_winding() {
+        let mut machine = FiringMachine::new(FiringConfig::default());
+        let mut status = FiringStatus::new(machine.subscribe());
+        machine.emit(FiringEvent::PawlClicked);
+        machine.wind().unwrap();
+        machine.emit(FiringEvent::PawlClicked);
+        machine.emit(FiringEvent::PawlClicked);
+        status.update();
+        assert_eq!(status.describe(), "Winding, 2 teeth wound");
+    }
+
+    #[test]
+    fn durations_come_from_the_config() {
+        let text = "[firing]\nwind_duration = 1.5\nfire_duration = -1\nrelease_at = 2";
+        let config = FiringConfig::from_config(&Config::parse(text));
+        assert_eq!(config.wind_duration, 1.5);
+        assert_eq!(config.load_duration, FiringConfig::default().load_duration);
+        assert_eq!(config.fire_duration, 0.0);
+        assert_eq!(config.release_at, 1.0);
+
+        let mut machine = FiringMachine::new(config);
+        machine.wind().unwrap();
+        run(&mut machine, 1.4);
+        assert_eq!(machine.state(), FiringState::Winding);
+        run(&mut machine, 0.2);
+        assert_eq!(machine.state(), FiringState::Cocked);
+    }
+
+    #[test]
+    fn out_of_order_requests_fail() {
+        let mut machine = FiringMachine::new(FiringConfig::default());
+        assert_eq!(machine.fire(), Err(InvalidTransition {
+            from: FiringState::Idle, to: FiringState::Firing
+        }));
+        assert!(machine.load().is_err());
+        machine.wind().unwrap();
+        assert!(machine.wind().is_err());
+        assert_eq!(machine.state(), FiringState::Winding);
+    }
+}
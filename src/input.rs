@@ -0,0 +1,458 @@
+use glium::glutin::{ElementState, Event, MouseButton, MouseScrollDelta, VirtualKeyCode};
+use config::Config;
+use keys::parse_key;
+
+// Touchpads scroll by pixels, this many make up a line
+const SCROLL_PIXELS_PER_LINE: f32 = 20.0;
+
+/**
+ * Everything the player can do, bound to keys and mouse buttons in the
+ * [input] section.
+ */
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Action {
+    ToggleFullscreen,
+    // The catapult
+    Fire,
+    Wind,
+    TurnLeft,
+    TurnRight,
+    MorePower,
+    LessPower,
+    ToggleDriving,
+    DriveForward,
+    DriveBackward,
+    ToggleRange,
+    // The weather
+    TurnWindLeft,
+    TurnWindRight,
+    WeakerWind,
+    StrongerWind,
+    ToggleGusts,
+    // The camera
+    CameraForward,
+    CameraBackward,
+    CameraLeft,
+    CameraRight,
+    CameraUp,
+    CameraDown,
+    LookUp,
+    LookDown,
+    LookLeft,
+    LookRight,
+    RollLeft,
+    RollRight,
+    Sprint,
+    MouseLook,
+    ReleaseMouse,
+    Orbit,
+    OrbitDrag,
+    PanDrag,
+    SelectNext,
+    FrameSelected,
+    Chase,
+    AddKeyframe,
+    RemoveKeyframe,
+    PlayPath,
+    ZoomIn,
+    ZoomOut,
+    ToggleProjection,
+    ToggleConstraints,
+    // Slots count from 0
+    Bookmark(usize),
+    StoreBookmark(usize),
+    // The viewports
+    SplitView,
+    MaximizeView,
+}
+
+/**
+ * Names of the actions in the config and their default bindings.
+ */
+const ACTIONS: &'static [(&'static str, Action, &'static str)] = &[
+    ("toggle_fullscreen", Action::ToggleFullscreen, "Return"),
+    ("fire", Action::Fire, "Space"),
+    ("wind", Action::Wind, "Back"),
+    ("turn_left", Action::TurnLeft, "J"),
+    ("turn_right", Action::TurnRight, "L"),
+    ("more_power", Action::MorePower, "I"),
+    ("less_power", Action::LessPower, "K"),
+    ("toggle_driving", Action::ToggleDriving, "V"),
+    ("drive_forward", Action::DriveForward, "Y"),
+    ("drive_backward", Action::DriveBackward, "H"),
+    ("toggle_range", Action::ToggleRange, "T"),
+    ("turn_wind_left", Action::TurnWindLeft, "Comma"),
+    ("turn_wind_right", Action::TurnWindRight, "Period"),
+    ("weaker_wind", Action::WeakerWind, "Minus"),
+    ("stronger_wind", Action::StrongerWind, "Equals"),
+    ("toggle_gusts", Action::ToggleGusts, "G"),
+    ("camera_forward", Action::CameraForward, "W"),
+    ("camera_backward", Action::CameraBackward, "S"),
+    ("camera_left", Action::CameraLeft, "A"),
+    ("camera_right", Action::CameraRight, "D"),
+    ("camera_up", Action::CameraUp, "Q"),
+    ("camera_down", Action::CameraDown, "E"),
+    ("look_up", Action::LookUp, "Up"),
+    ("look_down", Action::LookDown, "Down"),
+    ("look_left", Action::LookLeft, "Left"),
+    ("look_right", Action::LookRight, "Right"),
+    ("roll_left", Action::RollLeft, "Z"),
+    ("roll_right", Action::RollRight, "X"),
+    ("sprint", Action::Sprint, "LShift, RShift"),
+    ("mouse_look", Action::MouseLook, "M"),
+    ("release_mouse", Action::ReleaseMouse, "Escape"),
+    ("orbit", Action::Orbit, "O"),
+    ("orbit_drag", Action::OrbitDrag, "MouseLeft"),
+    ("pan_drag", Action::PanDrag, "MouseRight"),
+    ("select_next", Action::SelectNext, "Tab"),
+    ("frame_selected", Action::FrameSelected, "F"),
+    ("chase", Action::Chase, "C"),
+    ("add_keyframe", Action::AddKeyframe, "N"),
+    ("remove_keyframe", Action::RemoveKeyframe, "Delete"),
+    ("play_path", Action::PlayPath, "P"),
+    ("zoom_in", Action::ZoomIn, "PageUp"),
+    ("zoom_out", Action::ZoomOut, "PageDown"),
+    ("toggle_projection", Action::ToggleProjection, "Numpad5"),
+    ("toggle_constraints", Action::ToggleConstraints, "B"),
+    ("bookmark_1", Action::Bookmark(0), "Key1"),
+    ("bookmark_2", Action::Bookmark(1), "Key2"),
+    ("bookmark_3", Action::Bookmark(2), "Key3"),
+    ("bookmark_4", Action::Bookmark(3), "Key4"),
+    ("bookmark_5", Action::Bookmark(4), "Key5"),
+    ("bookmark_6", Action::Bookmark(5), "Key6"),
+    ("bookmark_7", Action::Bookmark(6), "Key7"),
+    ("bookmark_8", Action::Bookmark(7), "Key8"),
+    ("bookmark_9", Action::Bookmark(8), "Key9"),
+    ("store_bookmark_1", Action::StoreBookmark(0), "Ctrl+Key1"),
+    ("store_bookmark_2", Action::StoreBookmark(1), "Ctrl+Key2"),
+    ("store_bookmark_3", Action::StoreBookmark(2), "Ctrl+Key3"),
+    ("store_bookmark_4", Action::StoreBookmark(3), "Ctrl+Key4"),
+    ("store_bookmark_5", Action::StoreBookmark(4), "Ctrl+Key5"),
+    ("store_bookmark_6", Action::StoreBookmark(5), "Ctrl+Key6"),
+    ("store_bookmark_7", Action::StoreBookmark(6), "Ctrl+Key7"),
+    ("store_bookmark_8", Action::StoreBookmark(7), "Ctrl+Key8"),
+    ("store_bookmark_9", Action::StoreBookmark(8), "Ctrl+Key9"),
+    ("split_view", Action::SplitView, "F1"),
+    ("maximize_view", Action::MaximizeView, "F2"),
+];
+
+/**
+ * What the subsystems get to see of the input: actions starting and
+ * stopping, and the things that aren't buttons.
+ */
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Input {
+    Pressed(Action),
+    Released(Action),
+    MouseMoved(i32, i32),
+    // Lines scrolled, positive away from the player
+    Scrolled(f32),
+    Resized(u32, u32),
+    Focused(bool),
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Trigger {
+    Key(VirtualKeyCode),
+    Mouse(MouseButton),
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct Modifiers {
+    ctrl: bool,
+    shift: bool,
+    alt: bool,
+}
+
+impl Modifiers {
+    fn count(&self) -> usize {
+        self.ctrl as usize + self.shift as usize + self.alt as usize
+    }
+
+    /**
+     * Whether all of these are held in the other ones.
+     */
+    fn within(&self, held: &Modifiers) -> bool {
+        (!self.ctrl || held.ctrl) && (!self.shift || held.shift) && (!self.alt || held.alt)
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+struct Binding {
+    trigger: Trigger,
+    modifiers: Modifiers,
+    action: Action,
+}
+
+/**
+ * Turns window events into actions, bound in the [input] section like
+ *
+ *     fire = Space
+ *     sprint = LShift, RShift
+ *     orbit_drag = MouseLeft
+ *     store_bookmark_1 = Ctrl+Key1
+ *
+ * Keys are named like in recordings, mouse buttons MouseLeft, MouseRight,
+ * MouseMiddle or Mouse and a number. Ctrl, Shift and Alt in front have to
+ * be held as well. Of the bindings of a key only the ones with the most
+ * held modifiers count, so Ctrl and 1 doesn't also trigger what 1 does,
+ * while Shift and W still moves forward. An action without any bindings is
+ * set to none. Actions left out keep their defaults.
+ */
+pub struct InputMap {
+    bindings: Vec<Binding>,
+    // Held modifier keys
+    modifier_keys: Vec<VirtualKeyCode>,
+    // Actions started by triggers still held, to stop them on release
+    held: Vec<(Trigger, Action)>,
+}
+
+impl InputMap {
+    pub fn from_config(config: &Config) -> InputMap {
+        let mut bindings = Vec::new();
+        for &(name, action, default) in ACTIONS.iter() {
+            let value = config.get_str("input", name).unwrap_or(default);
+            if value.trim() == "none" {
+                continue;
+            }
+            for binding in value.split(',') {
+                match parse_binding(binding.trim(), action) {
+                    Some(binding) => bindings.push(binding),
+                    None => println!("[input] {}: unknown binding {}", name, binding.trim()),
+                }
+            }
+        }
+        InputMap { bindings: bindings, modifier_keys: Vec::new(), held: Vec::new() }
+    }
+
+    /**
+     * Moves the bindings of the action into a map of their own, which keeps
+     * its own held keys.
+     */
+    pub fn take(&mut self, action: Action) -> InputMap {
+        let (taken, kept) = self.bindings.iter().partition(|binding| binding.action == action);
+        self.bindings = kept;
+        InputMap { bindings: taken, modifier_keys: Vec::new(), held: Vec::new() }
+    }
+
+    /**
+     * The input for the events, in order.
+     */
+    pub fn translate(&mut self, events: &[Event]) -> Vec<Input> {
+        let mut inputs = Vec::new();
+        for event in events {
+            match event {
+                &Event::KeyboardInput(state, _, Some(key)) => {
+                    if is_modifier(key) {
+                        self.modifier_keys.retain(|&k| k != key);
+                        if state == ElementState::Pressed {
+                            self.modifier_keys.push(key);
+                        }
+                    }
+                    self.trigger(Trigger::Key(key), state, &mut inputs);
+                },
+                &Event::MouseInput(state, button) => {
+                    self.trigger(Trigger::Mouse(button), state, &mut inputs);
+                },
+                &Event::MouseMoved(x, y) => inputs.push(Input::MouseMoved(x, y)),
+                &Event::MouseWheel(MouseScrollDelta::LineDelta(_, y), _) => {
+                    inputs.push(Input::Scrolled(y));
+                },
+                &Event::MouseWheel(MouseScrollDelta::PixelDelta(_, y), _) => {
+                    inputs.push(Input::Scrolled(y / SCROLL_PIXELS_PER_LINE));
+                },
+                &Event::Resized(width, height) => inputs.push(Input::Resized(width, height)),
+                &Event::Focused(focused) => {
+                    // Releases don't arrive while the window is in the background
+                    if !focused {
+                        for (_, action) in self.held.drain(..) {
+                            inputs.push(Input::Released(action));
+                        }
+                        self.modifier_keys.clear();
+                    }
+                    inputs.push(Input::Focused(focused));
+                },
+                _ => {}
+            }
+        }
+        inputs
+    }
+
+    fn trigger(&mut self, trigger: Trigger, state: ElementState, inputs: &mut Vec<Input>) {
+        if state == ElementState::Released {
+            self.held.retain(|&(t, action)| {
+                if t == trigger {
+                    inputs.push(Input::Released(action));
+                }
+                t != trigger
+            });
+            return;
+        }
+        let modifiers = self.modifiers();
+        let matching = self.bindings.iter()
+            .filter(|binding| binding.trigger == trigger && binding.modifiers.within(&modifiers))
+            .cloned()
+            .collect::<Vec<_>>();
+        let most = matching.iter().map(|binding| binding.modifiers.count()).max();
+        for binding in matching.iter().filter(|binding| Some(binding.modifiers.count()) == most) {
+            // Held keys repeat their presses, but get released only once
+            if !self.held.contains(&(trigger, binding.action)) {
+                self.held.push((trigger, binding.action));
+            }
+            inputs.push(Input::Pressed(binding.action));
+        }
+    }
+
+    fn modifiers(&self) -> Modifiers {
+        let held = |left, right| {
+            self.modifier_keys.iter().any(|&key| key == left || key == right)
+        };
+        Modifiers {
+            ctrl: held(VirtualKeyCode::LControl, VirtualKeyCode::RControl),
+            shift: held(VirtualKeyCode::LShift, VirtualKeyCode::RShift),
+            alt: held(VirtualKeyCode::LAlt, VirtualKeyCode::RAlt),
+        }
+    }
+}
+
+fn is_modifier(key: VirtualKeyCode) -> bool {
+    match key {
+        VirtualKeyCode::LControl | VirtualKeyCode::RControl |
+        VirtualKeyCode::LShift | VirtualKeyCode::RShift |
+        VirtualKeyCode::LAlt | VirtualKeyCode::RAlt => true,
+        _ => false,
+    }
+}
+
+/**
+ * Parses a binding like Ctrl+Shift+S or MouseLeft.
+ */
+fn parse_binding(text: &str, action: Action) -> Option<Binding> {
+    let mut modifiers = Modifiers { ctrl: false, shift: false, alt: false };
+    let mut parts = text.split('+').map(|part| part.trim()).collect::<Vec<_>>();
+    let name = parts.pop()?;
+    for part in parts {
+        match part {
+            "Ctrl" => modifiers.ctrl = true,
+            "Shift" => modifiers.shift = true,
+            "Alt" => modifiers.alt = true,
+            _ => return None,
+        }
+    }
+    let trigger = match name {
+        "MouseLeft" => Trigger::Mouse(MouseButton::Left),
+        "MouseRight" => Trigger::Mouse(MouseButton::Right),
+        "MouseMiddle" => Trigger::Mouse(MouseButton::Middle),
+        _ if name.starts_with("Mouse") => {
+            Trigger::Mouse(MouseButton::Other(name["Mouse".len()..].parse().ok()?))
+        },
+        _ => Trigger::Key(parse_key(name)?),
+    };
+    Some(Binding { trigger: trigger, modifiers: modifiers, action: action })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn press(key: VirtualKeyCode) -> Event {
+        Event::KeyboardInput(ElementState::Pressed, 0, Some(key))
+    }
+
+    fn release(key: VirtualKeyCode) -> Event {
+        Event::KeyboardInput(ElementState::Released, 0, Some(key))
+    }
+
+    fn defaults() -> InputMap {
+        InputMap::from_config(&Config::parse(""))
+    }
+
+    #[test]
+    fn bindings_parse_modifiers_and_mouse_buttons() {
+        let binding = parse_binding("Ctrl+Key1", Action::StoreBookmark(0)).unwrap();
+        assert_eq!(binding.trigger, Trigger::Key(VirtualKeyCode::Key1));
+        assert_eq!(binding.modifiers, Modifiers { ctrl: true, shift: false, alt: false });
+        let binding = parse_binding("Mouse4", Action::Fire).unwrap();
+        assert_eq!(binding.trigger, Trigger::Mouse(MouseButton::Other(4)));
+        let binding = parse_binding("MouseLeft", Action::OrbitDrag).unwrap();
+        assert_eq!(binding.trigger, Trigger::Mouse(MouseButton::Left));
+        assert_eq!(binding.modifiers.count(), 0);
+
+        assert!(parse_binding("NoSuchKey", Action::Fire).is_none());
+        assert!(parse_binding("Hyper+A", Action::Fire).is_none());
+        assert!(parse_binding("MouseX", Action::Fire).is_none());
+    }
+
+    #[test]
+    fn config_lists_and_unbinds_actions() {
+        let config = Config::parse("[input]\nsprint = LShift, RShift\nfire = none");
+        let mut map = InputMap::from_config(&config);
+        assert!(!map.bindings.iter().any(|binding| binding.action == Action::Fire));
+        assert_eq!(map.translate(&[press(VirtualKeyCode::Space)]), vec![]);
+        assert_eq!(map.translate(&[press(VirtualKeyCode::LShift)]),
+                   vec![Input::Pressed(Action::Sprint)]);
+        assert_eq!(map.translate(&[press(VirtualKeyCode::RShift)]),
+                   vec![Input::Pressed(Action::Sprint)]);
+    }
+
+    #[test]
+    fn only_the_most_held_modifiers_count() {
+        let mut map = defaults();
+        let inputs = map.translate(&[press(VirtualKeyCode::LControl), press(VirtualKeyCode::Key1)]);
+        assert_eq!(inputs, vec![Input::Pressed(Action::StoreBookmark(0))]);
+        let inputs = map.translate(&[release(VirtualKeyCode::Key1)]);
+        assert_eq!(inputs, vec![Input::Released(Action::StoreBookmark(0))]);
+
+        let mut map = defaults();
+        let inputs = map.translate(&[press(VirtualKeyCode::LShift), press(VirtualKeyCode::W)]);
+        assert_eq!(inputs, vec![Input::Pressed(Action::Sprint),
+                                Input::Pressed(Action::CameraForward)]);
+    }
+
+    #[test]
+    fn modifiers_are_within_more_held_ones() {
+        let none = Modifiers { ctrl: false, shift: false, alt: false };
+        let ctrl = Modifiers { ctrl: true, .. none };
+        let ctrl_shift = Modifiers { shift: true, .. ctrl };
+        assert!(none.within(&ctrl));
+        assert!(ctrl.within(&ctrl_shift));
+        assert!(!ctrl_shift.within(&ctrl));
+        assert!(!ctrl.within(&Modifiers { alt: true, .. none }));
+    }
+
+    #[test]
+    fn losing_focus_releases_held_actions() {
+        let mut map = defaults();
+        map.translate(&[press(VirtualKeyCode::W), press(VirtualKeyCode::LControl)]);
+        let inputs = map.translate(&[Event::Focused(false)]);
+        assert_eq!(inputs, vec![Input::Released(Action::CameraForward), Input::Focused(false)]);
+        // Ctrl was let go in the background
+        assert_eq!(map.translate(&[press(VirtualKeyCode::Key1)]),
+                   vec![Input::Pressed(Action::Bookmark(0))]);
+    }
+
+    #[test]
+    fn default_bindings_never_share_a_key() {
+        let map = defaults();
+        for (i, binding) in map.bindings.iter().enumerate() {
+            for other in map.bindings[i + 1..].iter() {
+                assert!(binding.trigger != other.trigger || binding.modifiers != other.modifiers,
+                        "{:?} and {:?} share {:?}", binding.action, other.action, binding.trigger);
+            }
+        }
+        let mut map = defaults();
+        assert_eq!(map.translate(&[press(VirtualKeyCode::Y)]),
+                   vec![Input::Pressed(Action::DriveForward)]);
+        assert_eq!(map.translate(&[press(VirtualKeyCode::I)]),
+                   vec![Input::Pressed(Action::MorePower)]);
+    }
+
+    #[test]
+    fn taken_actions_leave_the_map() {
+        let mut map = defaults();
+        let mut window = map.take(Action::ToggleFullscreen);
+        let events = [press(VirtualKeyCode::Return)];
+        assert_eq!(map.translate(&events), vec![]);
+        assert_eq!(window.translate(&events), vec![Input::Pressed(Action::ToggleFullscreen)]);
+    }
+}
@@ -0,0 +1,333 @@
+use quaternion;
+use vecmath::{vec3_dot, vec3_scale};
+use animation::Transform;
+use clock::lerp;
+use matrix::{mul_matrices, rot_matrix_by, translation_matrix};
+use render::DEFAULT_MATRIX;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum JointKind {
+    // Turns around the axis through the pivot, the position is an angle
+    Hinge,
+    // Moves along the axis, the position is a distance
+    Slider,
+    // Doesn't move at all
+    Fixed,
+}
+
+/**
+ * Connects a child node to its parent and says how the child may move
+ * relative to it. Whatever drives the child, be it an animation, physics or
+ * the player, only sets the position of the joint; the joint keeps it within
+ * its limits and turns it into the matrix the child gets drawn with.
+ *
+ * Pivot and axis are in the coordinates of the parent. At position 0.0 the
+ * child is where its model puts it.
+ */
+#[derive(Clone, Debug)]
+pub struct Joint {
+    pub kind: JointKind,
+    pub parent: String,
+    pub child: String,
+    pub pivot: [f32; 3],
+    // Unit length
+    pub axis: [f32; 3],
+    // Lowest and highest position, None for a joint moving freely
+    pub limits: Option<(f32, f32)>,
+    // Seconds it takes to get about two thirds of the way to a driven
+    // position, 0.0 gets there right away
+    pub damping: f32,
+    position: f32,
+    previous_position: f32,
+}
+
+impl Joint {
+    pub fn hinge(parent: &str, child: &str, pivot: [f32; 3], axis: [f32; 3]) -> Joint {
+        Joint::new(JointKind::Hinge, parent, child, pivot, axis)
+    }
+
+    pub fn slider(parent: &str, child: &str, axis: [f32; 3]) -> Joint {
+        Joint::new(JointKind::Slider, parent, child, [0.0, 0.0, 0.0], axis)
+    }
+
+    pub fn fixed(parent: &str, child: &str) -> Joint {
+        Joint::new(JointKind::Fixed, parent, child, [0.0, 0.0, 0.0], [0.0, 0.0, 1.0])
+    }
+
+    fn new(kind: JointKind, parent: &str, child: &str, pivot: [f32; 3], axis: [f32; 3])
+        -> Joint
+    {
+        let length = vec3_dot(axis, axis).sqrt();
+        Joint {
+            kind: kind,
+            parent: parent.to_string(),
+            child: child.to_string(),
+            pivot: pivot,
+            axis: vec3_scale(axis, 1.0 / length),
+            limits: None,
+            damping: 0.0,
+            position: 0.0,
+            previous_position: 0.0,
+        }
+    }
+
+    pub fn with_limits(mut self, low: f32, high: f32) -> Joint {
+        self.limits = Some((low, high));
+        self.position = self.clamp(self.position);
+        self.previous_position = self.position;
+        self
+    }
+
+    pub fn with_damping(mut self, damping: f32) -> Joint {
+        self.damping = damping;
+        self
+    }
+
+    pub fn position(&self) -> f32 {
+        self.position
+    }
+
+    pub fn previous_position(&self) -> f32 {
+        self.previous_position
+    }
+
+    /**
+     * Remembers the current position for interpolating, call at the start
+     * of every simulation step before moving the joint.
+     */
+    pub fn save_previous(&mut self) {
+        self.previous_position = self.position;
+    }
+
+    /**
+     * Moves towards the target position, slowed down by the damping.
+     */
+    pub fn drive(&mut self, target: f32, dt: f32) {
+        let target = self.clamp(target);
+        self.position = if self.damping > 0.0 {
+            self.position + (target - self.position) * (1.0 - (-dt / self.damping).exp())
+        }
+        else {
+            target
+        };
+    }
+
+    /**
+     * The position an animated transform of the child asks for, the parts
+     * of the transform the joint doesn't allow are ignored.
+     */
+    pub fn position_for(&self, transform: &Transform) -> f32 {
+        match self.kind {
+            JointKind::Hinge => {
+                let (w, v) = transform.rotation;
+                2.0 * vec3_dot(v, self.axis).atan2(w)
+            },
+            JointKind::Slider => vec3_dot(transform.translation, self.axis),
+            JointKind::Fixed => 0.0,
+        }
+    }
+
+    /**
+     * Moves by the given amount, for joints turned by something else like
+     * wheels rolling on the ground.
+     */
+    pub fn advance(&mut self, amount: f32) {
+        let target = self.position + amount;
+        self.position = self.clamp(target);
+    }
+
+    fn clamp(&self, position: f32) -> f32 {
+        if self.kind == JointKind::Fixed {
+            return 0.0;
+        }
+        match self.limits {
+            Some((low, high)) => position.max(low).min(high),
+            None => position,
+        }
+    }
+
+    /**
+     * Transforms the coordinates of the child to the ones of the parent for
+     * the given position.
+     */
+    pub fn matrix_at(&self, position: f32) -> [[f32; 4]; 4] {
+        match self.kind {
+            JointKind::Hinge => {
+                let rot = quaternion::axis_angle(self.axis, position);
+                rot_matrix_by(&rot, self.pivot)
+            },
+            JointKind::Slider => translation_matrix(vec3_scale(self.axis, position)),
+            JointKind::Fixed => DEFAULT_MATRIX,
+        }
+    }
+
+    /**
+     * The matrix between the previous and the current position.
+     */
+    pub fn interpolated_matrix(&self, alpha: f32) -> [[f32; 4]; 4] {
+        self.matrix_at(lerp(self.previous_position, self.position, alpha))
+    }
+}
+
+/**
+ * The joints of a model. Nodes get looked up by name once, when resolving
+ * them to the index of the joint moving them; afterwards everything goes by
+ * index. Every joint knows the joint moving its parent, so following them
+ * up to the root never has to search.
+ */
+pub struct Rig {
+    joints: Vec<Joint>,
+    // Index of the joint whose child is the parent of each joint, None for
+    // the ones hanging off the root
+    parents: Vec<Option<usize>>,
+}
+
+impl Rig {
+    /**
+     * Fails if two joints move the same node or the parents form a cycle.
+     */
+    pub fn new(joints: Vec<Joint>) -> Result<Rig, String> {
+        for (i, joint) in joints.iter().enumerate() {
+            if joints[..i].iter().any(|other| other.child == joint.child) {
+                return Err(format!("{} has more than one joint", joint.child));
+            }
+        }
+        let parents = joints.iter()
+            .map(|joint| joints.iter().position(|other| other.child == joint.parent))
+            .collect::<Vec<_>>();
+        for (i, joint) in joints.iter().enumerate() {
+            // Without a cycle the root is at most one step per joint away
+            let mut steps = 0;
+            let mut next = parents[i];
+            while let Some(parent) = next {
+                steps += 1;
+                if steps > joints.len() {
+                    return Err(format!("{} is its own ancestor", joint.child));
+                }
+                next = parents[parent];
+            }
+        }
+        Ok(Rig { joints: joints, parents: parents })
+    }
+
+    /**
+     * The index of the joint moving the node.
+     */
+    pub fn index(&self, node: &str) -> Result<usize, String> {
+        self.joints.iter().position(|joint| joint.child == node)
+            .ok_or_else(|| format!("{} has no joint", node))
+    }
+
+    pub fn joint(&self, index: usize) -> &Joint {
+        &self.joints[index]
+    }
+
+    pub fn joint_mut(&mut self, index: usize) -> &mut Joint {
+        &mut self.joints[index]
+    }
+
+    /**
+     * See Joint::save_previous.
+     */
+    pub fn save_previous(&mut self) {
+        for joint in self.joints.iter_mut() {
+            joint.save_previous();
+        }
+    }
+
+    /**
+     * Whether any joint moved since the last save_previous.
+     */
+    pub fn moved(&self) -> bool {
+        self.joints.iter().any(|joint| joint.position() != joint.previous_position())
+    }
+
+    /**
+     * Transforms the coordinates of the node the joint moves to the ones of
+     * the root, following the joints up. Alpha interpolates between the
+     * previous and the current step, 1.0 gives the current positions.
+     */
+    pub fn matrix(&self, index: usize, alpha: f32) -> [[f32; 4]; 4] {
+        let mut matrix = DEFAULT_MATRIX;
+        let mut next = Some(index);
+        while let Some(i) = next {
+            matrix = mul_matrices(self.joints[i].interpolated_matrix(alpha), matrix);
+            next = self.parents[i];
+        }
+        matrix
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use matrix::transform_point as transform;
+
+    fn assert_near(a: [f32; 3], b: [f32; 3]) {
+        for i in 0..3 {
+            assert!((a[i] - b[i]).abs() < 1e-5, "{:?} != {:?}", a, b);
+        }
+    }
+
+    #[test]
+    fn matrices_follow_the_parents() {
+        let mut rig = Rig::new(vec![
+            Joint::slider("carriage", "arm", [0.0, 1.0, 0.0]),
+            Joint::slider("base", "carriage", [1.0, 0.0, 0.0]),
+            Joint::hinge("base", "wheel", [1.0, 0.0, 0.0], [0.0, 0.0, 1.0]),
+        ]).unwrap();
+        let carriage = rig.index("carriage").unwrap();
+        let arm = rig.index("arm").unwrap();
+        let wheel = rig.index("wheel").unwrap();
+        rig.joint_mut(carriage).drive(2.0, 0.0);
+        rig.joint_mut(arm).drive(3.0, 0.0);
+        rig.joint_mut(wheel).drive(::std::f32::consts::PI, 0.0);
+        assert_near(transform(rig.matrix(carriage, 1.0), [0.0, 0.0, 0.0]), [2.0, 0.0, 0.0]);
+        assert_near(transform(rig.matrix(arm, 1.0), [0.0, 0.0, 0.0]), [2.0, 3.0, 0.0]);
+        assert_near(transform(rig.matrix(arm, 0.0), [0.0, 0.0, 0.0]), [0.0, 0.0, 0.0]);
+        // Half a turn around the pivot
+        assert_near(transform(rig.matrix(wheel, 1.0), [0.0, 0.0, 0.0]), [2.0, 0.0, 0.0]);
+        assert!(rig.index("base").is_err());
+    }
+
+    #[test]
+    fn rigs_tell_when_a_joint_moved() {
+        let mut rig = Rig::new(vec![
+            Joint::hinge("base", "arm", [0.0, 0.0, 0.0], [0.0, 0.0, 1.0]),
+            Joint::slider("base", "carriage", [1.0, 0.0, 0.0]),
+        ]).unwrap();
+        assert!(!rig.moved());
+        let carriage = rig.index("carriage").unwrap();
+        rig.joint_mut(carriage).drive(1.0, 0.0);
+        assert!(rig.moved());
+        rig.save_previous();
+        assert!(!rig.moved());
+        rig.joint_mut(carriage).drive(1.0, 0.0);
+        assert!(!rig.moved());
+    }
+
+    #[test]
+    fn cycles_and_duplicates_are_rejected() {
+        assert!(Rig::new(vec![
+            Joint::fixed("a", "b"),
+            Joint::fixed("b", "c"),
+            Joint::fixed("c", "a"),
+        ]).is_err());
+        assert!(Rig::new(vec![Joint::fixed("a", "a")]).is_err());
+        assert!(Rig::new(vec![Joint::fixed("a", "b"), Joint::fixed("c", "b")]).is_err());
+    }
+
+    #[test]
+    fn limits_hold_the_position() {
+        let mut joint = Joint::hinge("a", "b", [0.0, 0.0, 0.0], [0.0, 0.0, 2.0])
+            .with_limits(-1.0, 0.5);
+        assert_eq!(joint.axis, [0.0, 0.0, 1.0]);
+        joint.drive(3.0, 0.1);
+        assert_eq!(joint.position(), 0.5);
+        joint.advance(-4.0);
+        assert_eq!(joint.position(), -1.0);
+        let mut fixed = Joint::fixed("a", "b");
+        fixed.advance(1.0);
+        assert_eq!(fixed.position(), 0.0);
+    }
+}
@@ -0,0 +1,168 @@
+use glium::glutin::VirtualKeyCode;
+
+/**
+ * Names of the keys, as used in files like recordings and the config.
+ */
+pub fn key_name(key: VirtualKeyCode) -> String {
+    format!("{:?}", key)
+}
+
+/**
+ * The key with the given name, the inverse of key_name.
+ */
+pub fn parse_key(name: &str) -> Option<VirtualKeyCode> {
+    KEYS.iter().find(|&&(key_name, _)| key_name == name).map(|&(_, key)| key)
+}
+
+const KEYS: &'static [(&'static str, VirtualKeyCode)] = &[
+    ("Key1", VirtualKeyCode::Key1),
+    ("Key2", VirtualKeyCode::Key2),
+    ("Key3", VirtualKeyCode::Key3),
+    ("Key4", VirtualKeyCode::Key4),
+    ("Key5", VirtualKeyCode::Key5),
+    ("Key6", VirtualKeyCode::Key6),
+    ("Key7", VirtualKeyCode::Key7),
+    ("Key8", VirtualKeyCode::Key8),
+    ("Key9", VirtualKeyCode::Key9),
+    ("Key0", VirtualKeyCode::Key0),
+    ("A", VirtualKeyCode::A),
+    ("B", VirtualKeyCode::B),
+    ("C", VirtualKeyCode::C),
+    ("D", VirtualKeyCode::D),
+    ("E", VirtualKeyCode::E),
+    ("F", VirtualKeyCode::F),
+    ("G", VirtualKeyCode::G),
+    ("H", VirtualKeyCode::H),
+    ("I", VirtualKeyCode::I),
+    ("J", VirtualKeyCode::J),
+    ("K", VirtualKeyCode::K),
+    ("L", VirtualKeyCode::L),
+    ("M", VirtualKeyCode::M),
+    ("N", VirtualKeyCode::N),
+    ("O", VirtualKeyCode::O),
+    ("P", VirtualKeyCode::P),
+    ("Q", VirtualKeyCode::Q),
+    ("R", VirtualKeyCode::R),
+    ("S", VirtualKeyCode::S),
+    ("T", VirtualKeyCode::T),
+    ("U", VirtualKeyCode::U),
+    ("V", VirtualKeyCode::V),
+    ("W", VirtualKeyCode::W),
+    ("X", VirtualKeyCode::X),
+    ("Y", VirtualKeyCode::Y),
+    ("Z", VirtualKeyCode::Z),
+    ("Escape", VirtualKeyCode::Escape),
+    ("F1", VirtualKeyCode::F1),
+    ("F2", VirtualKeyCode::F2),
+    ("F3", VirtualKeyCode::F3),
+    ("F4", VirtualKeyCode::F4),
+    ("F5", VirtualKeyCode::F5),
+    ("F6", VirtualKeyCode::F6),
+    ("F7", VirtualKeyCode::F7),
+    ("F8", VirtualKeyCode::F8),
+    ("F9", VirtualKeyCode::F9),
+    ("F10", VirtualKeyCode::F10),
+    ("F11", VirtualKeyCode::F11),
+    ("F12", VirtualKeyCode::F12),
+    ("F13", VirtualKeyCode::F13),
+    ("F14", VirtualKeyCode::F14),
+    ("F15", VirtualKeyCode::F15),
+    ("Snapshot", VirtualKeyCode::Snapshot),
+    ("Scroll", VirtualKeyCode::Scroll),
+    ("Pause", VirtualKeyCode::Pause),
+    ("Insert", VirtualKeyCode::Insert),
+    ("Home", VirtualKeyCode::Home),
+    ("Delete", VirtualKeyCode::Delete),
+    ("End", VirtualKeyCode::End),
+    ("PageDown", VirtualKeyCode::PageDown),
+    ("PageUp", VirtualKeyCode::PageUp),
+    ("Left", VirtualKeyCode::Left),
+    ("Up", VirtualKeyCode::Up),
+    ("Right", VirtualKeyCode::Right),
+    ("Down", VirtualKeyCode::Down),
+    ("Back", VirtualKeyCode::Back),
+    ("Return", VirtualKeyCode::Return),
+    ("Space", VirtualKeyCode::Space),
+    ("Compose", VirtualKeyCode::Compose),
+    ("Numlock", VirtualKeyCode::Numlock),
+    ("Numpad0", VirtualKeyCode::Numpad0),
+    ("Numpad1", VirtualKeyCode::Numpad1),
+    ("Numpad2", VirtualKeyCode::Numpad2),
+    ("Numpad3", VirtualKeyCode::Numpad3),
+    ("Numpad4", VirtualKeyCode::Numpad4),
+    ("Numpad5", VirtualKeyCode::Numpad5),
+    ("Numpad6", VirtualKeyCode::Numpad6),
+    ("Numpad7", VirtualKeyCode::Numpad7),
+    ("Numpad8", VirtualKeyCode::Numpad8),
+    ("Numpad9", VirtualKeyCode::Numpad9),
+    ("AbntC1", VirtualKeyCode::AbntC1),
+    ("AbntC2", VirtualKeyCode::AbntC2),
+    ("Add", VirtualKeyCode::Add),
+    ("Apostrophe", VirtualKeyCode::Apostrophe),
+    ("Apps", VirtualKeyCode::Apps),
+    ("At", VirtualKeyCode::At),
+    ("Ax", VirtualKeyCode::Ax),
+    ("Backslash", VirtualKeyCode::Backslash),
+    ("Calculator", VirtualKeyCode::Calculator),
+    ("Capital", VirtualKeyCode::Capital),
+    ("Colon", VirtualKeyCode::Colon),
+    ("Comma", VirtualKeyCode::Comma),
+    ("Convert", VirtualKeyCode::Convert),
+    ("Decimal", VirtualKeyCode::Decimal),
+    ("Divide", VirtualKeyCode::Divide),
+    ("Equals", VirtualKeyCode::Equals),
+    ("Grave", VirtualKeyCode::Grave),
+    ("Kana", VirtualKeyCode::Kana),
+    ("Kanji", VirtualKeyCode::Kanji),
+    ("LAlt", VirtualKeyCode::LAlt),
+    ("LBracket", VirtualKeyCode::LBracket),
+    ("LControl", VirtualKeyCode::LControl),
+    ("LMenu", VirtualKeyCode::LMenu),
+    ("LShift", VirtualKeyCode::LShift),
+    ("LWin", VirtualKeyCode::LWin),
+    ("Mail", VirtualKeyCode::Mail),
+    ("MediaSelect", VirtualKeyCode::MediaSelect),
+    ("MediaStop", VirtualKeyCode::MediaStop),
+    ("Minus", VirtualKeyCode::Minus),
+    ("Multiply", VirtualKeyCode::Multiply),
+    ("Mute", VirtualKeyCode::Mute),
+    ("MyComputer", VirtualKeyCode::MyComputer),
+    ("NavigateForward", VirtualKeyCode::NavigateForward),
+    ("NavigateBackward", VirtualKeyCode::NavigateBackward),
+    ("NextTrack", VirtualKeyCode::NextTrack),
+    ("NoConvert", VirtualKeyCode::NoConvert),
+    ("NumpadComma", VirtualKeyCode::NumpadComma),
+    ("NumpadEnter", VirtualKeyCode::NumpadEnter),
+    ("NumpadEquals", VirtualKeyCode::NumpadEquals),
+    ("OEM102", VirtualKeyCode::OEM102),
+    ("Period", VirtualKeyCode::Period),
+    ("PlayPause", VirtualKeyCode::PlayPause),
+    ("Power", VirtualKeyCode::Power),
+    ("PrevTrack", VirtualKeyCode::PrevTrack),
+    ("RAlt", VirtualKeyCode::RAlt),
+    ("RBracket", VirtualKeyCode::RBracket),
+    ("RControl", VirtualKeyCode::RControl),
+    ("RMenu", VirtualKeyCode::RMenu),
+    ("RShift", VirtualKeyCode::RShift),
+    ("RWin", VirtualKeyCode::RWin),
+    ("Semicolon", VirtualKeyCode::Semicolon),
+    ("Slash", VirtualKeyCode::Slash),
+    ("Sleep", VirtualKeyCode::Sleep),
+    ("Stop", VirtualKeyCode::Stop),
+    ("Subtract", VirtualKeyCode::Subtract),
+    ("Sysrq", VirtualKeyCode::Sysrq),
+    ("Tab", VirtualKeyCode::Tab),
+    ("Underline", VirtualKeyCode::Underline),
+    ("Unlabeled", VirtualKeyCode::Unlabeled),
+    ("VolumeDown", VirtualKeyCode::VolumeDown),
+    ("VolumeUp", VirtualKeyCode::VolumeUp),
+    ("Wake", VirtualKeyCode::Wake),
+    ("WebBack", VirtualKeyCode::WebBack),
+    ("WebFavorites", VirtualKeyCode::WebFavorites),
+    ("WebForward", VirtualKeyCode::WebForward),
+    ("WebHome", VirtualKeyCode::WebHome),
+    ("WebRefresh", VirtualKeyCode::WebRefresh),
+    ("WebSearch", VirtualKeyCode::WebSearch),
+    ("WebStop", VirtualKeyCode::WebStop),
+    ("Yen", VirtualKeyCode::Yen),
+];
@@ -0,0 +1,188 @@
+use joint::Rig;
+
+/**
+ * How the position of the driver turns into the one of the follower.
+ */
+#[derive(Clone, Debug)]
+pub enum Link {
+    // The follower moves ratio times as far as the driver
+    Gear { ratio: f32 },
+    // Like a gear, but while the pawl is engaged the follower only moves in
+    // the given direction (1.0 or -1.0) and the pawl clicks over a tooth
+    // every tooth of travel. Moving the driver the other way is blocked too,
+    // the pawl holds it where the follower is. Once released the follower
+    // runs free.
+    Ratchet { ratio: f32, tooth: f32, direction: f32 },
+    // The follower takes the position the profile gives for the one of the
+    // driver. The profile is pairs of driver and follower positions sorted
+    // by the driver position, linear in between and flat outside.
+    Cam { profile: Vec<(f32, f32)> },
+}
+
+/**
+ * Couples two joints, so one degree of freedom can drive a whole mechanism.
+ * Linkages update in order, the follower of one can drive the next.
+ */
+#[derive(Clone, Debug)]
+pub struct Linkage {
+    // Indices of the joints in the rig
+    pub driver: usize,
+    pub follower: usize,
+    pub link: Link,
+    engaged: bool,
+}
+
+impl Linkage {
+    pub fn gear(driver: usize, follower: usize, ratio: f32) -> Linkage {
+        Linkage::new(driver, follower, Link::Gear { ratio: ratio })
+    }
+
+    pub fn ratchet(driver: usize, follower: usize, ratio: f32, tooth: f32, direction: f32)
+        -> Linkage
+    {
+        Linkage::new(driver, follower, Link::Ratchet {
+            ratio: ratio, tooth: tooth, direction: direction.signum()
+        })
+    }
+
+    pub fn cam(driver: usize, follower: usize, profile: Vec<(f32, f32)>) -> Linkage {
+        Linkage::new(driver, follower, Link::Cam { profile: profile })
+    }
+
+    fn new(driver: usize, follower: usize, link: Link) -> Linkage {
+        Linkage {
+            driver: driver,
+            follower: follower,
+            link: link,
+            engaged: true,
+        }
+    }
+
+    /**
+     * Engages or releases the pawl of a ratchet, other links ignore it.
+     */
+    pub fn set_engaged(&mut self, engaged: bool) {
+        self.engaged = engaged;
+    }
+
+    /**
+     * Moves the follower after the driver. Returns how many teeth the pawl
+     * clicked over.
+     */
+    pub fn update(&self, rig: &mut Rig, dt: f32) -> u32 {
+        let driver = rig.joint(self.driver).position();
+        let follower = rig.joint_mut(self.follower);
+        let current = follower.position();
+        match self.link {
+            Link::Gear { ratio } => {
+                follower.drive(driver * ratio, dt);
+                0
+            },
+            Link::Ratchet { ratio, tooth, direction } => {
+                let target = driver * ratio;
+                if !self.engaged {
+                    follower.drive(target, dt);
+                    return 0;
+                }
+                if (target - current) * direction > 0.0 {
+                    follower.drive(target, dt);
+                }
+                let position = follower.position();
+                let from = (current / tooth).floor();
+                let to = (position / tooth).floor();
+                if (target - position) * direction < 0.0 {
+                    let held = position / ratio;
+                    rig.joint_mut(self.driver).advance(held - driver);
+                }
+                (to - from).abs() as u32
+            },
+            Link::Cam { ref profile } => {
+                follower.drive(cam_position(profile, driver), dt);
+                0
+            },
+        }
+    }
+}
+
+fn cam_position(profile: &[(f32, f32)], driver: f32) -> f32 {
+    let next = match profile.iter().position(|&(at, _)| at > driver) {
+        Some(0) => return profile[0].1,
+        Some(next) => next,
+        None => return profile.last().map_or(0.0, |&(_, position)| position),
+    };
+    let (from_at, from) = profile[next - 1];
+    let (to_at, to) = profile[next];
+    from + (to - from) * (driver - from_at) / (to_at - from_at)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use joint::Joint;
+
+    fn rig() -> Rig {
+        Rig::new(vec![
+            Joint::hinge("frame", "driver", [0.0, 0.0, 0.0], [0.0, 0.0, 1.0]),
+            Joint::hinge("frame", "follower", [0.0, 0.0, 0.0], [0.0, 0.0, 1.0]),
+        ]).unwrap()
+    }
+
+    fn positions(rig: &Rig) -> (f32, f32) {
+        (rig.joint(0).position(), rig.joint(1).position())
+    }
+
+    #[test]
+    fn gears_multiply() {
+        let mut rig = rig();
+        let gear = Linkage::gear(0, 1, -2.0);
+        rig.joint_mut(0).drive(1.5, 0.0);
+        assert_eq!(gear.update(&mut rig, 0.01), 0);
+        assert_eq!(positions(&rig), (1.5, -3.0));
+    }
+
+    #[test]
+    fn ratchet_clicks_one_way_and_holds_the_driver() {
+        let mut rig = rig();
+        let ratchet = Linkage::ratchet(0, 1, 2.0, 1.0, -1.0);
+        rig.joint_mut(0).drive(-1.6, 0.0);
+        assert_eq!(ratchet.update(&mut rig, 0.01), 4);
+        assert_eq!(positions(&rig), (-1.6, -3.2));
+        // Backing off is blocked, the driver stays with the follower
+        rig.joint_mut(0).drive(-0.5, 0.0);
+        assert_eq!(ratchet.update(&mut rig, 0.01), 0);
+        assert_eq!(positions(&rig), (-1.6, -3.2));
+    }
+
+    #[test]
+    fn released_ratchet_runs_free() {
+        let mut rig = rig();
+        let mut ratchet = Linkage::ratchet(0, 1, 2.0, 1.0, -1.0);
+        rig.joint_mut(0).drive(-1.0, 0.0);
+        ratchet.update(&mut rig, 0.01);
+        ratchet.set_engaged(false);
+        rig.joint_mut(0).drive(0.0, 0.0);
+        assert_eq!(ratchet.update(&mut rig, 0.01), 0);
+        assert_eq!(positions(&rig), (0.0, 0.0));
+    }
+
+    #[test]
+    fn cam_follows_the_profile() {
+        let mut rig = rig();
+        let cam = Linkage::cam(0, 1, vec![(0.0, 1.0), (1.0, 3.0)]);
+        rig.joint_mut(0).drive(0.25, 0.0);
+        cam.update(&mut rig, 0.01);
+        assert_eq!(positions(&rig), (0.25, 1.5));
+    }
+
+    #[test]
+    fn cam_is_flat_outside_the_profile() {
+        let profile = [(0.0, 1.0), (1.0, 3.0), (2.0, 2.0)];
+        assert_eq!(cam_position(&profile, -5.0), 1.0);
+        assert_eq!(cam_position(&profile, 0.0), 1.0);
+        assert_eq!(cam_position(&profile, 1.0), 3.0);
+        assert_eq!(cam_position(&profile, 1.5), 2.5);
+        assert_eq!(cam_position(&profile, 2.0), 2.0);
+        assert_eq!(cam_position(&profile, 7.0), 2.0);
+        assert_eq!(cam_position(&[], 1.0), 0.0);
+    }
+}
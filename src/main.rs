@@ -10,10 +10,40 @@ mod camera;
 mod matrix;
 mod state;
 mod drawable;
+mod firing;
+mod clock;
+mod collision;
+mod projectile;
+mod world;
+mod config;
+mod range;
+mod rope;
+mod atmosphere;
+mod rigid_body;
+mod structures;
+mod terrain;
+mod keys;
+mod replay;
+mod animation;
+mod joint;
+mod linkage;
+mod chase;
+mod constraint;
+mod bookmark;
+mod path;
+mod projection;
+mod viewport;
+mod input;
+mod mass;
 
 use glium::glutin;
 use glium::DisplayBuild;
 use glium::backend::glutin_backend;
+use input::{Action, Input};
+use std::env;
+
+// Seconds per simulation step, independent of the frame rate
+const SIMULATION_TIMESTEP: f32 = 1.0 / 120.0;
 
 /*
  *  Remember, remember: matrices rows are columns when defined as an array!
@@ -22,8 +52,39 @@ use glium::backend::glutin_backend;
 /**
  * Renders something of a catapult in yo' face.
  *
- * Use WASD, Arrow keys and Q and E to control the camera.
- * Use Space and Backspace to see some animations. Yay!
+ * The actions below are named like in the [input] section of catapult.cfg,
+ * which binds them to keys and mouse buttons. Their defaults are listed in
+ * input.rs, WASD, the arrow keys and Q and E move the camera for example.
+ *
+ * camera_forward, camera_backward, camera_left, camera_right, camera_up and
+ * camera_down move the camera, the look actions turn it, roll_left and
+ * roll_right roll it and sprint speeds it up.
+ * mouse_look toggles mouse-look, release_mouse leaves it, the scroll wheel
+ * sets the speed.
+ * orbit orbits around the catapult or the part chosen with select_next,
+ * frame_selected frames it.
+ * chase toggles following the thrown projectiles with the camera.
+ * add_keyframe drops a camera path keyframe, remove_keyframe removes one and
+ * play_path flies the path, see camera.path. zoom_in and zoom_out zoom,
+ * toggle_projection toggles the orthographic view.
+ * split_view splits the window into top, front, side and free views,
+ * maximize_view maximizes the one under the cursor.
+ * store_bookmark_1 to 9 store the view in camera.bookmarks, bookmark_1 to 9
+ * fly back to it.
+ * toggle_constraints keeps the camera out of the ground and everything solid.
+ * wind winds the catapult up and again loads it, fire fires.
+ * turn_left and turn_right turn the catapult, more_power and less_power set
+ * how far it gets wound back.
+ * toggle_driving toggles drive mode, then drive_forward and drive_backward
+ * drive and turn_left and turn_right steer.
+ * toggle_range toggles the target range, see catapult.cfg for its layout. The
+ * arm moves along the clips in catapult.anim.
+ * turn_wind_left and turn_wind_right turn the wind, weaker_wind and
+ * stronger_wind change its strength and toggle_gusts toggles gusts.
+ * toggle_fullscreen switches between the window and fullscreen.
+ *
+ * Start with `--record <file>` to write all input to a file, and with
+ * `--replay <file>` to play it back step by step, reproducing the same run.
  */
 fn main() {
     let display: glutin_backend::GlutinFacade = glutin::WindowBuilder::new()
@@ -31,22 +92,31 @@ fn main() {
         .build_glium().unwrap();
     let mut settings: state::Settings = render::init(&display);
     let mut fullscreen = false;
+    let mut clock = clock::Clock::new(SIMULATION_TIMESTEP);
+    let (mut recorder, mut replay) = parse_args();
+    // Events wait here until the next simulation step consumes them
+    let mut pending_events = Vec::new();
+    let mut window_title = String::new();
+    let mut cursor_captured = false;
+    let mut input_map = input::InputMap::from_config(&settings.config);
+    // The window handles fullscreen itself, the steps only see recorded or
+    // replayed events
+    let mut window_input = input_map.take(Action::ToggleFullscreen);
+    if let Some((width, height)) = display.get_window().and_then(|w| w.get_inner_size_pixels()) {
+        settings.camera.set_window_size(width, height);
+        settings.viewports.set_window_size(width, height);
+    }
 
     loop {
-        render::render(&display, &settings);
         let keyboard_events = display.poll_events().collect::<Vec<_>>();
-        for renderable in settings.objects.iter_mut() {
-            renderable.update(&keyboard_events);
+        let closed = keyboard_events.iter()
+            .any(|ev| match *ev { glutin::Event::Closed => true, _ => false });
+        if closed {
+            return;
         }
-        settings.camera.update();
-        for ev in keyboard_events {
-            settings.camera.process_input(&ev);
-            match ev {
-                glutin::Event::Closed => return,
-                glutin::Event::KeyboardInput(
-                    glutin::ElementState::Pressed, _,
-                    Some(glutin::VirtualKeyCode::Return)
-                ) => {
+        for input in window_input.translate(&keyboard_events) {
+            match input {
+                Input::Pressed(Action::ToggleFullscreen) => {
                     if fullscreen {
                         glium::glutin::WindowBuilder::new()
                             .rebuild_glium(&display)
@@ -64,6 +134,123 @@ fn main() {
                 _ => ()
             }
         }
+        // A replay brings its own input, until it runs out
+        if replay.is_none() {
+            pending_events.extend(keyboard_events);
+        }
+
+        clock.advance();
+        while clock.step() {
+            let dt = clock.timestep();
+            let tick = clock.tick();
+            let finished = match replay {
+                Some(ref mut replay) => {
+                    pending_events = replay.events_for(tick);
+                    replay.is_finished()
+                },
+                None => false,
+            };
+            if finished {
+                println!("Replay finished at step {}", tick);
+                replay = None;
+            }
+            if let Some(ref mut recorder) = recorder {
+                recorder.record(tick, &pending_events);
+            }
+
+            let targets = settings.objects.iter()
+                .flat_map(|object| object.parts(render::DEFAULT_MATRIX))
+                .collect::<Vec<_>>();
+            settings.camera.set_targets(targets);
+            let inputs = input_map.translate(&pending_events);
+            for input in inputs.iter() {
+                settings.camera.process_input(input);
+                settings.viewports.process_input(input);
+                settings.world.atmosphere.process_input(input);
+            }
+            for renderable in settings.objects.iter_mut() {
+                renderable.update(&inputs, dt);
+            }
+            let colliders = settings.objects.iter()
+                .flat_map(|object| object.colliders(render::DEFAULT_MATRIX))
+                .collect::<Vec<_>>();
+            settings.world.update(dt, &colliders);
+            let newest = settings.world.projectiles.last()
+                .map(|projectile| (projectile.id, projectile.position, projectile.velocity));
+            settings.camera.track_projectile(newest);
+            settings.camera.update(dt);
+            let obstacles = settings.world.obstacles(&colliders);
+            settings.camera.constrain(&settings.world.terrain, &obstacles);
+            settings.viewports.update(settings.camera.selected_target());
+            pending_events.clear();
+        }
+
+        let mut status = vec![settings.world.atmosphere.describe()];
+        if let Some(ref mut firing) = settings.firing_status {
+            firing.update();
+            status.push(firing.describe());
+        }
+        status.extend(settings.objects.iter().filter_map(|object| object.status()));
+        status.extend(settings.camera.status());
+        let title = format!("Catapult - {}", status.join(" | "));
+        if let Some(window) = display.get_window() {
+            if title != window_title {
+                window.set_title(&title);
+                window_title = title;
+            }
+            let mouse_look = settings.camera.mouse_look();
+            if mouse_look != cursor_captured {
+                // Grabbing keeps the cursor in the window where the system
+                // supports it, elsewhere it only gets hidden
+                let _ = if mouse_look {
+                    window.set_cursor_state(glutin::CursorState::Grab)
+                        .or_else(|_| window.set_cursor_state(glutin::CursorState::Hide))
+                }
+                else {
+                    window.set_cursor_state(glutin::CursorState::Normal)
+                };
+                cursor_captured = mouse_look;
+            }
+            // A replay already contains the moves of putting it back
+            if let Some((x, y)) = settings.camera.take_cursor_warp() {
+                if replay.is_none() && window.set_cursor_position(x, y).is_err() {
+                    settings.camera.cursor_warp_failed();
+                }
+            }
+        }
+
+        settings.alpha = clock.alpha();
+        render::render(&display, &settings);
     }
 }
 
+/**
+ * Reads --record and --replay from the command line.
+ */
+fn parse_args() -> (Option<replay::Recorder>, Option<replay::Replay>) {
+    let args = env::args().collect::<Vec<_>>();
+    let mut recorder = None;
+    let mut replay = None;
+    let mut i = 1;
+    while i < args.len() {
+        match (args[i].as_str(), args.get(i + 1)) {
+            ("--record", Some(path)) => {
+                match replay::Recorder::create(path, SIMULATION_TIMESTEP) {
+                    Ok(r) => recorder = Some(r),
+                    Err(e) => println!("{}", e),
+                }
+                i += 1;
+            },
+            ("--replay", Some(path)) => {
+                match replay::Replay::load(path, SIMULATION_TIMESTEP) {
+                    Ok(r) => replay = Some(r),
+                    Err(e) => println!("{}", e),
+                }
+                i += 1;
+            },
+            (arg, _) => println!("Unknown argument {}", arg),
+        }
+        i += 1;
+    }
+    (recorder, replay)
+}
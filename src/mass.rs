@@ -0,0 +1,151 @@
+use vecmath::{Vector3, vec3_add, vec3_dot, vec3_scale, vec3_sub};
+use collision::Obb;
+
+// Densities in kg/m³
+pub const WOOD_DENSITY: f32 = 600.0;
+// The thrown clay balls are as heavy
+pub const STONE_DENSITY: f32 = 1500.0;
+// The woods the catapult is built from
+pub const OAK_DENSITY: f32 = 750.0;
+pub const ASH_DENSITY: f32 = 670.0;
+pub const PINE_DENSITY: f32 = 510.0;
+
+/**
+ * Mass in kg, center of mass and the inertia tensor about the center of
+ * mass in kg·m². Center and tensor are in the coordinates the parts were
+ * given in.
+ */
+#[derive(Copy, Clone, Debug)]
+pub struct MassProperties {
+    pub mass: f32,
+    pub center: Vector3<f32>,
+    pub inertia: [[f32; 3]; 3],
+}
+
+pub const NO_MASS: MassProperties = MassProperties {
+    mass: 0.0,
+    center: [0.0, 0.0, 0.0],
+    inertia: [[0.0; 3]; 3],
+};
+
+impl MassProperties {
+    /**
+     * A solid box of uniform density.
+     */
+    pub fn of_box(obb: &Obb, density: f32) -> MassProperties {
+        let h = obb.half_extents;
+        let mass = density * 8.0 * h[0] * h[1] * h[2];
+        // Moments around the axes of the box, (2h)² / 12 = h² / 3
+        let moments = [
+            mass * (h[1] * h[1] + h[2] * h[2]) / 3.0,
+            mass * (h[0] * h[0] + h[2] * h[2]) / 3.0,
+            mass * (h[0] * h[0] + h[1] * h[1]) / 3.0,
+        ];
+        // Rotated into the given coordinates: the sum of moment · a aᵀ
+        let mut inertia = [[0.0; 3]; 3];
+        for (moment, a) in moments.iter().zip(obb.axes.iter()) {
+            for (row, a_i) in inertia.iter_mut().zip(a.iter()) {
+                for (element, a_j) in row.iter_mut().zip(a.iter()) {
+                    *element += moment * a_i * a_j;
+                }
+            }
+        }
+        MassProperties { mass: mass, center: obb.center, inertia: inertia }
+    }
+
+    /**
+     * The properties of both parts together.
+     */
+    pub fn combine(&self, other: &MassProperties) -> MassProperties {
+        let mass = self.mass + other.mass;
+        if mass <= 0.0 {
+            return NO_MASS;
+        }
+        let center = vec3_scale(
+            vec3_add(vec3_scale(self.center, self.mass), vec3_scale(other.center, other.mass)),
+            1.0 / mass
+        );
+        let a = self.inertia_about(center);
+        let b = other.inertia_about(center);
+        let mut inertia = a;
+        for (row, b_row) in inertia.iter_mut().zip(b.iter()) {
+            for (element, b) in row.iter_mut().zip(b_row.iter()) {
+                *element += b;
+            }
+        }
+        MassProperties { mass: mass, center: center, inertia: inertia }
+    }
+
+    /**
+     * The inertia tensor about another point, by the parallel axis theorem.
+     */
+    pub fn inertia_about(&self, point: Vector3<f32>) -> [[f32; 3]; 3] {
+        let d = vec3_sub(self.center, point);
+        let distance_squared = vec3_dot(d, d);
+        let mut inertia = self.inertia;
+        for (i, row) in inertia.iter_mut().enumerate() {
+            for (j, element) in row.iter_mut().enumerate() {
+                let identity = if i == j { 1.0 } else { 0.0 };
+                *element += self.mass * (distance_squared * identity - d[i] * d[j]);
+            }
+        }
+        inertia
+    }
+
+    /**
+     * Moment of inertia for turning around the axis (of unit length) through
+     * the point, like a hinge.
+     */
+    pub fn moment_about(&self, point: Vector3<f32>, axis: Vector3<f32>) -> f32 {
+        let inertia = self.inertia_about(point);
+        inertia.iter().zip(axis.iter()).map(|(row, a_i)| {
+            row.iter().zip(axis.iter()).map(|(element, a_j)| a_i * element * a_j).sum::<f32>()
+        }).sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const X: Vector3<f32> = [1.0, 0.0, 0.0];
+    const Y: Vector3<f32> = [0.0, 1.0, 0.0];
+    const Z: Vector3<f32> = [0.0, 0.0, 1.0];
+
+    fn cube(center: Vector3<f32>) -> MassProperties {
+        let obb = Obb { center: center, axes: [X, Y, Z], half_extents: [0.5, 0.5, 0.5] };
+        MassProperties::of_box(&obb, 6.0)
+    }
+
+    #[test]
+    fn box_moments_follow_its_axes() {
+        // 2 x 4 x 6 m, lying with its long side along x
+        let obb = Obb { center: [1.0, 2.0, 3.0], axes: [Z, Y, X], half_extents: [1.0, 2.0, 3.0] };
+        let properties = MassProperties::of_box(&obb, 0.5);
+        assert_eq!(properties.mass, 24.0);
+        assert_eq!(properties.center, [1.0, 2.0, 3.0]);
+        assert_eq!(properties.inertia, [
+            [24.0 * (4.0 + 16.0) / 12.0, 0.0, 0.0],
+            [0.0, 24.0 * (36.0 + 4.0) / 12.0, 0.0],
+            [0.0, 0.0, 24.0 * (36.0 + 16.0) / 12.0],
+        ]);
+    }
+
+    #[test]
+    fn combining_shifts_by_the_parallel_axis() {
+        let both = cube([-1.0, 0.0, 0.0]).combine(&cube([1.0, 0.0, 0.0]));
+        assert_eq!(both.mass, 12.0);
+        assert_eq!(both.center, [0.0, 0.0, 0.0]);
+        // Each cube has 1 kg·m² about its center and lies 1 m off the axes
+        assert_eq!(both.inertia, [[2.0, 0.0, 0.0], [0.0, 14.0, 0.0], [0.0, 0.0, 14.0]]);
+        assert_eq!(both.moment_about([0.0, 0.0, 0.0], Y), 14.0);
+        assert_eq!(both.moment_about([1.0, 0.0, 0.0], Z), 1.0 + 6.0 * 4.0 + 1.0);
+    }
+
+    #[test]
+    fn nothing_combines_to_nothing() {
+        let none = NO_MASS.combine(&NO_MASS);
+        assert_eq!(none.mass, 0.0);
+        assert_eq!(NO_MASS.combine(&cube([2.0, 0.0, 0.0])).center, [2.0, 0.0, 0.0]);
+    }
+}
@@ -82,3 +82,35 @@ fn col_of_matrix(mat: [[f32; 4]; 4], col_number: usize) -> [f32; 4] {
 fn vec4_dot(a: [f32; 4], b: [f32; 4]) -> f32 {
     a[0] * b[0] + a[1] * b[1] + a[2] * b[2] + a[3] * b[3]
 }
+
+/**
+ * Transforms a point by the (column-major) matrix, including the translation.
+ */
+pub fn transform_point(mat: [[f32; 4]; 4], p: [f32; 3]) -> [f32; 3] {
+    [
+        mat[0][0] * p[0] + mat[1][0] * p[1] + mat[2][0] * p[2] + mat[3][0],
+        mat[0][1] * p[0] + mat[1][1] * p[1] + mat[2][1] * p[2] + mat[3][1],
+        mat[0][2] * p[0] + mat[1][2] * p[1] + mat[2][2] * p[2] + mat[3][2],
+    ]
+}
+
+
+/**
+ * Transforms a direction by the (column-major) matrix, ignoring the translation.
+ */
+pub fn transform_vector(mat: [[f32; 4]; 4], v: [f32; 3]) -> [f32; 3] {
+    [
+        mat[0][0] * v[0] + mat[1][0] * v[1] + mat[2][0] * v[2],
+        mat[0][1] * v[0] + mat[1][1] * v[1] + mat[2][1] * v[2],
+        mat[0][2] * v[0] + mat[1][2] * v[1] + mat[2][2] * v[2],
+    ]
+}
+
+pub fn translation_matrix(t: [f32; 3]) -> [[f32; 4]; 4] {
+    [
+        [1.0, 0.0, 0.0, 0.0],
+        [0.0, 1.0, 0.0, 0.0],
+        [0.0, 0.0, 1.0, 0.0],
+        [t[0], t[1], t[2], 1.0],
+    ]
+}
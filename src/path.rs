@@ -0,0 +1,323 @@
+use std::fs::File;
+use std::io::{Read, Write};
+use vecmath::Vector3;
+use animation::{Easing, catmull_rom};
+use clock::lerp;
+
+pub const PATH_FILE: &'static str = "camera.path";
+// Seconds from the last keyframe to a newly dropped one
+const SEGMENT_TIME: f32 = 2.0;
+
+/**
+ * How the camera gets from keyframe to keyframe.
+ */
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Spline {
+    // Passes through every keyframe
+    CatmullRom,
+    // One Bezier curve with the keyframes as control points, smoother but
+    // only passing through the first and the last one
+    Bezier,
+}
+
+/**
+ * Where the camera is and looks at a point in time, with the vertical field
+ * of view in radians. The easing applies to the way to the next keyframe.
+ */
+#[derive(Copy, Clone, Debug)]
+pub struct PathKeyframe {
+    pub time: f32,
+    pub position: Vector3<f32>,
+    pub look_at: Vector3<f32>,
+    pub fov: f32,
+    pub easing: Easing,
+}
+
+/**
+ * A camera flight for demo videos, played back with play_path. Keyframes get
+ * dropped at the current camera and saved to camera.path right away, where
+ * their times, easings and the spline can be edited.
+ */
+pub struct CameraPath {
+    pub spline: Spline,
+    // Sorted by time
+    pub keyframes: Vec<PathKeyframe>,
+    // Seconds into the path while playing
+    playing: Option<f32>,
+}
+
+impl CameraPath {
+    pub fn new() -> CameraPath {
+        CameraPath { spline: Spline::CatmullRom, keyframes: Vec::new(), playing: None }
+    }
+
+    /**
+     * The path saved in the file, an empty one if there is none.
+     */
+    pub fn load(path: &str) -> CameraPath {
+        let mut text = String::new();
+        if File::open(path).and_then(|mut file| file.read_to_string(&mut text)).is_err() {
+            return CameraPath::new();
+        }
+        match CameraPath::parse(&text) {
+            Ok(camera_path) => camera_path,
+            Err(e) => {
+                println!("{}:{}", path, e);
+                CameraPath::new()
+            },
+        }
+    }
+
+    /**
+     * Parses a path from text like
+     *
+     *     spline catmull_rom
+     *     key 0.0  -5 1 1  0 1 0  60  ease_in
+     *     key 4.0  10 3 -6  0 1 0  45
+     *
+     * The spline is catmull_rom or bezier. Keys give the time in seconds,
+     * the position, the point looked at and the field of view in degrees,
+     * optionally followed by the easing towards the next key (linear,
+     * ease_in, ease_out or ease_in_out). Everything after a # is a comment.
+     */
+    pub fn parse(text: &str) -> Result<CameraPath, String> {
+        let mut camera_path = CameraPath::new();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("");
+            let words = line.split_whitespace().collect::<Vec<_>>();
+            if words.is_empty() {
+                continue;
+            }
+            let error = |message: &str| Err(format!("{}: {}", number + 1, message));
+            match words[0] {
+                "spline" if words.len() == 2 => {
+                    camera_path.spline = match words[1] {
+                        "catmull_rom" => Spline::CatmullRom,
+                        "bezier" => Spline::Bezier,
+                        _ => return error("unknown spline"),
+                    };
+                },
+                "key" if words.len() == 9 || words.len() == 10 => {
+                    let mut v = [0.0f32; 8];
+                    for i in 0..8 {
+                        v[i] = match words[1 + i].parse() {
+                            Ok(value) => value,
+                            Err(_) => return error("invalid number"),
+                        };
+                    }
+                    if camera_path.keyframes.last().map_or(false, |last| last.time >= v[0]) {
+                        return error("keys have to be in order");
+                    }
+                    let easing = match words.get(9) {
+                        None => Easing::Linear,
+                        Some(name) => match Easing::parse(name) {
+                            Some(easing) => easing,
+                            None => return error("unknown easing"),
+                        },
+                    };
+                    camera_path.keyframes.push(PathKeyframe {
+                        time: v[0],
+                        position: [v[1], v[2], v[3]],
+                        look_at: [v[4], v[5], v[6]],
+                        fov: v[7].to_radians(),
+                        easing: easing,
+                    });
+                },
+                _ => return error("expected spline or key"),
+            }
+        }
+        Ok(camera_path)
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        let mut text = String::from("# time  position  look at  field of view  easing\n");
+        let spline = match self.spline {
+            Spline::CatmullRom => "catmull_rom",
+            Spline::Bezier => "bezier",
+        };
+        text.push_str(&format!("spline {}\n", spline));
+        for key in self.keyframes.iter() {
+            text.push_str(&format!(
+                "key {}  {} {} {}  {} {} {}  {}  {}\n", key.time,
+                key.position[0], key.position[1], key.position[2],
+                key.look_at[0], key.look_at[1], key.look_at[2],
+                key.fov.to_degrees(), key.easing.name()
+            ));
+        }
+        File::create(path).and_then(|mut file| file.write_all(text.as_bytes()))
+            .map_err(|e| format!("Can't write {}: {}", path, e))
+    }
+
+    /**
+     * Adds a keyframe a little after the last one.
+     */
+    pub fn add_keyframe(&mut self, position: Vector3<f32>, look_at: Vector3<f32>, fov: f32) {
+        let time = self.keyframes.last().map_or(0.0, |last| last.time + SEGMENT_TIME);
+        self.keyframes.push(PathKeyframe {
+            time: time,
+            position: position,
+            look_at: look_at,
+            fov: fov,
+            easing: Easing::Linear,
+        });
+    }
+
+    pub fn remove_last(&mut self) {
+        self.keyframes.pop();
+    }
+
+    /**
+     * Starts playing from the beginning or stops. A path needs two keyframes
+     * to go anywhere.
+     */
+    pub fn toggle_playing(&mut self) -> Result<(), String> {
+        if self.playing.is_some() {
+            self.playing = None;
+        }
+        else if self.keyframes.len() >= 2 {
+            self.playing = Some(self.keyframes[0].time);
+        }
+        else {
+            return Err("The camera path needs two keyframes, add_keyframe adds one".to_string());
+        }
+        Ok(())
+    }
+
+    /**
+     * Advances the playback by dt seconds. Returns the position, the point
+     * to look at and the field of view, None when not playing. Stops after
+     * the last keyframe.
+     */
+    pub fn update(&mut self, dt: f32) -> Option<(Vector3<f32>, Vector3<f32>, f32)> {
+        let time = self.playing? + dt;
+        let end = self.keyframes.last().map_or(0.0, |last| last.time);
+        self.playing = if time < end { Some(time) } else { None };
+        self.sample(time.min(end))
+    }
+
+    /**
+     * The camera at the given time, None without keyframes.
+     */
+    pub fn sample(&self, time: f32) -> Option<(Vector3<f32>, Vector3<f32>, f32)> {
+        let keys = &self.keyframes;
+        // Position and field of view, and the point looked at
+        let pack = |key: &PathKeyframe| (
+            [key.position[0], key.position[1], key.position[2], key.fov],
+            [key.look_at[0], key.look_at[1], key.look_at[2], 0.0]
+        );
+        let next = match keys.iter().position(|k| k.time > time) {
+            Some(0) => return keys.first().map(|key| (key.position, key.look_at, key.fov)),
+            Some(next) => next,
+            None => return keys.last().map(|key| (key.position, key.look_at, key.fov)),
+        };
+        let from = &keys[next - 1];
+        let to = &keys[next];
+        let t = from.easing.apply((time - from.time) / (to.time - from.time));
+
+        let (camera, look_at) = match self.spline {
+            Spline::CatmullRom => {
+                let before = pack(if next >= 2 { &keys[next - 2] } else { from });
+                let after = pack(if next + 1 < keys.len() { &keys[next + 1] } else { to });
+                let (from, to) = (pack(from), pack(to));
+                (catmull_rom(before.0, from.0, to.0, after.0, t),
+                 catmull_rom(before.1, from.1, to.1, after.1, t))
+            },
+            Spline::Bezier => {
+                // Each segment takes an equal share of the curve
+                let u = (next as f32 - 1.0 + t) / (keys.len() as f32 - 1.0);
+                let points = keys.iter().map(|key| pack(key)).collect::<Vec<_>>();
+                (bezier(points.iter().map(|p| p.0).collect(), u),
+                 bezier(points.iter().map(|p| p.1).collect(), u))
+            },
+        };
+        Some(([camera[0], camera[1], camera[2]], [look_at[0], look_at[1], look_at[2]], camera[3]))
+    }
+}
+
+/**
+ * Point on the Bezier curve with the control points at t, by de Casteljau.
+ */
+fn bezier(mut points: Vec<[f32; 4]>, t: f32) -> [f32; 4] {
+    while points.len() > 1 {
+        for i in 0..points.len() - 1 {
+            let (a, b) = (points[i], points[i + 1]);
+            points[i] = [lerp(a[0], b[0], t), lerp(a[1], b[1], t),
+                         lerp(a[2], b[2], t), lerp(a[3], b[3], t)];
+        }
+        points.pop();
+    }
+    points[0]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    const TEXT: &'static str = "
+        spline catmull_rom
+        key 0.0  -5 1 1  0 1 0  60  ease_in  # start
+        key 2.0  0 4 -2  1 1 0  45
+        key 3.5  6 2 -6  2 0 1  50  ease_in_out
+        key 6.0  10 3 -3  0 1 0  30
+    ";
+
+    fn close(a: Vector3<f32>, b: Vector3<f32>) -> bool {
+        (0..3).all(|i| (a[i] - b[i]).abs() < 1e-3)
+    }
+
+    #[test]
+    fn path_survives_saving() {
+        let mut original = CameraPath::parse(TEXT).unwrap();
+        original.spline = Spline::Bezier;
+        let file = env::temp_dir().join(format!("catapult-path-{}.path", std::process::id()));
+        let file = file.to_str().unwrap();
+        original.save(file).unwrap();
+        let loaded = CameraPath::load(file);
+        let _ = ::std::fs::remove_file(file);
+
+        assert_eq!(loaded.spline, Spline::Bezier);
+        assert_eq!(loaded.keyframes.len(), 4);
+        for (a, b) in original.keyframes.iter().zip(loaded.keyframes.iter()) {
+            assert_eq!(a.time, b.time);
+            assert_eq!(a.position, b.position);
+            assert_eq!(a.look_at, b.look_at);
+            assert!((a.fov - b.fov).abs() < 1e-6);
+            assert_eq!(a.easing, b.easing);
+        }
+    }
+
+    #[test]
+    fn broken_paths_are_rejected() {
+        assert!(CameraPath::parse("spline straight").is_err());
+        assert!(CameraPath::parse("key 0.0  0 0 0  1 0 0").is_err());
+        assert!(CameraPath::parse("key 0.0  0 0 0  1 0 0  60  bouncy").is_err());
+        assert!(CameraPath::parse("key 1.0  0 0 0  1 0 0  60\nkey 1.0  1 0 0  1 0 0  60").is_err());
+    }
+
+    #[test]
+    fn catmull_rom_passes_through_every_keyframe() {
+        let path = CameraPath::parse(TEXT).unwrap();
+        for key in path.keyframes.iter() {
+            for &time in [key.time - 1e-4, key.time, key.time + 1e-4].iter() {
+                let (position, look_at, fov) = path.sample(time).unwrap();
+                assert!(close(position, key.position), "{:?} at {}", position, time);
+                assert!(close(look_at, key.look_at), "{:?} at {}", look_at, time);
+                assert!((fov - key.fov).abs() < 1e-3);
+            }
+        }
+    }
+
+    #[test]
+    fn bezier_hits_both_ends() {
+        let mut path = CameraPath::parse(TEXT).unwrap();
+        path.spline = Spline::Bezier;
+        let first = path.keyframes[0];
+        let last = path.keyframes[3];
+        assert!(close(path.sample(first.time + 1e-4).unwrap().0, first.position));
+        assert!(close(path.sample(last.time - 1e-4).unwrap().0, last.position));
+        // The keyframes between only pull the curve
+        let middle = path.keyframes[1];
+        assert!(!close(path.sample(middle.time).unwrap().0, middle.position));
+    }
+}
@@ -0,0 +1,246 @@
+use vecmath::{Vector3, vec3_add, vec3_sub, vec3_scale, vec3_dot, vec3_cross, vec3_len};
+use quaternion;
+use quaternion::Quaternion;
+use collision::{Collider, Contact, Material, Sphere};
+use atmosphere::Atmosphere;
+use std::f32::consts::PI;
+
+pub const GRAVITY: Vector3<f32> = [0.0, -9.81, 0.0];
+
+// Bounces slower than this don't bounce anymore, so resting contacts settle
+const BOUNCE_THRESHOLD: f32 = 0.5;
+// Deceleration of a rolling stone, relative to gravity
+const ROLLING_RESISTANCE: f32 = 0.05;
+// Below this speed a touching stone counts as still
+const SLEEP_SPEED: f32 = 0.05;
+// How long a stone has to be still before it falls asleep
+const SLEEP_TIME: f32 = 0.5;
+// Drag coefficient of a sphere
+const SPHERE_DRAG: f32 = 0.47;
+
+/**
+ * Something thrown by the catapult, simulated as a sphere.
+ */
+pub struct Projectile {
+    // Tells the projectiles apart in events, set by the world
+    pub id: u64,
+    pub position: Vector3<f32>,
+    pub velocity: Vector3<f32>,
+    pub orientation: Quaternion<f32>,
+    pub angular_velocity: Vector3<f32>,
+    pub radius: f32,
+    pub mass: f32,
+    pub material: Material,
+    // State of the last step, used to interpolate the rendering
+    pub previous_position: Vector3<f32>,
+    pub previous_orientation: Quaternion<f32>,
+    // Whether it has touched anything since it was thrown
+    pub landed: bool,
+    still_time: f32,
+    asleep: bool,
+}
+
+impl Projectile {
+    /**
+     * A sphere of the given radius, density is in kg/m³.
+     */
+    pub fn new(
+        position: Vector3<f32>,
+        velocity: Vector3<f32>,
+        radius: f32,
+        density: f32,
+        material: Material
+    )
+        -> Projectile
+    {
+        Projectile {
+            id: 0,
+            mass: density * 4.0 / 3.0 * PI * radius * radius * radius,
+            position: position,
+            velocity: velocity,
+            orientation: (1.0, [0.0, 0.0, 0.0]),
+            angular_velocity: [0.0, 0.0, 0.0],
+            radius: radius,
+            material: material,
+            previous_position: position,
+            previous_orientation: (1.0, [0.0, 0.0, 0.0]),
+            landed: false,
+            still_time: 0.0,
+            asleep: false,
+        }
+    }
+
+    pub fn sphere(&self) -> Sphere {
+        Sphere { center: self.position, radius: self.radius }
+    }
+
+    /**
+     * Moves the projectile by dt seconds through the air and resolves its
+     * contacts with the colliders. Returns the contacts that were resolved.
+     */
+    pub fn update(&mut self, dt: f32, colliders: &[Collider], air: &Atmosphere) -> Vec<Contact> {
+        self.previous_position = self.position;
+        self.previous_orientation = self.orientation;
+        if self.asleep {
+            return Vec::new();
+        }
+
+        let acceleration = vec3_add(GRAVITY, vec3_scale(self.drag(air), 1.0 / self.mass));
+        self.velocity = vec3_add(self.velocity, vec3_scale(acceleration, dt));
+        self.position = vec3_add(self.position, vec3_scale(self.velocity, dt));
+
+        let mut contacts = Vec::new();
+        for collider in colliders.iter() {
+            if let Some(contact) = collider.collide_sphere(&self.sphere()) {
+                let material = self.material.combine(&collider.material);
+                self.resolve(&contact, &material, dt);
+                contacts.push(contact);
+            }
+        }
+
+        self.rotate(dt);
+
+        let speed = vec3_len(self.velocity);
+        if !contacts.is_empty() && speed < SLEEP_SPEED {
+            self.still_time += dt;
+            if self.still_time > SLEEP_TIME {
+                self.asleep = true;
+                self.velocity = [0.0, 0.0, 0.0];
+                self.angular_velocity = [0.0, 0.0, 0.0];
+            }
+        }
+        else {
+            self.still_time = 0.0;
+        }
+        contacts
+    }
+
+    /**
+     * Drag force of the air moving relative to the projectile. Pushes the
+     * projectile along with the wind as well as slowing it down.
+     */
+    fn drag(&self, air: &Atmosphere) -> Vector3<f32> {
+        let relative = vec3_sub(air.wind_at(self.position), self.velocity);
+        let speed = vec3_len(relative);
+        let area = PI * self.radius * self.radius;
+        let density = air.density_at(self.position[1]);
+        vec3_scale(relative, 0.5 * density * SPHERE_DRAG * area * speed)
+    }
+
+    /**
+     * Pushes the sphere out of the obstacle and applies the bounce and the
+     * friction of the contact to the velocity.
+     */
+    fn resolve(&mut self, contact: &Contact, material: &Material, dt: f32) {
+        self.position = vec3_add(self.position, vec3_scale(contact.normal, contact.depth));
+
+        let normal_speed = vec3_dot(self.velocity, contact.normal);
+        if normal_speed >= 0.0 {
+            return;
+        }
+        let restitution = if -normal_speed < BOUNCE_THRESHOLD {
+            0.0
+        }
+        else {
+            material.restitution
+        };
+        let normal_change = -(1.0 + restitution) * normal_speed;
+        self.velocity = vec3_add(self.velocity, vec3_scale(contact.normal, normal_change));
+
+        // Coulomb friction, can't take away more than the sliding speed
+        let tangent_velocity = vec3_sub(
+            self.velocity, vec3_scale(contact.normal, vec3_dot(self.velocity, contact.normal))
+        );
+        let tangent_speed = vec3_len(tangent_velocity);
+        if tangent_speed > 0.0 {
+            let friction = (material.friction * normal_change).min(tangent_speed);
+            let rolling = ROLLING_RESISTANCE * -GRAVITY[1] * dt;
+            let slow_down = (friction + rolling).min(tangent_speed);
+            self.velocity = vec3_sub(
+                self.velocity, vec3_scale(tangent_velocity, slow_down / tangent_speed)
+            );
+        }
+
+        // Roll without slipping along the surface
+        self.angular_velocity = vec3_scale(
+            vec3_cross(contact.normal, self.velocity), 1.0 / self.radius
+        );
+    }
+
+    fn rotate(&mut self, dt: f32) {
+        let speed = vec3_len(self.angular_velocity);
+        if speed == 0.0 {
+            return;
+        }
+        let axis = vec3_scale(self.angular_velocity, 1.0 / speed);
+        let rotation = quaternion::axis_angle(axis, speed * dt);
+        let orientation = quaternion::mul(rotation, self.orientation);
+        // Keep it a unit quaternion, errors add up otherwise
+        self.orientation = quaternion::scale(orientation, 1.0 / quaternion::len(orientation));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::rc::Rc;
+    use collision::{Shape, GRASS, STONE};
+    use config::Config;
+    use terrain::Terrain;
+
+    const DT: f32 = 1.0 / 120.0;
+
+    fn flat() -> Rc<Terrain> {
+        Rc::new(Terrain::from_config(&Config::parse("[terrain]\nhill_height = 0")))
+    }
+
+    fn ground() -> Vec<Collider> {
+        vec![Collider { shape: Shape::Terrain(flat()), material: GRASS }]
+    }
+
+    fn still_air() -> Atmosphere {
+        Atmosphere::from_config(&Config::parse("[wind]\nspeed = 0"), flat())
+    }
+
+    #[test]
+    fn stones_bounce_lower_each_time() {
+        let (colliders, air) = (ground(), still_air());
+        let mut stone = Projectile::new([20.0, 5.0, 20.0], [0.0; 3], 0.2, 1500.0, STONE);
+        let mut falling = true;
+        let mut apexes = Vec::new();
+        for _ in 0..(4.0 / DT) as usize {
+            stone.update(DT, &colliders, &air);
+            if falling && stone.velocity[1] > 0.0 {
+                falling = false;
+            }
+            else if !falling && stone.velocity[1] <= 0.0 {
+                falling = true;
+                apexes.push(stone.position[1]);
+            }
+        }
+        assert!(apexes.len() >= 2, "{:?}", apexes);
+        // Stone on grass keeps half of its speed, a quarter of its height
+        assert!((apexes[0] - 0.2 - 4.8 * 0.25).abs() < 0.1, "{:?}", apexes);
+        assert!(apexes[1] < apexes[0]);
+    }
+
+    #[test]
+    fn rolling_stones_come_to_rest() {
+        let (colliders, air) = (ground(), still_air());
+        let mut stone = Projectile::new([20.0, 0.2, 20.0], [3.0, 0.0, 0.0], 0.2, 1500.0, STONE);
+        let mut steps = 0;
+        while !stone.asleep && steps < (20.0 / DT) as usize {
+            let contacts = stone.update(DT, &colliders, &air);
+            assert!(!contacts.is_empty(), "left the ground after {} steps", steps);
+            steps += 1;
+        }
+        assert!(stone.asleep);
+        assert_eq!(stone.velocity, [0.0; 3]);
+        assert!((stone.position[1] - 0.2).abs() < 0.01);
+        assert!(stone.position[0] > 20.3 && stone.position[0] < 30.0, "{:?}", stone.position);
+        // Once asleep it stays put
+        let position = stone.position;
+        stone.update(DT, &colliders, &air);
+        assert_eq!(stone.position, position);
+    }
+}
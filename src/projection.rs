@@ -0,0 +1,148 @@
+use std::f32::consts::PI;
+use clock::lerp;
+use config::Config;
+
+// zoom_in and zoom_out change the field of view by this much
+const FOV_STEP: f32 = PI / 36.0;
+const MIN_FOV: f32 = PI / 18.0;
+const MAX_FOV: f32 = PI * 2.0 / 3.0;
+// ... and the height the orthographic view shows by this factor
+const ORTHO_ZOOM: f32 = 1.2;
+const MIN_ORTHO_HEIGHT: f32 = 0.5;
+const MAX_ORTHO_HEIGHT: f32 = 1000.0;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ProjectionMode {
+    Perspective,
+    // Parallel lines stay parallel, things don't get smaller further away
+    Orthographic,
+}
+
+/**
+ * How the camera projects the scene onto the window, read from the [camera]
+ * section. Rendering and everything fitting things into the view use the
+ * same one, so they always agree.
+ *
+ * The aspect ratio is the width of the view divided by its height.
+ */
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Projection {
+    pub mode: ProjectionMode,
+    // Vertical field of view in radians
+    pub fov: f32,
+    // Distances of the clip planes
+    pub near: f32,
+    pub far: f32,
+    // Height in meters the orthographic view shows
+    pub ortho_height: f32,
+}
+
+impl Projection {
+    pub fn from_config(config: &Config) -> Projection {
+        let mode = if config.get("camera", "orthographic", false) {
+            ProjectionMode::Orthographic
+        }
+        else {
+            ProjectionMode::Perspective
+        };
+        let fov: f32 = config.get("camera", "fov", 60.0);
+        let near = config.get("camera", "near", 0.1f32).max(0.001);
+        Projection {
+            mode: mode,
+            fov: fov.to_radians().max(MIN_FOV).min(MAX_FOV),
+            near: near,
+            far: config.get("camera", "far", 1024.0f32).max(near * 2.0),
+            ortho_height: config.get("camera", "ortho_height", 20.0f32)
+                .max(MIN_ORTHO_HEIGHT).min(MAX_ORTHO_HEIGHT),
+        }
+    }
+
+    pub fn matrix(&self, aspect_ratio: f32) -> [[f32; 4]; 4] {
+        let (near, far) = (self.near, self.far);
+        // note: remember that this is column-major, so the lines of code are actually columns
+        match self.mode {
+            ProjectionMode::Perspective => {
+                let f = 1.0 / (self.fov / 2.0).tan();
+                [
+                    [f / aspect_ratio, 0.0, 0.0, 0.0],
+                    [0.0, f, 0.0, 0.0],
+                    [0.0, 0.0, (far + near) / (far - near), 1.0],
+                    [0.0, 0.0, -(2.0 * far * near) / (far - near), 0.0],
+                ]
+            },
+            ProjectionMode::Orthographic => {
+                let f = 2.0 / self.ortho_height;
+                [
+                    [f / aspect_ratio, 0.0, 0.0, 0.0],
+                    [0.0, f, 0.0, 0.0],
+                    [0.0, 0.0, 2.0 / (far - near), 0.0],
+                    [0.0, 0.0, -(far + near) / (far - near), 1.0],
+                ]
+            },
+        }
+    }
+
+    pub fn toggle_mode(&mut self) {
+        self.mode = match self.mode {
+            ProjectionMode::Perspective => ProjectionMode::Orthographic,
+            ProjectionMode::Orthographic => ProjectionMode::Perspective,
+        };
+    }
+
+    /**
+     * Zooms in by steps, or out for negative ones.
+     */
+    pub fn zoom(&mut self, steps: f32) {
+        match self.mode {
+            ProjectionMode::Perspective => {
+                self.fov = (self.fov - steps * FOV_STEP).max(MIN_FOV).min(MAX_FOV);
+            },
+            ProjectionMode::Orthographic => self.zoom_ortho(ORTHO_ZOOM.powf(-steps)),
+        }
+    }
+
+    /**
+     * Multiplies the height the orthographic view shows.
+     */
+    pub fn zoom_ortho(&mut self, factor: f32) {
+        self.ortho_height = (self.ortho_height * factor)
+            .max(MIN_ORTHO_HEIGHT).min(MAX_ORTHO_HEIGHT);
+    }
+
+    pub fn describe(&self) -> String {
+        match self.mode {
+            ProjectionMode::Perspective => {
+                format!("Perspective, field of view {:.0}°", self.fov.to_degrees())
+            },
+            ProjectionMode::Orthographic => format!("Orthographic, {:.1} m high", self.ortho_height),
+        }
+    }
+
+    /**
+     * Makes a sphere with the radius fill the view, returning how far away
+     * its center has to be. The orthographic view gets zoomed to fit it.
+     */
+    pub fn fit(&mut self, radius: f32, aspect_ratio: f32) -> f32 {
+        // Narrow windows see less to the sides than up and down
+        let vertical = self.fov / 2.0;
+        let horizontal = (vertical.tan() * aspect_ratio).atan();
+        let distance = radius / vertical.min(horizontal).sin();
+        if self.mode == ProjectionMode::Orthographic {
+            self.ortho_height = (2.0 * radius / aspect_ratio.min(1.0))
+                .max(MIN_ORTHO_HEIGHT).min(MAX_ORTHO_HEIGHT);
+        }
+        // Far enough for the near plane not to cut into it
+        distance.max(radius + self.near)
+    }
+
+    /**
+     * The projection between this and the other one, for interpolating.
+     */
+    pub fn lerp(&self, other: &Projection, alpha: f32) -> Projection {
+        Projection {
+            fov: lerp(self.fov, other.fov, alpha),
+            ortho_height: lerp(self.ortho_height, other.ortho_height, alpha),
+            .. *other
+        }
+    }
+}
@@ -0,0 +1,307 @@
+use glium;
+use glium::backend::glutin_backend;
+use std::rc::Rc;
+use std::sync::mpsc::Receiver;
+use catapult::{gen_box, Normal, Vertex};
+use config::Config;
+use drawable::{Drawable, DrawObject};
+use firing::FiringEvent;
+use input::{Action, Input};
+use matrix::{mul_matrices, translation_matrix};
+use state::Settings;
+use viewport::Viewport;
+use world::WorldEvent;
+
+// Where the distances get measured from, the pivot of the throw arm
+pub const RANGE_ORIGIN: [f32; 3] = [3.65, 0.0, 1.75];
+// The catapult throws along the negative x axis
+pub const RANGE_DIRECTION: [f32; 3] = [-1.0, 0.0, 0.0];
+const POINTS_PER_RING: u32 = 10;
+
+/**
+ * How the target range is laid out, read from the [range] section of the
+ * config file.
+ */
+pub struct RangeConfig {
+    pub enabled: bool,
+    pub distances: Vec<f32>,
+    pub target_radius: f32,
+    pub rings: u32,
+}
+
+impl RangeConfig {
+    pub fn from_config(config: &Config) -> RangeConfig {
+        RangeConfig {
+            enabled: config.get("range", "enabled", false),
+            distances: config.get_list("range", "distances", vec![20.0, 35.0, 50.0]),
+            target_radius: config.get("range", "target_radius", 3.0),
+            rings: config.get("range", "rings", 5).max(1),
+        }
+    }
+
+    /**
+     * Score of a shot landing distance away from a target center.
+     */
+    pub fn score(&self, distance: f32) -> u32 {
+        if distance > self.target_radius {
+            return 0;
+        }
+        let ring_width = self.target_radius / self.rings as f32;
+        let ring = ((distance / ring_width) as u32).min(self.rings - 1);
+        (self.rings - ring) * POINTS_PER_RING
+    }
+}
+
+pub struct Target {
+    pub center: [f32; 3],
+    pub distance: f32,
+}
+
+/**
+ * Statistics of one training session, from enabling the range mode until
+ * disabling it.
+ */
+pub struct SessionStats {
+    pub shots: u32,
+    pub landed: u32,
+    pub hits: u32,
+    pub score: u32,
+    pub best_miss_distance: Option<f32>,
+    total_miss_distance: f32,
+}
+
+impl SessionStats {
+    pub fn new() -> SessionStats {
+        SessionStats {
+            shots: 0,
+            landed: 0,
+            hits: 0,
+            score: 0,
+            best_miss_distance: None,
+            total_miss_distance: 0.0,
+        }
+    }
+
+    /**
+     * Average distance of the landing points from the nearest target center.
+     */
+    pub fn average_miss_distance(&self) -> Option<f32> {
+        if self.landed == 0 {
+            None
+        }
+        else {
+            Some(self.total_miss_distance / self.landed as f32)
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        let mut text = format!("{} shots, {} hits, {} points", self.shots, self.hits, self.score);
+        if let (Some(best), Some(average)) =
+            (self.best_miss_distance, self.average_miss_distance())
+        {
+            text.push_str(&format!(", best {:.2}m, average {:.2}m", best, average));
+        }
+        text
+    }
+}
+
+/**
+ * Training mode: targets at fixed distances in the line of fire. Every shot
+ * fired gets counted and scored by how close to a target center it lands,
+ * the session and the last shot show in the window title. ToggleRange
+ * toggles the mode, each time it gets enabled a new session starts.
+ */
+pub struct TargetRange {
+    pub config: RangeConfig,
+    pub targets: Vec<Target>,
+    pub session: SessionStats,
+    active: bool,
+    // Shots fired while active, which haven't landed yet
+    in_flight: u32,
+    // Miss distance, distance of the nearest target and score of the last shot
+    last_shot: Option<(f32, f32, u32)>,
+    catapult_events: Receiver<FiringEvent>,
+    world_events: Receiver<WorldEvent>,
+    // One board per ring, the inner ones stacked on top
+    rings: Vec<DrawObject<Vertex, Normal, u16>>,
+}
+
+impl TargetRange {
+    fn toggle(&mut self) {
+        self.active = !self.active;
+        if self.active {
+            self.session = SessionStats::new();
+            self.in_flight = 0;
+            self.last_shot = None;
+        }
+    }
+
+    fn land(&mut self, position: [f32; 3]) {
+        if self.in_flight == 0 {
+            return;
+        }
+        self.in_flight -= 1;
+
+        let nearest = self.targets.iter().map(|target| {
+            let dx = position[0] - target.center[0];
+            let dz = position[2] - target.center[2];
+            (target.distance, (dx * dx + dz * dz).sqrt())
+        }).fold(None, |nearest: Option<(f32, f32)>, candidate| match nearest {
+            Some(n) if n.1 <= candidate.1 => Some(n),
+            _ => Some(candidate),
+        });
+        let (target_distance, miss) = match nearest {
+            Some(nearest) => nearest,
+            None => return,
+        };
+
+        let score = self.config.score(miss);
+        let session = &mut self.session;
+        session.landed += 1;
+        session.score += score;
+        session.total_miss_distance += miss;
+        if score > 0 {
+            session.hits += 1;
+        }
+        session.best_miss_distance = Some(match session.best_miss_distance {
+            Some(best) => best.min(miss),
+            None => miss,
+        });
+        self.last_shot = Some((miss, target_distance, score));
+    }
+}
+
+impl Drawable for TargetRange {
+    fn status(&self) -> Option<String> {
+        if !self.active {
+            return None;
+        }
+        let mut text = format!("Range: {}", self.session.describe());
+        if let Some((miss, target_distance, score)) = self.last_shot {
+            text.push_str(&format!(", last {:.2}m from the {}m target for {}",
+                                   miss, target_distance, score));
+        }
+        Some(text)
+    }
+
+    fn update(&mut self, inputs: &[Input], _: f32) {
+        for input in inputs {
+            if *input == Input::Pressed(Action::ToggleRange) {
+                self.toggle();
+            }
+        }
+
+        let released = self.catapult_events.try_iter().filter(|event| match event {
+            &FiringEvent::ReleasedProjectile { .. } => true,
+            _ => false,
+        }).count() as u32;
+        if self.active {
+            self.session.shots += released;
+            self.in_flight += released;
+        }
+
+        let landings = self.world_events.try_iter().collect::<Vec<_>>();
+        for event in landings {
+            match event {
+                WorldEvent::Landed { position, .. } => self.land(position),
+            }
+        }
+    }
+
+    fn draw(
+            &self,
+            settings: &Settings,
+            viewport: &Viewport,
+            target: &mut glium::Frame,
+            world_matrix: [[f32; 4]; 4]
+    )
+        -> Result<(), glium::DrawError>
+    {
+        if !self.active {
+            return Ok(());
+        }
+        for range_target in self.targets.iter() {
+            let matrix = mul_matrices(world_matrix, translation_matrix(range_target.center));
+            for ring in self.rings.iter() {
+                ring.draw(settings, viewport, target, matrix)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/**
+ * Places the targets along the line of fire.
+ */
+pub fn init_range(
+    display: &glutin_backend::GlutinFacade,
+    texture: Rc<glium::texture::Texture2d>,
+    config: RangeConfig,
+    catapult_events: Receiver<FiringEvent>,
+    world_events: Receiver<WorldEvent>
+)
+    -> TargetRange
+{
+    let targets = config.distances.iter().map(|&distance| Target {
+        center: [
+            RANGE_ORIGIN[0] + RANGE_DIRECTION[0] * distance,
+            RANGE_ORIGIN[1] + RANGE_DIRECTION[1] * distance,
+            RANGE_ORIGIN[2] + RANGE_DIRECTION[2] * distance,
+        ],
+        distance: distance,
+    }).collect();
+
+    let ring_width = config.target_radius / config.rings as f32;
+    let rings = (0..config.rings).map(|ring| {
+        let r = config.target_radius - ring as f32 * ring_width;
+        let height = 0.02 * (ring + 1) as f32;
+        gen_box(display, &texture, (-r, 0.0, -r), (r, height, r))
+    }).collect();
+
+    TargetRange {
+        active: config.enabled,
+        config: config,
+        targets: targets,
+        session: SessionStats::new(),
+        in_flight: 0,
+        last_shot: None,
+        catapult_events: catapult_events,
+        world_events: world_events,
+        rings: rings,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> RangeConfig {
+        RangeConfig::from_config(&Config::parse("[range]\ntarget_radius = 2.5\nrings = 5"))
+    }
+
+    #[test]
+    fn inner_rings_score_more() {
+        let config = config();
+        assert_eq!(config.score(0.0), 50);
+        assert_eq!(config.score(0.49), 50);
+        assert_eq!(config.score(0.51), 40);
+        assert_eq!(config.score(2.4), 10);
+    }
+
+    #[test]
+    fn the_edge_still_scores() {
+        let config = config();
+        assert_eq!(config.score(2.5), 10);
+        assert_eq!(config.score(2.51), 0);
+        assert_eq!(config.score(100.0), 0);
+    }
+
+    #[test]
+    fn session_averages_landed_shots() {
+        let mut session = SessionStats::new();
+        assert_eq!(session.average_miss_distance(), None);
+        session.landed = 2;
+        session.total_miss_distance = 3.0;
+        assert_eq!(session.average_miss_distance(), Some(1.5));
+    }
+}
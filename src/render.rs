@@ -5,7 +5,14 @@ use catapult;
 use glium::backend::glutin_backend;
 use glium::Surface;
 use camera;
+use viewport;
+use projection::Projection;
 use state::Settings;
+use world;
+use range;
+use config::{Config, CONFIG_FILE};
+use firing::{FiringConfig, FiringStatus};
+use std::rc::Rc;
 
 pub const DEFAULT_MATRIX: [[f32; 4]; 4] = [
     [1.0, 0.0, 0.0, 0.0],
@@ -20,8 +27,10 @@ pub const DEFAULT_MATRIX: [[f32; 4]; 4] = [
 pub fn init<'a>(display: &glutin_backend::GlutinFacade) -> Settings<'a> {
     let vertex_shader_src = read_file("vertex_shader.shader");
     let fragment_shader_src = read_file("fragment_shader.shader");
-    // Draw one time to get the target to create the initial perspective_matrix
+    // Draw one time to get the size of the window for the camera
     let target = display.draw();
+    let texture = load_texture(display);
+    let config = Config::load(CONFIG_FILE);
     let mut settings = Settings {
         program: glium::Program::from_source(
             display, &vertex_shader_src, &fragment_shader_src, None
@@ -34,45 +43,72 @@ pub fn init<'a>(display: &glutin_backend::GlutinFacade) -> Settings<'a> {
             },
             .. Default::default()
         },
-        camera: camera::CameraState::new(),
+        camera: camera::CameraState::new(camera::CameraConfig::from_config(&config)),
+        viewports: viewport::Viewports::new(Projection::from_config(&config)),
         light: [1.4, 0.4, -0.7f32],
-        objects: Vec::new()
+        objects: Vec::new(),
+        world: world::init_world(display, texture.clone(), &config),
+        firing_status: None,
+        config: Config::empty(),
+        alpha: 1.0,
     };
 
     let (width, height): (u32, u32) = target.get_dimensions();
-    let aspect_ratio = height as f32 / width as f32;
-    settings.set_aspect_ratio(aspect_ratio);
-    settings.objects = vec![catapult::init_catapult(&display, &settings)];
+    settings.camera.set_window_size(width, height);
+    settings.viewports.set_window_size(width, height);
+    let mut catapult = catapult::init_catapult(
+        &display, &settings, texture.clone(), FiringConfig::from_config(&config)
+    );
+    settings.world.listen(catapult.subscribe());
+    settings.firing_status = Some(FiringStatus::new(catapult.subscribe()));
+    settings.camera.listen(catapult.subscribe(), settings.world.subscribe());
+    let target_range = range::init_range(
+        &display, texture, range::RangeConfig::from_config(&config),
+        catapult.subscribe(), settings.world.subscribe()
+    );
+    settings.objects = vec![Box::new(catapult), Box::new(target_range)];
+    settings.config = config;
 
     target.finish().unwrap();
     settings
 }
 
 /**
- * Renders the whole scene.
+ * Renders the whole scene, once into every viewport.
  */
 pub fn render<'a>(display: &glutin_backend::GlutinFacade, settings: &Settings<'a>) {
     let mut target = display.draw();
     target.clear_color_and_depth((0.1, 0.1, 0.1, 1.0), 1.0);
-    settings.objects[0].draw(&settings, &mut target, DEFAULT_MATRIX).unwrap();
+    let (width, height): (u32, u32) = target.get_dimensions();
+    let viewports = settings.viewports.viewports(&settings.camera, settings.alpha, width, height);
+    for object in settings.objects.iter() {
+        object.prepare(settings.alpha);
+    }
+    for viewport in viewports.iter() {
+        for object in settings.objects.iter() {
+            object.draw(settings, viewport, &mut target, DEFAULT_MATRIX).unwrap();
+        }
+        settings.world.draw(settings, viewport, &mut target).unwrap();
+    }
 
     target.finish().unwrap();
 }
 
-pub fn perspective_matrix(target: &glium::Frame) -> [[f32; 4]; 4] {
-    use glium::Surface;
-    let (width, height): (u32, u32) = target.get_dimensions();
-    let aspect_ratio = height as f32 / width as f32;
-    let fov: f32 = 3.141592 / 3.0;
-    let zfar = 1024.0;
-    let znear = 0.1;
-    let f = 1.0 / (fov / 2.0).tan();
-    [
-        [f * aspect_ratio, 0.0, 0.0, 0.0],
-        [0.0, f, 0.0, 0.0],
-        [0.0, 0.0, (zfar + znear) / (zfar - znear), 1.0],
-        [0.0, 0.0, -(2.0 * zfar * znear) / (zfar - znear), 0.0],
-    ]
+/**
+ * Loads the wood texture everything is made of.
+ */
+pub fn load_texture(display: &glutin_backend::GlutinFacade) -> Rc<glium::texture::Texture2d> {
+    use std::io::Cursor;
+    use image;
+    let image = image::load(
+        Cursor::new(&include_bytes!("../wood.png")[..]), image::PNG
+    ).unwrap().to_rgba();
+    let image_dimensions = image.dimensions();
+    let image = glium::texture::RawImage2d::from_raw_rgba_reversed(
+        image.into_raw(),
+        image_dimensions
+    );
+    Rc::new(glium::texture::Texture2d::new(display, image).unwrap())
 }
 
 fn read_file(path: &str) -> String {
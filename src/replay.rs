@@ -0,0 +1,255 @@
+use glium::glutin::{ElementState, Event, MouseButton, MouseScrollDelta, TouchPhase};
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use keys::{key_name, parse_key};
+
+/**
+ * Writes the events consumed by every simulation step to a file, one line
+ * per event starting with the tick of the step:
+ *
+ *     # timestep 0.008333334
+ *     120 key pressed 57 Space
+ *     131 key released 57 Space
+ *
+ * Events that don't reach the simulation, like closing the window, are left
+ * out.
+ */
+pub struct Recorder {
+    file: BufWriter<File>,
+}
+
+impl Recorder {
+    pub fn create(path: &str, timestep: f32) -> Result<Recorder, String> {
+        let file = File::create(path)
+            .map_err(|e| format!("Can't create recording {}: {}", path, e))?;
+        let mut recorder = Recorder { file: BufWriter::new(file) };
+        recorder.write(&format!("# timestep {}", timestep));
+        Ok(recorder)
+    }
+
+    pub fn record(&mut self, tick: u64, events: &[Event]) {
+        for event in events {
+            if let Some(encoded) = encode_event(event) {
+                self.write(&format!("{} {}", tick, encoded));
+            }
+        }
+    }
+
+    fn write(&mut self, line: &str) {
+        if let Err(e) = writeln!(self.file, "{}", line) {
+            println!("Can't write recording: {}", e);
+        }
+    }
+}
+
+/**
+ * Events read from a recording, handed out tick by tick. As long as the
+ * config and the timestep are the same, the simulation runs exactly like it
+ * did when recording.
+ */
+pub struct Replay {
+    events: VecDeque<(u64, Event)>,
+}
+
+impl Replay {
+    pub fn load(path: &str, timestep: f32) -> Result<Replay, String> {
+        let file = File::open(path)
+            .map_err(|e| format!("Can't open recording {}: {}", path, e))?;
+        let mut events = VecDeque::new();
+        for (number, line) in BufReader::new(file).lines().enumerate() {
+            let line = line.map_err(|e| format!("Can't read recording {}: {}", path, e))?;
+            let line = line.trim();
+            if let Some(recorded) = line.strip_prefix("# timestep ") {
+                if recorded.parse::<f32>().ok() != Some(timestep) {
+                    println!("{} was recorded with a timestep of {}, replay may differ",
+                             path, recorded);
+                }
+                continue;
+            }
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut parts = line.splitn(2, ' ');
+            let tick = parts.next().and_then(|tick| tick.parse::<u64>().ok());
+            let event = parts.next().and_then(decode_event);
+            match (tick, event) {
+                (Some(tick), Some(event)) => events.push_back((tick, event)),
+                _ => {
+                    return Err(format!("{}:{}: invalid event \"{}\"", path, number + 1, line));
+                },
+            }
+        }
+        Ok(Replay { events: events })
+    }
+
+    /**
+     * The events recorded for the given tick.
+     */
+    pub fn events_for(&mut self, tick: u64) -> Vec<Event> {
+        let mut events = Vec::new();
+        while self.events.front().map_or(false, |&(t, _)| t <= tick) {
+            if let Some((_, event)) = self.events.pop_front() {
+                events.push(event);
+            }
+        }
+        events
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.events.is_empty()
+    }
+}
+
+fn encode_state(state: ElementState) -> &'static str {
+    match state {
+        ElementState::Pressed => "pressed",
+        ElementState::Released => "released",
+    }
+}
+
+fn decode_state(state: &str) -> Option<ElementState> {
+    match state {
+        "pressed" => Some(ElementState::Pressed),
+        "released" => Some(ElementState::Released),
+        _ => None,
+    }
+}
+
+fn encode_event(event: &Event) -> Option<String> {
+    match event {
+        &Event::KeyboardInput(state, scancode, key) => {
+            let key = key.map_or("-".to_string(), key_name);
+            Some(format!("key {} {} {}", encode_state(state), scancode, key))
+        },
+        &Event::MouseMoved(x, y) => Some(format!("mouse_moved {} {}", x, y)),
+        &Event::MouseInput(state, button) => {
+            let button = match button {
+                MouseButton::Left => "left".to_string(),
+                MouseButton::Right => "right".to_string(),
+                MouseButton::Middle => "middle".to_string(),
+                MouseButton::Other(n) => n.to_string(),
+            };
+            Some(format!("mouse {} {}", encode_state(state), button))
+        },
+        &Event::MouseWheel(delta, phase) => {
+            let delta = match delta {
+                MouseScrollDelta::LineDelta(x, y) => format!("lines {} {}", x, y),
+                MouseScrollDelta::PixelDelta(x, y) => format!("pixels {} {}", x, y),
+            };
+            Some(format!("wheel {} {:?}", delta, phase))
+        },
+        &Event::Focused(focused) => Some(format!("focused {}", focused)),
+        &Event::Resized(width, height) => Some(format!("resized {} {}", width, height)),
+        _ => None,
+    }
+}
+
+fn decode_event(line: &str) -> Option<Event> {
+    let words = line.split_whitespace().collect::<Vec<_>>();
+    if words.is_empty() {
+        return None;
+    }
+    match (words[0], words.len()) {
+        ("key", 4) => {
+            let key = if words[3] == "-" { None } else { Some(parse_key(words[3])?) };
+            Some(Event::KeyboardInput(decode_state(words[1])?, words[2].parse().ok()?, key))
+        },
+        ("mouse_moved", 3) => {
+            Some(Event::MouseMoved(words[1].parse().ok()?, words[2].parse().ok()?))
+        },
+        ("mouse", 3) => {
+            let button = match words[2] {
+                "left" => MouseButton::Left,
+                "right" => MouseButton::Right,
+                "middle" => MouseButton::Middle,
+                n => MouseButton::Other(n.parse().ok()?),
+            };
+            Some(Event::MouseInput(decode_state(words[1])?, button))
+        },
+        ("wheel", 5) => {
+            let x = words[2].parse().ok()?;
+            let y = words[3].parse().ok()?;
+            let delta = match words[1] {
+                "lines" => MouseScrollDelta::LineDelta(x, y),
+                "pixels" => MouseScrollDelta::PixelDelta(x, y),
+                _ => return None,
+            };
+            let phase = match words[4] {
+                "Started" => TouchPhase::Started,
+                "Moved" => TouchPhase::Moved,
+                "Ended" => TouchPhase::Ended,
+                "Cancelled" => TouchPhase::Cancelled,
+                _ => return None,
+            };
+            Some(Event::MouseWheel(delta, phase))
+        },
+        ("focused", 2) => Some(Event::Focused(words[1].parse().ok()?)),
+        ("resized", 3) => Some(Event::Resized(words[1].parse().ok()?, words[2].parse().ok()?)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use glium::glutin::VirtualKeyCode;
+    use std::env;
+
+    fn events() -> Vec<Event> {
+        vec![
+            Event::KeyboardInput(ElementState::Pressed, 57, Some(VirtualKeyCode::Space)),
+            Event::KeyboardInput(ElementState::Released, 42, Some(VirtualKeyCode::LShift)),
+            Event::KeyboardInput(ElementState::Pressed, 99, None),
+            Event::MouseMoved(-3, 750),
+            Event::MouseInput(ElementState::Pressed, MouseButton::Left),
+            Event::MouseInput(ElementState::Released, MouseButton::Right),
+            Event::MouseInput(ElementState::Pressed, MouseButton::Middle),
+            Event::MouseInput(ElementState::Pressed, MouseButton::Other(4)),
+            Event::MouseWheel(MouseScrollDelta::LineDelta(0.0, -1.0), TouchPhase::Moved),
+            Event::MouseWheel(MouseScrollDelta::PixelDelta(1.5, 30.25), TouchPhase::Started),
+            Event::MouseWheel(MouseScrollDelta::LineDelta(0.0, 2.0), TouchPhase::Ended),
+            Event::MouseWheel(MouseScrollDelta::LineDelta(0.0, 0.0), TouchPhase::Cancelled),
+            Event::Focused(false),
+            Event::Resized(1024, 768),
+        ]
+    }
+
+    #[test]
+    fn events_survive_encoding() {
+        for event in events() {
+            let encoded = encode_event(&event).expect("encodable");
+            let decoded = decode_event(&encoded).expect(&encoded);
+            assert_eq!(format!("{:?}", decoded), format!("{:?}", event));
+        }
+    }
+
+    #[test]
+    fn other_events_are_left_out() {
+        assert!(encode_event(&Event::Closed).is_none());
+        assert!(decode_event("key pressed 57").is_none());
+        assert!(decode_event("key pressed 57 NoSuchKey").is_none());
+        assert!(decode_event("wheel lines 0 1 Sideways").is_none());
+    }
+
+    #[test]
+    fn replay_hands_out_recorded_ticks() {
+        let path = env::temp_dir().join(format!("catapult-replay-{}.rec", std::process::id()));
+        let path = path.to_str().unwrap();
+        {
+            let mut recorder = Recorder::create(path, 0.01).unwrap();
+            let events = events();
+            recorder.record(3, &events[..2]);
+            recorder.record(7, &events[2..]);
+            recorder.record(7, &[Event::Closed]);
+        }
+        let mut replay = Replay::load(path, 0.01).unwrap();
+        let _ = ::std::fs::remove_file(path);
+        assert!(replay.events_for(2).is_empty());
+        let debug = |events: Vec<Event>| format!("{:?}", events);
+        assert_eq!(debug(replay.events_for(3)), debug(events()[..2].to_vec()));
+        assert!(!replay.is_finished());
+        assert_eq!(debug(replay.events_for(10)), debug(events()[2..].to_vec()));
+        assert!(replay.is_finished());
+    }
+}
@@ -0,0 +1,588 @@
+use vecmath::{Vector3, vec3_add, vec3_sub, vec3_scale, vec3_dot, vec3_cross, vec3_len};
+use quaternion;
+use quaternion::Quaternion;
+use collision::{Collider, Material, Obb, Shape};
+use projectile::{GRAVITY, Projectile};
+
+// Rounds of impulse solving per step, more give steadier stacks
+const SOLVER_ITERATIONS: usize = 12;
+// Overlap that is tolerated, so resting contacts don't jitter
+const PENETRATION_SLOP: f32 = 0.01;
+// How much of the overlap gets corrected per step
+const BAUMGARTE: f32 = 0.2;
+// Impacts slower than this don't bounce
+const BOUNCE_THRESHOLD: f32 = 1.0;
+// Below these speeds a body counts as still
+const SLEEP_LINEAR: f32 = 0.1;
+const SLEEP_ANGULAR: f32 = 0.2;
+// How long a body has to be still before it falls asleep
+const SLEEP_TIME: f32 = 0.5;
+
+/**
+ * A box shaped rigid body, for the blocks of the destructible structures.
+ */
+pub struct RigidBody {
+    pub position: Vector3<f32>,
+    pub orientation: Quaternion<f32>,
+    pub velocity: Vector3<f32>,
+    pub angular_velocity: Vector3<f32>,
+    pub half_extents: Vector3<f32>,
+    pub inverse_mass: f32,
+    // Diagonal of the inverse inertia tensor in body coordinates
+    inverse_inertia: Vector3<f32>,
+    pub material: Material,
+    // State of the last step, used to interpolate the rendering
+    pub previous_position: Vector3<f32>,
+    pub previous_orientation: Quaternion<f32>,
+    pub asleep: bool,
+    still_time: f32,
+    // Which mesh draws the body, chosen by whoever spawns it
+    pub mesh: usize,
+}
+
+impl RigidBody {
+    /**
+     * A box with the given half extents, density in kg/m³. Bodies start
+     * asleep so freshly built structures stand still until something hits
+     * them.
+     */
+    pub fn new_box(
+        position: Vector3<f32>,
+        orientation: Quaternion<f32>,
+        half_extents: Vector3<f32>,
+        density: f32,
+        material: Material
+    )
+        -> RigidBody
+    {
+        let size = vec3_scale(half_extents, 2.0);
+        let mass = density * size[0] * size[1] * size[2];
+        let inertia = [
+            mass / 12.0 * (size[1] * size[1] + size[2] * size[2]),
+            mass / 12.0 * (size[0] * size[0] + size[2] * size[2]),
+            mass / 12.0 * (size[0] * size[0] + size[1] * size[1]),
+        ];
+        RigidBody {
+            position: position,
+            orientation: orientation,
+            velocity: [0.0, 0.0, 0.0],
+            angular_velocity: [0.0, 0.0, 0.0],
+            half_extents: half_extents,
+            inverse_mass: 1.0 / mass,
+            inverse_inertia: [1.0 / inertia[0], 1.0 / inertia[1], 1.0 / inertia[2]],
+            material: material,
+            previous_position: position,
+            previous_orientation: orientation,
+            asleep: true,
+            still_time: 0.0,
+            mesh: 0,
+        }
+    }
+
+    pub fn axes(&self) -> [Vector3<f32>; 3] {
+        [
+            quaternion::rotate_vector(self.orientation, [1.0, 0.0, 0.0]),
+            quaternion::rotate_vector(self.orientation, [0.0, 1.0, 0.0]),
+            quaternion::rotate_vector(self.orientation, [0.0, 0.0, 1.0]),
+        ]
+    }
+
+    pub fn obb(&self) -> Obb {
+        Obb { center: self.position, axes: self.axes(), half_extents: self.half_extents }
+    }
+
+    fn is_moving(&self) -> bool {
+        vec3_len(self.velocity) >= SLEEP_LINEAR || vec3_len(self.angular_velocity) >= SLEEP_ANGULAR
+    }
+
+    pub fn wake(&mut self) {
+        self.asleep = false;
+        self.still_time = 0.0;
+    }
+
+    /**
+     * Applies the inverse inertia tensor in world coordinates to v.
+     */
+    fn inverse_inertia_times(&self, v: Vector3<f32>) -> Vector3<f32> {
+        let axes = self.axes();
+        let mut result = [0.0, 0.0, 0.0];
+        for i in 0..3 {
+            let local = vec3_dot(v, axes[i]) * self.inverse_inertia[i];
+            result = vec3_add(result, vec3_scale(axes[i], local));
+        }
+        result
+    }
+
+    fn velocity_at(&self, point: Vector3<f32>) -> Vector3<f32> {
+        let r = vec3_sub(point, self.position);
+        vec3_add(self.velocity, vec3_cross(self.angular_velocity, r))
+    }
+
+    /**
+     * Sleeping bodies don't take impulses, the solver treats them like the
+     * static world.
+     */
+    fn apply_impulse(&mut self, impulse: Vector3<f32>, point: Vector3<f32>) {
+        if self.asleep {
+            return;
+        }
+        let r = vec3_sub(point, self.position);
+        self.velocity = vec3_add(self.velocity, vec3_scale(impulse, self.inverse_mass));
+        let angular = self.inverse_inertia_times(vec3_cross(r, impulse));
+        self.angular_velocity = vec3_add(self.angular_velocity, angular);
+    }
+
+    /**
+     * How hard it is to change the velocity at point along direction.
+     */
+    fn inverse_effective_mass(&self, point: Vector3<f32>, direction: Vector3<f32>) -> f32 {
+        if self.asleep {
+            return 0.0;
+        }
+        let r = vec3_sub(point, self.position);
+        let rn = vec3_cross(r, direction);
+        self.inverse_mass + vec3_dot(vec3_cross(self.inverse_inertia_times(rn), r), direction)
+    }
+
+    fn integrate(&mut self, dt: f32) {
+        self.position = vec3_add(self.position, vec3_scale(self.velocity, dt));
+        let w = self.angular_velocity;
+        let spin = quaternion::mul((0.0, w), self.orientation);
+        let orientation = quaternion::add(self.orientation, quaternion::scale(spin, 0.5 * dt));
+        self.orientation = quaternion::scale(orientation, 1.0 / quaternion::len(orientation));
+    }
+}
+
+/**
+ * A touching point between body a and body b, or the static world if b is
+ * None. The normal points from b towards a.
+ */
+struct BodyContact {
+    a: usize,
+    b: Option<usize>,
+    point: Vector3<f32>,
+    normal: Vector3<f32>,
+    tangents: [Vector3<f32>; 2],
+    depth: f32,
+    material: Material,
+    bounce: f32,
+    normal_impulse: f32,
+    tangent_impulses: [f32; 2],
+}
+
+/**
+ * Simulates the bodies for dt seconds against each other and the static
+ * colliders.
+ */
+pub fn step(bodies: &mut [RigidBody], colliders: &[Collider], dt: f32) {
+    for body in bodies.iter_mut() {
+        body.previous_position = body.position;
+        body.previous_orientation = body.orientation;
+        if !body.asleep {
+            body.velocity = vec3_add(body.velocity, vec3_scale(GRAVITY, dt));
+        }
+    }
+
+    let mut contacts = find_contacts(bodies, colliders);
+    // Sleeping bodies touched by moving ones wake up
+    for contact in contacts.iter() {
+        if let Some(b) = contact.b {
+            let a = contact.a;
+            if bodies[a].asleep && bodies[b].is_moving() {
+                bodies[a].wake();
+            }
+            if bodies[b].asleep && bodies[a].is_moving() {
+                bodies[b].wake();
+            }
+        }
+    }
+    contacts.retain(|c| !bodies[c.a].asleep || c.b.map_or(false, |b| !bodies[b].asleep));
+
+    for contact in contacts.iter_mut() {
+        let relative = relative_velocity(bodies, contact);
+        let normal_speed = vec3_dot(relative, contact.normal);
+        contact.bounce = if normal_speed < -BOUNCE_THRESHOLD {
+            -contact.material.restitution * normal_speed
+        }
+        else {
+            0.0
+        };
+    }
+
+    for _ in 0..SOLVER_ITERATIONS {
+        for contact in contacts.iter_mut() {
+            solve_contact(bodies, contact, dt);
+        }
+    }
+
+    for body in bodies.iter_mut() {
+        if body.asleep {
+            continue;
+        }
+        body.integrate(dt);
+        if !body.is_moving() {
+            body.still_time += dt;
+            if body.still_time > SLEEP_TIME {
+                body.asleep = true;
+                body.velocity = [0.0, 0.0, 0.0];
+                body.angular_velocity = [0.0, 0.0, 0.0];
+            }
+        }
+        else {
+            body.still_time = 0.0;
+        }
+    }
+}
+
+/**
+ * Lets the projectile bounce off the bodies it touches, pushing them with
+ * the same impulse. Returns whether it hit any.
+ */
+pub fn hit_projectile(bodies: &mut [RigidBody], projectile: &mut Projectile) -> bool {
+    let mut hit = false;
+    for body in bodies.iter_mut() {
+        let contact = match ::collision::sphere_obb(&projectile.sphere(), &body.obb()) {
+            Some(contact) => contact,
+            None => continue,
+        };
+        hit = true;
+        projectile.position = vec3_add(
+            projectile.position, vec3_scale(contact.normal, contact.depth)
+        );
+        let relative = vec3_sub(projectile.velocity, body.velocity_at(contact.point));
+        let normal_speed = vec3_dot(relative, contact.normal);
+        if normal_speed >= 0.0 {
+            continue;
+        }
+        // Asleep it would count as immovable
+        body.wake();
+        let material = projectile.material.combine(&body.material);
+        let impulse = -(1.0 + material.restitution) * normal_speed /
+            (1.0 / projectile.mass + body.inverse_effective_mass(contact.point, contact.normal));
+        projectile.velocity = vec3_add(
+            projectile.velocity, vec3_scale(contact.normal, impulse / projectile.mass)
+        );
+        body.apply_impulse(vec3_scale(contact.normal, -impulse), contact.point);
+    }
+    hit
+}
+
+fn relative_velocity(bodies: &[RigidBody], contact: &BodyContact) -> Vector3<f32> {
+    let va = bodies[contact.a].velocity_at(contact.point);
+    match contact.b {
+        Some(b) => vec3_sub(va, bodies[b].velocity_at(contact.point)),
+        None => va,
+    }
+}
+
+fn inverse_effective_mass(bodies: &[RigidBody], contact: &BodyContact, direction: Vector3<f32>)
+    -> f32
+{
+    let a = bodies[contact.a].inverse_effective_mass(contact.point, direction);
+    match contact.b {
+        Some(b) => a + bodies[b].inverse_effective_mass(contact.point, direction),
+        None => a,
+    }
+}
+
+fn apply(bodies: &mut [RigidBody], contact: &BodyContact, impulse: Vector3<f32>) {
+    bodies[contact.a].apply_impulse(impulse, contact.point);
+    if let Some(b) = contact.b {
+        bodies[b].apply_impulse(vec3_scale(impulse, -1.0), contact.point);
+    }
+}
+
+/**
+ * One round of sequential impulses: the normal impulse keeps the bodies
+ * apart, the friction impulses are bounded by it.
+ */
+fn solve_contact(bodies: &mut [RigidBody], contact: &mut BodyContact, dt: f32) {
+    let relative = relative_velocity(bodies, contact);
+    let normal_speed = vec3_dot(relative, contact.normal);
+    let bias = BAUMGARTE / dt * (contact.depth - PENETRATION_SLOP).max(0.0);
+    let target = bias.max(contact.bounce);
+    let k = inverse_effective_mass(bodies, contact, contact.normal);
+    let lambda = (target - normal_speed) / k;
+    let old = contact.normal_impulse;
+    contact.normal_impulse = (old + lambda).max(0.0);
+    let impulse = vec3_scale(contact.normal, contact.normal_impulse - old);
+    apply(bodies, contact, impulse);
+
+    let max_friction = contact.material.friction * contact.normal_impulse;
+    for i in 0..2 {
+        let tangent = contact.tangents[i];
+        let relative = relative_velocity(bodies, contact);
+        let k = inverse_effective_mass(bodies, contact, tangent);
+        let lambda = -vec3_dot(relative, tangent) / k;
+        let old = contact.tangent_impulses[i];
+        let new = (old + lambda).max(-max_friction).min(max_friction);
+        contact.tangent_impulses[i] = new;
+        apply(bodies, contact, vec3_scale(tangent, new - old));
+    }
+}
+
+fn find_contacts(bodies: &[RigidBody], colliders: &[Collider]) -> Vec<BodyContact> {
+    let mut contacts = Vec::new();
+    for (i, body) in bodies.iter().enumerate() {
+        let obb = body.obb();
+        for collider in colliders.iter() {
+            let material = body.material.combine(&collider.material);
+            match collider.shape {
+                Shape::Box(ref other) => {
+                    for &(point, normal, depth) in box_box(&obb, other).iter() {
+                        contacts.push(new_contact(i, None, point, normal, depth, material));
+                    }
+                },
+                Shape::Terrain(ref terrain) => {
+                    for corner in obb_corners(&obb).iter() {
+                        let height = terrain.height_at(corner[0], corner[2]);
+                        if corner[1] < height {
+                            let normal = terrain.normal_at(corner[0], corner[2]);
+                            let depth = (height - corner[1]) * normal[1];
+                            contacts.push(new_contact(i, None, *corner, normal, depth, material));
+                        }
+                    }
+                },
+                // Blocks don't get near the meshes of the scene
+                Shape::Mesh(_) => {},
+            }
+        }
+
+        for j in i + 1..bodies.len() {
+            let other = &bodies[j];
+            if body.asleep && other.asleep {
+                continue;
+            }
+            let reach = vec3_len(body.half_extents) + vec3_len(other.half_extents);
+            if vec3_len(vec3_sub(body.position, other.position)) > reach {
+                continue;
+            }
+            let material = body.material.combine(&other.material);
+            for &(point, normal, depth) in box_box(&obb, &other.obb()).iter() {
+                contacts.push(new_contact(i, Some(j), point, normal, depth, material));
+            }
+        }
+    }
+    contacts
+}
+
+fn obb_corners(obb: &Obb) -> [Vector3<f32>; 8] {
+    let h = obb.half_extents;
+    let mut corners = [[0.0; 3]; 8];
+    for i in 0..8 {
+        let sx = if i & 1 == 0 { -h[0] } else { h[0] };
+        let sy = if i & 2 == 0 { -h[1] } else { h[1] };
+        let sz = if i & 4 == 0 { -h[2] } else { h[2] };
+        corners[i] = vec3_add(obb.center, vec3_add(
+            vec3_scale(obb.axes[0], sx),
+            vec3_add(vec3_scale(obb.axes[1], sy), vec3_scale(obb.axes[2], sz))
+        ));
+    }
+    corners
+}
+
+/**
+ * Half the length of the box projected onto axis.
+ */
+fn projected_radius(obb: &Obb, axis: Vector3<f32>) -> f32 {
+    (0..3).map(|i| vec3_dot(obb.axes[i], axis).abs() * obb.half_extents[i]).sum()
+}
+
+/**
+ * Contacts between two boxes, the normal points from b towards a.
+ *
+ * The normal is the face axis the boxes overlap least along. The face of
+ * the other box turned most against it gets clipped to the sides of that
+ * reference face, the points of it below the reference face touch.
+ */
+fn box_box(a: &Obb, b: &Obb) -> Vec<(Vector3<f32>, Vector3<f32>, f32)> {
+    let offset = vec3_sub(a.center, b.center);
+    let mut normal = [0.0, 1.0, 0.0];
+    let mut overlap = ::std::f32::MAX;
+    let mut reference_is_b = true;
+    let mut reference_axis = 0;
+    for (i, axis) in a.axes.iter().chain(b.axes.iter()).enumerate() {
+        let distance = vec3_dot(offset, *axis);
+        let depth = projected_radius(a, *axis) + projected_radius(b, *axis) - distance.abs();
+        if depth <= 0.0 {
+            return Vec::new();
+        }
+        if depth < overlap {
+            overlap = depth;
+            normal = if distance < 0.0 { vec3_scale(*axis, -1.0) } else { *axis };
+            reference_is_b = i >= 3;
+            reference_axis = i % 3;
+        }
+    }
+
+    let (reference, incident, face_normal) = if reference_is_b {
+        (b, a, normal)
+    }
+    else {
+        (a, b, vec3_scale(normal, -1.0))
+    };
+    let face_offset = vec3_dot(reference.center, face_normal) +
+        reference.half_extents[reference_axis];
+
+    let mut points = incident_face(incident, face_normal);
+    for j in 0..3 {
+        if j == reference_axis {
+            continue;
+        }
+        let axis = reference.axes[j];
+        let center = vec3_dot(reference.center, axis);
+        let extent = reference.half_extents[j];
+        points = clip(&points, axis, center + extent);
+        points = clip(&points, vec3_scale(axis, -1.0), extent - center);
+    }
+
+    points.iter().filter_map(|point| {
+        let depth = face_offset - vec3_dot(*point, face_normal);
+        if depth > 0.0 { Some((*point, normal, depth)) } else { None }
+    }).collect()
+}
+
+/**
+ * Corners of the face of the box turned most against direction, in order
+ * around the face.
+ */
+fn incident_face(obb: &Obb, direction: Vector3<f32>) -> Vec<Vector3<f32>> {
+    let mut axis = 0;
+    for i in 1..3 {
+        if vec3_dot(obb.axes[i], direction).abs() > vec3_dot(obb.axes[axis], direction).abs() {
+            axis = i;
+        }
+    }
+    let sign = if vec3_dot(obb.axes[axis], direction) > 0.0 { -1.0 } else { 1.0 };
+    let center = vec3_add(
+        obb.center, vec3_scale(obb.axes[axis], sign * obb.half_extents[axis])
+    );
+    let u = (axis + 1) % 3;
+    let v = (axis + 2) % 3;
+    let eu = vec3_scale(obb.axes[u], obb.half_extents[u]);
+    let ev = vec3_scale(obb.axes[v], obb.half_extents[v]);
+    vec![
+        vec3_add(center, vec3_add(eu, ev)),
+        vec3_add(center, vec3_sub(eu, ev)),
+        vec3_sub(center, vec3_add(eu, ev)),
+        vec3_sub(center, vec3_sub(eu, ev)),
+    ]
+}
+
+/**
+ * Sutherland-Hodgman: the part of the polygon where dot(p, normal) <= offset.
+ */
+fn clip(polygon: &[Vector3<f32>], normal: Vector3<f32>, offset: f32) -> Vec<Vector3<f32>> {
+    let mut clipped = Vec::with_capacity(polygon.len() + 1);
+    for i in 0..polygon.len() {
+        let current = polygon[i];
+        let next = polygon[(i + 1) % polygon.len()];
+        let d_current = vec3_dot(current, normal) - offset;
+        let d_next = vec3_dot(next, normal) - offset;
+        if d_current <= 0.0 {
+            clipped.push(current);
+        }
+        if (d_current < 0.0) != (d_next < 0.0) && d_current != d_next {
+            let t = d_current / (d_current - d_next);
+            clipped.push(vec3_add(current, vec3_scale(vec3_sub(next, current), t)));
+        }
+    }
+    clipped
+}
+
+fn new_contact(
+    a: usize,
+    b: Option<usize>,
+    point: Vector3<f32>,
+    normal: Vector3<f32>,
+    depth: f32,
+    material: Material
+)
+    -> BodyContact
+{
+    let helper = if normal[0].abs() > 0.9 { [0.0, 1.0, 0.0] } else { [1.0, 0.0, 0.0] };
+    let first = vec3_cross(normal, helper);
+    let first = vec3_scale(first, 1.0 / vec3_len(first));
+    let second = vec3_cross(normal, first);
+    BodyContact {
+        a: a,
+        b: b,
+        point: point,
+        normal: normal,
+        tangents: [first, second],
+        depth: depth,
+        material: material,
+        bounce: 0.0,
+        normal_impulse: 0.0,
+        tangent_impulses: [0.0, 0.0],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use collision::{STONE, WOOD};
+    use mass::{STONE_DENSITY, WOOD_DENSITY};
+
+    fn block() -> RigidBody {
+        RigidBody::new_box([0.0, 1.0, 0.0], quaternion::id(), [0.5, 0.5, 0.5], WOOD_DENSITY, WOOD)
+    }
+
+    fn stone(velocity: Vector3<f32>) -> Projectile {
+        // Just touching the face of the block at x = 0.5
+        Projectile::new([0.65, 1.1, 0.0], velocity, 0.2, STONE_DENSITY, STONE)
+    }
+
+    fn momentum(projectile: &Projectile, body: &RigidBody) -> Vector3<f32> {
+        vec3_add(
+            vec3_scale(projectile.velocity, projectile.mass),
+            vec3_scale(body.velocity, 1.0 / body.inverse_mass)
+        )
+    }
+
+    #[test]
+    fn hits_wake_sleeping_blocks_and_keep_the_momentum() {
+        let mut bodies = [block()];
+        let mut projectile = stone([-20.0, -2.0, 1.0]);
+        assert!(bodies[0].asleep);
+        let before = momentum(&projectile, &bodies[0]);
+
+        assert!(hit_projectile(&mut bodies, &mut projectile));
+        assert!(!bodies[0].asleep);
+        let after = momentum(&projectile, &bodies[0]);
+        let error = vec3_len(vec3_sub(after, before));
+        assert!(error < 1e-3 * vec3_len(before), "{:?} became {:?}", before, after);
+        // Parting at half the speed they met with, as stone on wood bounces
+        let parting = projectile.velocity[0] - bodies[0].velocity_at([0.5, 1.1, 0.0])[0];
+        assert!((parting - 10.0).abs() < 1e-3, "parting at {} m/s", parting);
+        // The block got pushed away, the stone bounced back slower than it came
+        assert!(bodies[0].velocity[0] < 0.0);
+        assert!(projectile.velocity[0] > 0.0 && projectile.velocity[0] < 20.0);
+        // Hit above its center the block starts to tip over
+        assert!(vec3_len(bodies[0].angular_velocity) > 0.0);
+    }
+
+    #[test]
+    fn stones_moving_away_leave_blocks_alone() {
+        let mut bodies = [block()];
+        let mut projectile = stone([5.0, 0.0, 0.0]);
+        hit_projectile(&mut bodies, &mut projectile);
+        assert_eq!(bodies[0].velocity, [0.0; 3]);
+        assert_eq!(projectile.velocity, [5.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn moving_blocks_wake_the_ones_they_touch() {
+        let mut lower = block();
+        lower.position = [0.0, 0.5, 0.0];
+        let mut upper = block();
+        upper.position = [0.0, 1.49, 0.0];
+        upper.wake();
+        upper.velocity = [0.0, -1.0, 0.0];
+        let mut bodies = [lower, upper];
+        step(&mut bodies, &[], 1.0 / 120.0);
+        assert!(!bodies[0].asleep);
+        // Pushed down by the one falling onto it
+        assert!(bodies[0].velocity[1] < 0.0);
+    }
+}
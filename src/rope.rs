@@ -0,0 +1,196 @@
+use vecmath::{Vector3, vec3_add, vec3_sub, vec3_scale, vec3_cross, vec3_dot, vec3_len};
+use catapult::Vertex;
+use clock::lerp;
+
+// How much of the velocity is kept each step
+const DAMPING: f32 = 0.98;
+// Rounds of constraint solving per step, more make the rope stiffer
+const ITERATIONS: usize = 20;
+
+/**
+ * A rope simulated with Verlet integration. Both ends are pinned, the
+ * particles in between keep at most length / (particles - 1) apart, so the
+ * rope sags when the ends are closer than its length.
+ */
+pub struct Rope {
+    pub particles: Vec<Vector3<f32>>,
+    // Positions of the last step, they hold the velocity
+    pub previous: Vec<Vector3<f32>>,
+    pub length: f32,
+    pub gravity: Vector3<f32>,
+}
+
+impl Rope {
+    /**
+     * A straight rope with count particles from start to end.
+     */
+    pub fn new(start: Vector3<f32>, end: Vector3<f32>, count: usize, length: f32) -> Rope {
+        let particles = (0..count).map(|i| {
+            let t = i as f32 / (count - 1) as f32;
+            vec3_add(start, vec3_scale(vec3_sub(end, start), t))
+        }).collect::<Vec<_>>();
+        Rope {
+            previous: particles.clone(),
+            particles: particles,
+            length: length,
+            gravity: [0.0, -9.81, 0.0],
+        }
+    }
+
+    /**
+     * Moves the pinned ends to start and end and simulates dt seconds.
+     */
+    pub fn update(&mut self, dt: f32, start: Vector3<f32>, end: Vector3<f32>) {
+        let count = self.particles.len();
+        let old = self.particles.clone();
+        for i in 1..count - 1 {
+            let velocity = vec3_scale(vec3_sub(self.particles[i], self.previous[i]), DAMPING);
+            self.particles[i] = vec3_add(
+                self.particles[i], vec3_add(velocity, vec3_scale(self.gravity, dt * dt))
+            );
+        }
+        self.previous = old;
+        self.particles[0] = start;
+        self.particles[count - 1] = end;
+
+        let segment_length = self.length / (count - 1) as f32;
+        for _ in 0..ITERATIONS {
+            for i in 0..count - 1 {
+                let delta = vec3_sub(self.particles[i + 1], self.particles[i]);
+                let distance = vec3_len(delta);
+                // A rope only pulls, it doesn't push
+                if distance <= segment_length || distance == 0.0 {
+                    continue;
+                }
+                let correction = vec3_scale(delta, (distance - segment_length) / distance);
+                let first_pinned = i == 0;
+                let second_pinned = i + 1 == count - 1;
+                if first_pinned && !second_pinned {
+                    self.particles[i + 1] = vec3_sub(self.particles[i + 1], correction);
+                }
+                else if second_pinned && !first_pinned {
+                    self.particles[i] = vec3_add(self.particles[i], correction);
+                }
+                else if !first_pinned && !second_pinned {
+                    let half = vec3_scale(correction, 0.5);
+                    self.particles[i] = vec3_add(self.particles[i], half);
+                    self.particles[i + 1] = vec3_sub(self.particles[i + 1], half);
+                }
+            }
+        }
+    }
+
+    /**
+     * Particle positions between the last two steps.
+     */
+    pub fn interpolated(&self, alpha: f32) -> Vec<Vector3<f32>> {
+        self.previous.iter().zip(self.particles.iter()).map(|(p, c)| [
+            lerp(p[0], c[0], alpha),
+            lerp(p[1], c[1], alpha),
+            lerp(p[2], c[2], alpha),
+        ]).collect()
+    }
+}
+
+/**
+ * Vertices of a tube with the given radius along the points, one ring of
+ * sides vertices per point.
+ */
+pub fn gen_tube(points: &[Vector3<f32>], radius: f32, sides: usize) -> Vec<Vertex> {
+    let mut vertices = Vec::with_capacity(points.len() * sides);
+    for i in 0..points.len() {
+        let next = if i + 1 < points.len() { points[i + 1] } else { points[i] };
+        let prev = if i > 0 { points[i - 1] } else { points[i] };
+        let tangent = vec3_sub(next, prev);
+        let tangent = if vec3_len(tangent) > 0.0 { tangent } else { [1.0, 0.0, 0.0] };
+        // Any vector not parallel to the tangent gives a frame around it
+        let helper = if vec3_dot(tangent, [0.0, 1.0, 0.0]).abs() > 0.9 * vec3_len(tangent) {
+            [1.0, 0.0, 0.0]
+        }
+        else {
+            [0.0, 1.0, 0.0]
+        };
+        let side = vec3_cross(tangent, helper);
+        let side = vec3_scale(side, 1.0 / vec3_len(side));
+        let up = vec3_cross(side, tangent);
+        let up = vec3_scale(up, 1.0 / vec3_len(up));
+
+        for j in 0..sides {
+            let angle = j as f32 / sides as f32 * 2.0 * ::std::f32::consts::PI;
+            let offset = vec3_add(
+                vec3_scale(side, angle.cos() * radius), vec3_scale(up, angle.sin() * radius)
+            );
+            let p = vec3_add(points[i], offset);
+            vertices.push(Vertex {
+                position: (p[0], p[1], p[2]),
+                tex_coords: [j as f32 / sides as f32, i as f32 / points.len() as f32],
+            });
+        }
+    }
+    vertices
+}
+
+/**
+ * Indices for a tube made by gen_tube.
+ */
+pub fn tube_indices(point_count: usize, sides: usize) -> Vec<u16> {
+    let mut indices = Vec::with_capacity((point_count - 1) * sides * 6);
+    for i in 0..point_count - 1 {
+        for j in 0..sides {
+            let a = (i * sides + j) as u16;
+            let b = (i * sides + (j + 1) % sides) as u16;
+            let c = ((i + 1) * sides + j) as u16;
+            let d = ((i + 1) * sides + (j + 1) % sides) as u16;
+            indices.extend_from_slice(&[a, b, d, a, d, c]);
+        }
+    }
+    indices
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settle(rope: &mut Rope, start: Vector3<f32>, end: Vector3<f32>) {
+        for _ in 0..600 {
+            rope.update(1.0 / 120.0, start, end);
+        }
+    }
+
+    fn polyline_length(points: &[Vector3<f32>]) -> f32 {
+        points.windows(2).map(|pair| vec3_len(vec3_sub(pair[1], pair[0]))).sum()
+    }
+
+    #[test]
+    fn slack_ropes_sag_at_their_length() {
+        let (start, end) = ([0.0, 2.0, 0.0], [3.0, 2.0, 0.0]);
+        let mut rope = Rope::new(start, end, 16, 4.0);
+        settle(&mut rope, start, end);
+        assert!((polyline_length(&rope.particles) - 4.0).abs() < 0.05);
+        let lowest = rope.particles.iter().map(|p| p[1]).fold(2.0, f32::min);
+        assert!(lowest < 1.3, "sags down to {}", lowest);
+        // Hanging still, without swinging sideways
+        assert!(rope.particles.iter().all(|p| p[2].abs() < 1e-4));
+        assert!(rope.interpolated(0.0).iter().zip(rope.particles.iter())
+            .all(|(a, b)| vec3_len(vec3_sub(*a, *b)) < 1e-3));
+    }
+
+    #[test]
+    fn taut_ropes_run_straight() {
+        let (start, end) = ([0.0, 2.0, 0.0], [3.0, 0.0, 0.0]);
+        // Shorter than the 3.6 m between the ends, the rope can't sag
+        for &length in [3.0, 2.0].iter() {
+            let mut rope = Rope::new(start, [0.0, 0.0, 0.0], 16, length);
+            settle(&mut rope, start, end);
+            assert_eq!(rope.particles[0], start);
+            assert_eq!(rope.particles[15], end);
+            let direction = vec3_sub(end, start);
+            let direction = vec3_scale(direction, 1.0 / vec3_len(direction));
+            for p in rope.particles.iter() {
+                let offset = vec3_sub(*p, start);
+                let off_line = vec3_sub(offset, vec3_scale(direction, vec3_dot(offset, direction)));
+                assert!(vec3_len(off_line) < 0.02, "{:?} is off the line for {}", p, length);
+            }
+        }
+    }
+}
@@ -1,7 +1,10 @@
 use glium;
 use camera;
-use render::{perspective_matrix};
+use viewport::Viewports;
 use drawable::{Drawable};
+use world::World;
+use config::Config;
+use firing::FiringStatus;
 
 /**
  * Contains the global state of the program.
@@ -10,17 +13,13 @@ pub struct Settings<'a> {
     pub program: glium::Program,
     pub draw_params: glium::DrawParameters<'a>,
     pub camera: camera::CameraState,
+    pub viewports: Viewports,
     pub objects: Vec<Box<Drawable>>,
     pub light: [f32; 3],
+    pub world: World,
+    // Set once the catapult exists
+    pub firing_status: Option<FiringStatus>,
+    pub config: Config,
+    // How far rendering is between the last two simulation steps
+    pub alpha: f32,
 }
-
-impl<'a> Settings<'a> {
-    pub fn perspective_matrix(&self, target: &glium::Frame) -> [[f32; 4]; 4] {
-        perspective_matrix(target)
-    }
-
-    pub fn set_aspect_ratio(&mut self, aspect_ratio: f32) {
-        self.camera.set_aspect_ratio(aspect_ratio);
-    }
-}
-
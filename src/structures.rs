@@ -0,0 +1,209 @@
+use std::f32::consts::PI;
+use quaternion;
+use collision::WOOD;
+use mass::WOOD_DENSITY;
+use config::Config;
+use range::{RANGE_ORIGIN, RANGE_DIRECTION};
+use rigid_body::RigidBody;
+use terrain::Terrain;
+
+// Space left between the blocks, so they don't start out overlapping
+const GAP: f32 = 0.01;
+// Full size of a wall brick, the long side runs across the line of fire
+const BRICK_SIZE: [f32; 3] = [0.5, 0.5, 1.0];
+// Closes the ends of the shifted rows
+const HALF_BRICK_SIZE: [f32; 3] = [0.5, 0.5, 0.5];
+// Full size of a tower beam, three of them make a level
+const BEAM_SIZE: [f32; 3] = [1.6, 0.3, 0.5];
+// Sizes of the blocks, the index is the mesh of a body
+pub const BLOCK_SIZES: [[f32; 3]; 3] = [BRICK_SIZE, HALF_BRICK_SIZE, BEAM_SIZE];
+const BRICK: usize = 0;
+const HALF_BRICK: usize = 1;
+const BEAM: usize = 2;
+
+/**
+ * Where the structures stand, read from the [structures] section of the
+ * config file. Distances are measured like the ones of the target range.
+ */
+pub struct StructureConfig {
+    pub enabled: bool,
+    pub wall_distance: f32,
+    pub wall_width: u32,
+    pub wall_height: u32,
+    pub tower_distance: f32,
+    pub tower_levels: u32,
+}
+
+impl StructureConfig {
+    pub fn from_config(config: &Config) -> StructureConfig {
+        StructureConfig {
+            enabled: config.get("structures", "enabled", true),
+            wall_distance: config.get("structures", "wall_distance", 28.0),
+            wall_width: config.get("structures", "wall_width", 6),
+            wall_height: config.get("structures", "wall_height", 5),
+            tower_distance: config.get("structures", "tower_distance", 42.0),
+            tower_levels: config.get("structures", "tower_levels", 8),
+        }
+    }
+}
+
+/**
+ * Blocks of all structures in the config, standing on the terrain.
+ */
+pub fn build_structures(config: &StructureConfig, terrain: &Terrain) -> Vec<RigidBody> {
+    let mut bodies = Vec::new();
+    if !config.enabled {
+        return bodies;
+    }
+    let wall = along_range(config.wall_distance, terrain);
+    bodies.extend(build_wall(wall, config.wall_width, config.wall_height));
+    let tower = along_range(config.tower_distance, terrain);
+    bodies.extend(build_tower(tower, config.tower_levels));
+    bodies
+}
+
+/**
+ * A wall of bricks across the line of fire, every other row shifted by half
+ * a brick like real masonry and closed with half bricks at both ends.
+ */
+pub fn build_wall(base: [f32; 3], width: u32, height: u32) -> Vec<RigidBody> {
+    let pitch = BRICK_SIZE[2] + GAP;
+    let length = width as f32 * pitch - GAP;
+    let mut bricks = Vec::new();
+    for row in 0..height {
+        let y = base[1] + BRICK_SIZE[1] / 2.0 + row as f32 * (BRICK_SIZE[1] + GAP);
+        let mut z = base[2] - length / 2.0;
+        let mut add = |mesh: usize, z: &mut f32| {
+            let size = BLOCK_SIZES[mesh];
+            let half = [size[0] / 2.0, size[1] / 2.0, size[2] / 2.0];
+            let position = [base[0], y, *z + half[2]];
+            let mut brick = RigidBody::new_box(
+                position, quaternion::id(), half, WOOD_DENSITY, WOOD
+            );
+            brick.mesh = mesh;
+            bricks.push(brick);
+            *z += size[2] + GAP;
+        };
+        if row % 2 == 1 && width > 1 {
+            add(HALF_BRICK, &mut z);
+            for _ in 0..width - 1 {
+                add(BRICK, &mut z);
+            }
+            add(HALF_BRICK, &mut z);
+        }
+        else {
+            for _ in 0..width {
+                add(BRICK, &mut z);
+            }
+        }
+    }
+    bricks
+}
+
+/**
+ * A tower of three beams per level, each level turned by 90° against the
+ * one below.
+ */
+pub fn build_tower(base: [f32; 3], levels: u32) -> Vec<RigidBody> {
+    let half = [BEAM_SIZE[0] / 2.0, BEAM_SIZE[1] / 2.0, BEAM_SIZE[2] / 2.0];
+    let pitch = BEAM_SIZE[2] + GAP;
+    let mut beams = Vec::new();
+    for level in 0..levels {
+        let turned = level % 2 == 1;
+        let orientation = if turned {
+            quaternion::axis_angle([0.0, 1.0, 0.0], PI / 2.0)
+        }
+        else {
+            quaternion::id()
+        };
+        let y = base[1] + half[1] + level as f32 * (BEAM_SIZE[1] + GAP);
+        for i in 0..3 {
+            let offset = (i as f32 - 1.0) * pitch;
+            let position = if turned {
+                [base[0] + offset, y, base[2]]
+            }
+            else {
+                [base[0], y, base[2] + offset]
+            };
+            let mut beam = RigidBody::new_box(position, orientation, half, WOOD_DENSITY, WOOD);
+            beam.mesh = BEAM;
+            beams.push(beam);
+        }
+    }
+    beams
+}
+
+fn along_range(distance: f32, terrain: &Terrain) -> [f32; 3] {
+    let x = RANGE_ORIGIN[0] + RANGE_DIRECTION[0] * distance;
+    let z = RANGE_ORIGIN[2] + RANGE_DIRECTION[2] * distance;
+    [x, terrain.height_at(x, z), z]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::rc::Rc;
+    use collision::{Collider, Shape, GRASS};
+    use rigid_body::step;
+    use vecmath::vec3_sub;
+
+    const DT: f32 = 1.0 / 120.0;
+
+    fn settle(mut bodies: Vec<RigidBody>, seconds: f32) -> Vec<(RigidBody, [f32; 3])> {
+        let terrain = Terrain::from_config(&Config::parse("[terrain]\nhill_height = 0"));
+        let colliders = [Collider { shape: Shape::Terrain(Rc::new(terrain)), material: GRASS }];
+        let start = bodies.iter().map(|body| body.position).collect::<Vec<_>>();
+        for body in bodies.iter_mut() {
+            body.wake();
+        }
+        for _ in 0..(seconds / DT) as usize {
+            step(&mut bodies, &colliders, DT);
+        }
+        bodies.into_iter().zip(start.into_iter()).collect()
+    }
+
+    /**
+     * Every block fell asleep where it was built. They only sink a little
+     * while the gaps between them close and the contacts find their slop.
+     */
+    fn assert_in_place(settled: &[(RigidBody, [f32; 3])]) {
+        for &(ref body, start) in settled.iter() {
+            assert!(body.asleep, "{:?} still moving", body.position);
+            let moved = vec3_sub(body.position, start);
+            let sideways = (moved[0] * moved[0] + moved[2] * moved[2]).sqrt();
+            assert!(sideways < 0.03, "{:?} drifted {} from {:?}", body.position, sideways, start);
+            assert!(moved[1] <= 0.0 && -moved[1] < 0.01 + start[1] * 0.1,
+                    "{:?} sank {} from {:?}", body.position, -moved[1], start);
+        }
+    }
+
+    #[test]
+    fn towers_settle_and_fall_asleep_in_place() {
+        let settled = settle(build_tower([0.0, 0.0, 0.0], 8), 5.0);
+        assert_eq!(settled.len(), 24);
+        assert_in_place(&settled);
+    }
+
+    #[test]
+    fn walls_settle_and_fall_asleep_in_place() {
+        let settled = settle(build_wall([0.0, 0.0, 0.0], 6, 5), 5.0);
+        assert_eq!(settled.len(), 32);
+        assert_in_place(&settled);
+    }
+
+    #[test]
+    fn walls_shift_every_other_row() {
+        let wall = build_wall([0.0, 0.0, 0.0], 4, 2);
+        assert_eq!(wall.iter().map(|brick| brick.mesh).collect::<Vec<_>>(),
+                   vec![BRICK, BRICK, BRICK, BRICK, HALF_BRICK, BRICK, BRICK, BRICK, HALF_BRICK]);
+        // Both rows are equally long
+        let ends = |row: &[RigidBody]| (
+            row[0].position[2] - row[0].half_extents[2],
+            row[row.len() - 1].position[2] + row[row.len() - 1].half_extents[2]
+        );
+        let (first, second) = (ends(&wall[..4]), ends(&wall[4..]));
+        assert!((first.0 - second.0).abs() < 1e-4);
+        // The shifted row has one more gap
+        assert!((second.1 - first.1 - GAP).abs() < 1e-4);
+    }
+}
@@ -0,0 +1,216 @@
+use glium;
+use glium::backend::glutin_backend;
+use glium::VertexBuffer;
+use glium::IndexBuffer;
+use glium::index::PrimitiveType::TrianglesList;
+use std::rc::Rc;
+use vecmath::{Vector3, vec3_scale, vec3_len};
+use catapult::{Normal, Vertex, NO_NORMALS};
+use collision::{Aabb, GRASS};
+use config::Config;
+use drawable::{DrawObject, RenderData};
+use range::{RANGE_ORIGIN, RANGE_DIRECTION};
+use render::DEFAULT_MATRIX;
+
+// The terrain covers SIZE x SIZE meters around the origin
+const SIZE: f32 = 400.0;
+const CELL_SIZE: f32 = 2.0;
+// Meters per repetition of the texture
+const TEXTURE_SIZE: f32 = 4.0;
+
+/**
+ * Rolling hills as a grid of heights. Between the grid points the ground is
+ * made of two triangles per cell, split along the diagonal from the low x,
+ * low z corner, the same way it is drawn.
+ *
+ * Around the catapult and along the line of fire the ground is flat, so the
+ * range and the structures stand level.
+ */
+#[derive(Debug)]
+pub struct Terrain {
+    // Number of cells along each side
+    pub cells: usize,
+    pub cell_size: f32,
+    // World coordinates of the grid point (0, 0)
+    pub origin: [f32; 2],
+    // (cells + 1)² heights, row by row along x
+    heights: Vec<f32>,
+}
+
+impl Terrain {
+    pub fn from_config(config: &Config) -> Terrain {
+        let hill_height = config.get("terrain", "hill_height", 3.0f32);
+        let hill_size = config.get("terrain", "hill_size", 25.0f32).max(1.0);
+        let flat_width = config.get("terrain", "flat_width", 15.0f32);
+        let flat_length = config.get("terrain", "flat_length", 70.0f32);
+
+        let cells = (SIZE / CELL_SIZE) as usize;
+        let origin = [-SIZE / 2.0, -SIZE / 2.0];
+        let mut heights = Vec::with_capacity((cells + 1) * (cells + 1));
+        for j in 0..cells + 1 {
+            for i in 0..cells + 1 {
+                let x = origin[0] + i as f32 * CELL_SIZE;
+                let z = origin[1] + j as f32 * CELL_SIZE;
+                let hills = 0.5 * (x / hill_size).sin() * (z / hill_size * 0.8).cos() +
+                    0.3 * ((x + z) / (hill_size * 0.5) + 1.3).sin() +
+                    0.2 * ((x - 2.0 * z) / (hill_size * 0.35)).cos();
+                let distance = distance_to_range(x, z, flat_length);
+                let t = ((distance - flat_width) / flat_width).max(0.0).min(1.0);
+                let blend = t * t * (3.0 - 2.0 * t);
+                heights.push(hill_height * (hills + 1.0) * 0.5 * blend);
+            }
+        }
+
+        Terrain {
+            cells: cells,
+            cell_size: CELL_SIZE,
+            origin: origin,
+            heights: heights,
+        }
+    }
+
+    fn height(&self, i: usize, j: usize) -> f32 {
+        self.heights[j * (self.cells + 1) + i]
+    }
+
+    fn position(&self, i: usize, j: usize) -> Vector3<f32> {
+        [
+            self.origin[0] + i as f32 * self.cell_size,
+            self.height(i, j),
+            self.origin[1] + j as f32 * self.cell_size,
+        ]
+    }
+
+    /**
+     * The cell containing (x, z) and where in it the point is, from 0.0 to
+     * 1.0 along both axes. Points outside get clamped to the border cells.
+     */
+    fn locate(&self, x: f32, z: f32) -> (usize, usize, f32, f32) {
+        let gx = ((x - self.origin[0]) / self.cell_size).max(0.0);
+        let gz = ((z - self.origin[1]) / self.cell_size).max(0.0);
+        let i = (gx as usize).min(self.cells - 1);
+        let j = (gz as usize).min(self.cells - 1);
+        let fx = (gx - i as f32).min(1.0);
+        let fz = (gz - j as f32).min(1.0);
+        (i, j, fx, fz)
+    }
+
+    /**
+     * Slopes of the triangle under (x, z): how much the height changes per
+     * meter along x and along z.
+     */
+    fn slopes(&self, x: f32, z: f32) -> (f32, f32) {
+        let (i, j, fx, fz) = self.locate(x, z);
+        let h00 = self.height(i, j);
+        let h10 = self.height(i + 1, j);
+        let h01 = self.height(i, j + 1);
+        let h11 = self.height(i + 1, j + 1);
+        if fx >= fz {
+            ((h10 - h00) / self.cell_size, (h11 - h10) / self.cell_size)
+        }
+        else {
+            ((h11 - h01) / self.cell_size, (h01 - h00) / self.cell_size)
+        }
+    }
+
+    pub fn height_at(&self, x: f32, z: f32) -> f32 {
+        let (i, j, fx, fz) = self.locate(x, z);
+        let (dx, dz) = self.slopes(x, z);
+        self.height(i, j) + (fx * dx + fz * dz) * self.cell_size
+    }
+
+    pub fn normal_at(&self, x: f32, z: f32) -> Vector3<f32> {
+        let (dx, dz) = self.slopes(x, z);
+        let normal = [-dx, 1.0, -dz];
+        vec3_scale(normal, 1.0 / vec3_len(normal))
+    }
+
+    /**
+     * The triangles of all cells overlapping the given area.
+     */
+    pub fn triangles_in(&self, min: [f32; 2], max: [f32; 2]) -> Vec<[Vector3<f32>; 3]> {
+        let (i0, j0, _, _) = self.locate(min[0], min[1]);
+        let (i1, j1, _, _) = self.locate(max[0], max[1]);
+        let mut triangles = Vec::new();
+        for j in j0..j1 + 1 {
+            for i in i0..i1 + 1 {
+                let p00 = self.position(i, j);
+                let p10 = self.position(i + 1, j);
+                let p01 = self.position(i, j + 1);
+                let p11 = self.position(i + 1, j + 1);
+                triangles.push([p00, p10, p11]);
+                triangles.push([p00, p11, p01]);
+            }
+        }
+        triangles
+    }
+
+    pub fn bounds(&self) -> Aabb {
+        let (low, high) = self.heights.iter()
+            .fold((0.0f32, 0.0f32), |(low, high), &h| (low.min(h), high.max(h)));
+        let far = self.cells as f32 * self.cell_size;
+        Aabb::new(
+            (self.origin[0], low, self.origin[1]),
+            (self.origin[0] + far, high, self.origin[1] + far)
+        )
+    }
+}
+
+/**
+ * Horizontal distance of (x, z) from the line of fire, from the catapult up
+ * to length meters downrange.
+ */
+fn distance_to_range(x: f32, z: f32, length: f32) -> f32 {
+    let dx = x - RANGE_ORIGIN[0];
+    let dz = z - RANGE_ORIGIN[2];
+    let along = (dx * RANGE_DIRECTION[0] + dz * RANGE_DIRECTION[2]).max(0.0).min(length);
+    let nearest_x = RANGE_DIRECTION[0] * along;
+    let nearest_z = RANGE_DIRECTION[2] * along;
+    ((dx - nearest_x) * (dx - nearest_x) + (dz - nearest_z) * (dz - nearest_z)).sqrt()
+}
+
+/**
+ * The mesh of the terrain.
+ */
+pub fn gen_terrain(
+    display: &glutin_backend::GlutinFacade,
+    texture: &Rc<glium::texture::Texture2d>,
+    terrain: &Terrain
+)
+    -> DrawObject<Vertex, Normal, u16>
+{
+    let points = terrain.cells + 1;
+    let mut vertices = Vec::with_capacity(points * points);
+    for j in 0..points {
+        for i in 0..points {
+            let p = terrain.position(i, j);
+            vertices.push(Vertex {
+                position: (p[0], p[1], p[2]),
+                tex_coords: [p[0] / TEXTURE_SIZE, p[2] / TEXTURE_SIZE],
+            });
+        }
+    }
+    let mut indices = Vec::with_capacity(terrain.cells * terrain.cells * 6);
+    for j in 0..terrain.cells {
+        for i in 0..terrain.cells {
+            let p00 = (j * points + i) as u16;
+            let p10 = (j * points + i + 1) as u16;
+            let p01 = ((j + 1) * points + i) as u16;
+            let p11 = ((j + 1) * points + i + 1) as u16;
+            indices.extend_from_slice(&[p00, p10, p11, p00, p11, p01]);
+        }
+    }
+
+    DrawObject {
+        data: RenderData {
+            positions: VertexBuffer::new(display, &vertices).unwrap(),
+            normals: VertexBuffer::new(display, &NO_NORMALS).unwrap(),
+            indices: IndexBuffer::new(display, TrianglesList, &indices).unwrap(),
+        },
+        model_matrix: DEFAULT_MATRIX,
+        texture: texture.clone(),
+        children: Vec::new(),
+        bounds: terrain.bounds(),
+        material: GRASS,
+    }
+}
@@ -0,0 +1,267 @@
+extern crate glium;
+use std::f32::consts::PI;
+use quaternion;
+use quaternion::Quaternion;
+use vecmath;
+use camera::{orientation_from, view_matrix, CameraState, FRAME_MARGIN};
+use collision::Aabb;
+use input::{Action, Input};
+use projection::{Projection, ProjectionMode};
+
+// Pixels between the viewports
+const GAP: u32 = 2;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum View {
+    // Looking down, the front of the catapult at the bottom
+    Top,
+    // Looking at the front of the catapult, along the x axis
+    Front,
+    // Looking at the catapult from the right, along the z axis
+    Side,
+    // The free camera everything else controls
+    Perspective,
+}
+
+impl View {
+    /**
+     * Yaw and pitch of the fixed views, see FixedView::look_at.
+     */
+    fn direction(&self) -> (f32, f32) {
+        match *self {
+            View::Top => (0.0, -PI / 2.0),
+            View::Front => (0.0, 0.0),
+            View::Side => (PI / 2.0, 0.0),
+            View::Perspective => (0.0, 0.0),
+        }
+    }
+}
+
+/**
+ * The camera of a fixed view. It only moves when the selection does, so
+ * there is nothing to interpolate.
+ */
+#[derive(Copy, Clone, Debug)]
+pub struct FixedView {
+    pub position: [f32; 3],
+    pub orientation: Quaternion<f32>,
+    pub projection: Projection,
+}
+
+impl FixedView {
+    /**
+     * Looks at the bounds from the direction yaw and pitch say, from just
+     * far enough away to see all of them in a viewport with the aspect
+     * ratio. The pitch isn't limited, so it can look straight down.
+     */
+    pub fn look_at(&mut self, yaw: f32, pitch: f32, bounds: &Aabb, aspect_ratio: f32) {
+        let radius = vecmath::vec3_len(bounds.half_extents());
+        let distance = self.projection.fit(radius * FRAME_MARGIN, aspect_ratio);
+        self.orientation = orientation_from(yaw, pitch);
+        let forward = quaternion::rotate_vector(self.orientation, [1.0, 0.0, 0.0]);
+        self.position = vecmath::vec3_sub(bounds.center(), vecmath::vec3_scale(forward, distance));
+    }
+
+    pub fn view_matrix(&self) -> [[f32; 4]; 4] {
+        let p = self.position;
+        view_matrix((p[0], p[1], p[2]), self.orientation)
+    }
+}
+
+/**
+ * What one viewport gets drawn with: the view and projection of its camera
+ * and where in the window it goes.
+ */
+#[derive(Copy, Clone, Debug)]
+pub struct Viewport {
+    pub view: [[f32; 4]; 4],
+    pub projection: [[f32; 4]; 4],
+    pub rect: glium::Rect,
+}
+
+/**
+ * Splits the window CAD-style, for checking how the parts line up.
+ * split_view toggles four viewports: top, front and side views, which are
+ * orthographic and always show the selected part, and the free camera.
+ * maximize_view maximizes the viewport under the cursor and brings all four
+ * back again.
+ *
+ * Input always goes to the free camera.
+ */
+pub struct Viewports {
+    split: bool,
+    maximized: Option<View>,
+    fixed_views: Vec<(View, FixedView)>,
+    cursor: (i32, i32),
+    window_size: (u32, u32),
+}
+
+impl Viewports {
+    pub fn new(projection: Projection) -> Viewports {
+        let fixed_view = FixedView {
+            position: [0.0, 0.0, 0.0],
+            orientation: quaternion::id(),
+            projection: Projection { mode: ProjectionMode::Orthographic, .. projection },
+        };
+        Viewports {
+            split: false,
+            maximized: None,
+            fixed_views: [View::Top, View::Front, View::Side].iter()
+                .map(|&view| (view, fixed_view))
+                .collect(),
+            cursor: (0, 0),
+            window_size: (1024, 768),
+        }
+    }
+
+    pub fn set_window_size(&mut self, width: u32, height: u32) {
+        self.window_size = (width, height);
+    }
+
+    /**
+     * The fixed view, None for the free one.
+     */
+    pub fn fixed_view(&self, view: View) -> Option<&FixedView> {
+        self.fixed_views.iter().find(|&&(v, _)| v == view).map(|&(_, ref fixed)| fixed)
+    }
+
+    /**
+     * Everything to draw the viewports of a window of the size with, the
+     * free one seen by the camera between its last two simulation steps.
+     */
+    pub fn viewports(&self, camera: &CameraState, alpha: f32, width: u32, height: u32)
+        -> Vec<Viewport>
+    {
+        self.layout(width, height).into_iter().map(|(view, rect)| {
+            let aspect_ratio = rect.width as f32 / rect.height.max(1) as f32;
+            let (view, projection) = match self.fixed_view(view) {
+                Some(fixed) => (fixed.view_matrix(), fixed.projection.matrix(aspect_ratio)),
+                None => (
+                    camera.get_interpolated_view(alpha),
+                    camera.get_interpolated_projection(aspect_ratio, alpha)
+                ),
+            };
+            Viewport { view: view, projection: projection, rect: rect }
+        }).collect()
+    }
+
+    /**
+     * The views to draw and where, in pixels of a window of the size.
+     */
+    pub fn layout(&self, width: u32, height: u32) -> Vec<(View, glium::Rect)> {
+        let full = glium::Rect { left: 0, bottom: 0, width: width, height: height };
+        if !self.split {
+            return vec![(View::Perspective, full)];
+        }
+        if let Some(view) = self.maximized {
+            return vec![(view, full)];
+        }
+        let half_width = width.saturating_sub(GAP) / 2;
+        let half_height = height.saturating_sub(GAP) / 2;
+        let right = width - half_width;
+        let top = height - half_height;
+        let rect = |left, bottom| glium::Rect {
+            left: left, bottom: bottom, width: half_width, height: half_height
+        };
+        vec![
+            (View::Top, rect(0, top)),
+            (View::Perspective, rect(right, top)),
+            (View::Front, rect(0, 0)),
+            (View::Side, rect(right, 0)),
+        ]
+    }
+
+    /**
+     * Points the fixed views at the bounds, from far enough away to see all
+     * of them.
+     */
+    pub fn update(&mut self, bounds: Option<Aabb>) {
+        let bounds = match bounds {
+            Some(bounds) => bounds,
+            None => return,
+        };
+        let (width, height) = self.window_size;
+        let layout = self.layout(width, height);
+        for &mut (view, ref mut fixed) in self.fixed_views.iter_mut() {
+            let aspect_ratio = match layout.iter().find(|&&(v, _)| v == view) {
+                Some(&(_, rect)) => rect.width as f32 / rect.height.max(1) as f32,
+                None => width as f32 / height.max(1) as f32,
+            };
+            let (yaw, pitch) = view.direction();
+            fixed.look_at(yaw, pitch, &bounds, aspect_ratio);
+        }
+    }
+
+    fn view_under_cursor(&self) -> View {
+        let (width, height) = self.window_size;
+        // The cursor counts from the top, viewports from the bottom
+        let x = self.cursor.0.max(0) as u32;
+        let y = height.saturating_sub(self.cursor.1.max(0) as u32);
+        self.layout(width, height).into_iter()
+            .find(|&(_, rect)| {
+                x >= rect.left && x < rect.left + rect.width &&
+                y >= rect.bottom && y < rect.bottom + rect.height
+            })
+            .map_or(View::Perspective, |(view, _)| view)
+    }
+
+    pub fn process_input(&mut self, input: &Input) {
+        match *input {
+            Input::Pressed(Action::SplitView) => {
+                self.split = !self.split;
+                self.maximized = None;
+            },
+            Input::Pressed(Action::MaximizeView) => {
+                if self.split {
+                    self.maximized = match self.maximized {
+                        Some(_) => None,
+                        None => Some(self.view_under_cursor()),
+                    };
+                }
+            },
+            Input::MouseMoved(x, y) => self.cursor = (x, y),
+            Input::Resized(width, height) => self.set_window_size(width, height),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use config::Config;
+    use matrix::transform_point;
+
+    fn viewports() -> Viewports {
+        Viewports::new(Projection::from_config(&Config::parse("")))
+    }
+
+    #[test]
+    fn split_shows_four_views_and_maximizes_the_one_under_the_cursor() {
+        let mut viewports = viewports();
+        assert_eq!(viewports.layout(1024, 768).len(), 1);
+        viewports.process_input(&Input::Pressed(Action::SplitView));
+        let layout = viewports.layout(1024, 768);
+        assert_eq!(layout.len(), 4);
+        for &(_, rect) in layout.iter() {
+            assert!(rect.left + rect.width <= 1024 && rect.bottom + rect.height <= 768);
+        }
+        // The top left quarter, counted from the top of the window
+        viewports.process_input(&Input::MouseMoved(100, 100));
+        viewports.process_input(&Input::Pressed(Action::MaximizeView));
+        assert_eq!(viewports.layout(1024, 768)[0].0, View::Top);
+    }
+
+    #[test]
+    fn top_view_looks_down_at_the_selection() {
+        let mut viewports = viewports();
+        let bounds = Aabb::new((1.0, 0.0, 2.0), (3.0, 2.0, 6.0));
+        viewports.update(Some(bounds));
+        let top = viewports.fixed_view(View::Top).unwrap();
+        let forward = quaternion::rotate_vector(top.orientation, [1.0, 0.0, 0.0]);
+        assert!(forward[1] < -0.999, "{:?}", forward);
+        let center = transform_point(top.view_matrix(), bounds.center());
+        assert!(center[0].abs() < 1e-4 && center[1].abs() < 1e-4, "{:?}", center);
+        assert!(viewports.fixed_view(View::Perspective).is_none());
+    }
+}
@@ -0,0 +1,283 @@
+use glium;
+use glium::backend::glutin_backend;
+use glium::VertexBuffer;
+use glium::IndexBuffer;
+use glium::index::PrimitiveType::TrianglesList;
+use std::rc::Rc;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use catapult::{gen_box, Normal, Vertex, NO_NORMALS};
+use clock::lerp;
+use collision::{Aabb, Collider, Shape, TriangleMesh, GRASS, STONE, WOOD};
+use drawable::{Drawable, DrawObject, RenderData};
+use firing::FiringEvent;
+use mass::STONE_DENSITY;
+use matrix::{mul_matrices, rot_matrix_by, translation_matrix};
+use projectile::Projectile;
+use quaternion;
+use rigid_body;
+use rigid_body::RigidBody;
+use structures::{build_structures, StructureConfig, BLOCK_SIZES};
+use render::DEFAULT_MATRIX;
+use state::Settings;
+use viewport::Viewport;
+use atmosphere::Atmosphere;
+use config::Config;
+use terrain::{gen_terrain, Terrain};
+
+const STONE_RADIUS: f32 = 0.4;
+// Oldest projectiles get removed when there are more
+const MAX_PROJECTILES: usize = 32;
+
+/**
+ * Things happening to the projectiles.
+ */
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum WorldEvent {
+    // A projectile touched something for the first time after being thrown
+    Landed { projectile: u64, position: [f32; 3] },
+}
+
+/**
+ * Everything in the scene that isn't the catapult: the ground, props, the
+ * thrown projectiles and the blocks of the structures they can knock down.
+ */
+pub struct World {
+    pub projectiles: Vec<Projectile>,
+    pub bodies: Vec<RigidBody>,
+    // Static shapes of the world itself
+    pub colliders: Vec<Collider>,
+    pub atmosphere: Atmosphere,
+    pub terrain: Rc<Terrain>,
+    ground: DrawObject<Vertex, Normal, u16>,
+    props: Vec<DrawObject<Vertex, Normal, u16>>,
+    stone: DrawObject<Vertex, Normal, u16>,
+    // One mesh per block size, indexed by the mesh of a body
+    blocks: Vec<DrawObject<Vertex, Normal, u16>>,
+    catapult_events: Vec<Receiver<FiringEvent>>,
+    subscribers: Vec<Sender<WorldEvent>>,
+    // Id of the next projectile spawned
+    next_projectile: u64,
+}
+
+impl World {
+    /**
+     * Spawns a projectile for every one the catapult releases.
+     */
+    pub fn listen(&mut self, events: Receiver<FiringEvent>) {
+        self.catapult_events.push(events);
+    }
+
+    /**
+     * Returns a receiver getting all events emitted from now on.
+     */
+    pub fn subscribe(&mut self) -> Receiver<WorldEvent> {
+        let (sender, receiver) = channel();
+        self.subscribers.push(sender);
+        receiver
+    }
+
+    /**
+     * The static colliders, the blocks and the dynamic colliders of the
+     * other objects: everything solid in the scene.
+     */
+    pub fn obstacles(&self, dynamic_colliders: &[Collider]) -> Vec<Collider> {
+        let mut obstacles = self.colliders.clone();
+        obstacles.extend_from_slice(dynamic_colliders);
+        obstacles.extend(self.bodies.iter().map(|body| Collider {
+            shape: Shape::Box(body.obb()),
+            material: body.material,
+        }));
+        obstacles
+    }
+
+    pub fn spawn(&mut self, position: [f32; 3], velocity: [f32; 3]) {
+        if self.projectiles.len() >= MAX_PROJECTILES {
+            self.projectiles.remove(0);
+        }
+        let mut projectile = Projectile::new(
+            position, velocity, STONE_RADIUS, STONE_DENSITY, STONE
+        );
+        projectile.id = self.next_projectile;
+        self.next_projectile += 1;
+        self.projectiles.push(projectile);
+    }
+
+    /**
+     * Advances the projectiles and blocks by dt seconds. The dynamic colliders are the
+     * ones of the other objects in the scene.
+     */
+    pub fn update(&mut self, dt: f32, dynamic_colliders: &[Collider]) {
+        let mut released = Vec::new();
+        for events in self.catapult_events.iter() {
+            for event in events.try_iter() {
+                if let FiringEvent::ReleasedProjectile { position, velocity } = event {
+                    released.push((position, velocity));
+                }
+            }
+        }
+        for (position, velocity) in released {
+            self.spawn(position, velocity);
+        }
+
+        self.atmosphere.update(dt);
+        let mut colliders = self.colliders.clone();
+        colliders.extend_from_slice(dynamic_colliders);
+        let mut events = Vec::new();
+        for projectile in self.projectiles.iter_mut() {
+            let contacts = projectile.update(dt, &colliders, &self.atmosphere);
+            let hit_block = rigid_body::hit_projectile(&mut self.bodies, projectile);
+            if !projectile.landed && (hit_block || !contacts.is_empty()) {
+                projectile.landed = true;
+                let position = match contacts.first() {
+                    Some(contact) => contact.point,
+                    None => projectile.position,
+                };
+                events.push(WorldEvent::Landed { projectile: projectile.id, position: position });
+            }
+        }
+        rigid_body::step(&mut self.bodies, &colliders, dt);
+        for event in events {
+            self.subscribers.retain(|sender| sender.send(event).is_ok());
+        }
+    }
+
+    pub fn draw(&self, settings: &Settings, viewport: &Viewport, target: &mut glium::Frame)
+        -> Result<(), glium::DrawError>
+    {
+        self.ground.draw(settings, viewport, target, DEFAULT_MATRIX)?;
+        for prop in self.props.iter() {
+            prop.draw(settings, viewport, target, DEFAULT_MATRIX)?;
+        }
+        for projectile in self.projectiles.iter() {
+            let p = projectile.previous_position;
+            let position = [
+                lerp(p[0], projectile.position[0], settings.alpha),
+                lerp(p[1], projectile.position[1], settings.alpha),
+                lerp(p[2], projectile.position[2], settings.alpha),
+            ];
+            let matrix = mul_matrices(
+                translation_matrix(position),
+                rot_matrix_by(&projectile.orientation, [0.0, 0.0, 0.0])
+            );
+            self.stone.draw(settings, viewport, target, matrix)?;
+        }
+        for body in self.bodies.iter() {
+            let block = match self.blocks.get(body.mesh) {
+                Some(block) => block,
+                None => continue,
+            };
+            let p = body.previous_position;
+            let position = [
+                lerp(p[0], body.position[0], settings.alpha),
+                lerp(p[1], body.position[1], settings.alpha),
+                lerp(p[2], body.position[2], settings.alpha),
+            ];
+            let q = body.previous_orientation;
+            let orientation = quaternion::add(
+                quaternion::scale(q, 1.0 - settings.alpha),
+                quaternion::scale(body.orientation, settings.alpha)
+            );
+            let orientation = quaternion::scale(orientation, 1.0 / quaternion::len(orientation));
+            let matrix = mul_matrices(
+                translation_matrix(position),
+                rot_matrix_by(&orientation, [0.0, 0.0, 0.0])
+            );
+            block.draw(settings, viewport, target, matrix)?;
+        }
+        Ok(())
+    }
+}
+
+/**
+ * Creates the terrain, a ramp next to the line of fire and the structures
+ * in it.
+ */
+pub fn init_world(
+    display: &glutin_backend::GlutinFacade,
+    texture: Rc<glium::texture::Texture2d>,
+    config: &Config
+)
+    -> World
+{
+    let ramp_positions = gen_ramp((-22.0, 0.0, 6.0), (-18.0, 1.5, 11.5));
+    let ramp_vertices = ramp_positions.iter().map(|p| [p.position.0, p.position.1, p.position.2])
+        .collect::<Vec<_>>();
+    let ramp = DrawObject {
+        data: RenderData {
+            positions: VertexBuffer::new(display, &ramp_positions).unwrap(),
+            normals: VertexBuffer::new(display, &NO_NORMALS).unwrap(),
+            indices: IndexBuffer::new(display, TrianglesList, &RAMP_INDICES).unwrap(),
+        },
+        model_matrix: DEFAULT_MATRIX,
+        texture: texture.clone(),
+        children: Vec::new(),
+        bounds: Aabb::new((-22.0, 0.0, 6.0), (-18.0, 1.5, 11.5)),
+        material: WOOD,
+    };
+    let r = STONE_RADIUS;
+    let terrain = Rc::new(Terrain::from_config(config));
+    let blocks = BLOCK_SIZES.iter().map(|size| {
+        let h = [size[0] / 2.0, size[1] / 2.0, size[2] / 2.0];
+        gen_box(display, &texture, (-h[0], -h[1], -h[2]), (h[0], h[1], h[2]))
+    }).collect();
+
+    World {
+        projectiles: Vec::new(),
+        bodies: build_structures(&StructureConfig::from_config(config), &terrain),
+        colliders: vec![
+            Collider {
+                shape: Shape::Terrain(terrain.clone()),
+                material: GRASS,
+            },
+            Collider {
+                shape: Shape::Mesh(TriangleMesh::from_indexed(
+                    &ramp_vertices, &RAMP_INDICES, DEFAULT_MATRIX
+                )),
+                material: WOOD,
+            },
+        ],
+        atmosphere: Atmosphere::from_config(config, terrain.clone()),
+        ground: gen_terrain(display, &texture, &terrain),
+        terrain: terrain,
+        props: vec![ramp],
+        stone: DrawObject {
+            material: STONE,
+            .. gen_box(display, &texture, (-r, -r, -r), (r, r, r))
+        },
+        blocks: blocks,
+        catapult_events: Vec::new(),
+        subscribers: Vec::new(),
+        next_projectile: 0,
+    }
+}
+
+/*
+ *  Generate vertices of a ramp rising from max.0 to min.0
+ */
+fn gen_ramp(min: (f32, f32, f32), max: (f32, f32, f32)) -> [Vertex; 6] {
+    [
+        // Low edge and the bottom of the high edge, front side
+        Vertex { position: (max.0, min.1, min.2), tex_coords: [0.0, 0.0] },
+        Vertex { position: (min.0, min.1, min.2), tex_coords: [1.0, 0.0] },
+        Vertex { position: (min.0, max.1, min.2), tex_coords: [1.0, 1.0] },
+        // Back side
+        Vertex { position: (max.0, min.1, max.2), tex_coords: [0.0, 1.0] },
+        Vertex { position: (min.0, min.1, max.2), tex_coords: [1.0, 1.0] },
+        Vertex { position: (min.0, max.1, max.2), tex_coords: [0.0, 0.0] },
+    ]
+}
+
+const RAMP_INDICES: [u16; 24] = [
+    // Bottom
+    0, 1, 4,
+    0, 4, 3,
+    // Slope
+    0, 5, 2,
+    0, 3, 5,
+    // High end
+    1, 2, 5,
+    1, 5, 4,
+    // Sides
+    0, 1, 2,
+    3, 5, 4,
+];